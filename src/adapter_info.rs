@@ -0,0 +1,256 @@
+//! Structured description of an [adapter][crate::devices::Adapter].
+//!
+//! [AdapterInfo] decodes `DXGI_ADAPTER_DESC3` into plain rust types: the PCI ids with a [Vendor],
+//! memory sizes, whether the adapter is a software or remote one and its preemption granularity.
+//! The decoding doesn't call into DXGI, so it can be tested anywhere.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::adapter_info::Vendor;
+//!
+//! let info = adapter.info()?;
+//! let encoder = match info.vendor {
+//!     Vendor::Nvidia => "nvenc",
+//!     Vendor::Amd => "amf",
+//!     Vendor::Intel => "qsv",
+//!     _ => "software",
+//! };
+//! ```
+
+use windows::Win32::Foundation::LUID;
+use windows::Win32::Graphics::Dxgi::{DXGI_ADAPTER_DESC3, DXGI_ADAPTER_FLAG3, DXGI_ADAPTER_FLAG3_REMOTE, DXGI_ADAPTER_FLAG3_SOFTWARE, DXGI_COMPUTE_PREEMPTION_GRANULARITY, DXGI_GRAPHICS_PREEMPTION_GRANULARITY};
+
+use crate::utils::convert_u16_to_string;
+
+#[cfg(test)]
+mod test {
+    use windows::Win32::Foundation::LUID;
+    use windows::Win32::Graphics::Dxgi::{DXGI_ADAPTER_DESC3, DXGI_ADAPTER_FLAG3, DXGI_ADAPTER_FLAG3_REMOTE, DXGI_ADAPTER_FLAG3_SOFTWARE, DXGI_ADAPTER_FLAG3_SUPPORT_MONITORED_FENCES, DXGI_COMPUTE_PREEMPTION_GRANULARITY, DXGI_GRAPHICS_PREEMPTION_GRANULARITY};
+
+    use crate::adapter_info::{AdapterInfo, ComputePreemption, GraphicsPreemption, Vendor};
+
+    fn desc(name: &str, vendor_id: u32, device_id: u32, flags: i32) -> DXGI_ADAPTER_DESC3 {
+        let mut desc = DXGI_ADAPTER_DESC3 {
+            VendorId: vendor_id,
+            DeviceId: device_id,
+            SubSysId: 0x1234_1043,
+            Revision: 0xa1,
+            DedicatedVideoMemory: 512 << 20,
+            DedicatedSystemMemory: 0,
+            SharedSystemMemory: 1536 << 20,
+            AdapterLuid: LUID { LowPart: 0x1234, HighPart: 1 },
+            Flags: DXGI_ADAPTER_FLAG3(flags),
+            GraphicsPreemptionGranularity: DXGI_GRAPHICS_PREEMPTION_GRANULARITY(3),
+            ComputePreemptionGranularity: DXGI_COMPUTE_PREEMPTION_GRANULARITY(2),
+            ..Default::default()
+        };
+        for (dst, src) in desc.Description.iter_mut().zip(name.encode_utf16()) {
+            *dst = src;
+        }
+        desc
+    }
+
+    #[test]
+    fn test_vendor_ids() {
+        assert_eq!(Vendor::from_id(0x10de), Vendor::Nvidia);
+        assert_eq!(Vendor::from_id(0x1002), Vendor::Amd);
+        assert_eq!(Vendor::from_id(0x1022), Vendor::Amd);
+        assert_eq!(Vendor::from_id(0x8086), Vendor::Intel);
+        assert_eq!(Vendor::from_id(0x1414), Vendor::MicrosoftBasicRender);
+        assert_eq!(Vendor::from_id(0x5143), Vendor::Other(0x5143));
+        for id in [0x10de, 0x1002, 0x8086, 0x1414, 0x5143] {
+            assert_eq!(Vendor::from_id(id).id(), id);
+        }
+    }
+
+    #[test]
+    fn test_from_desc() {
+        let info = AdapterInfo::from_desc(&desc("NVIDIA GeForce RTX 3070", 0x10de, 0x2484,
+                                                DXGI_ADAPTER_FLAG3_SUPPORT_MONITORED_FENCES.0));
+        assert_eq!(info.description, "NVIDIA GeForce RTX 3070");
+        assert_eq!(info.vendor, Vendor::Nvidia);
+        assert_eq!((info.vendor_id, info.device_id, info.subsystem_id, info.revision), (0x10de, 0x2484, 0x1234_1043, 0xa1));
+        assert_eq!(info.dedicated_video_memory, 512 << 20);
+        assert_eq!(info.shared_system_memory, 1536 << 20);
+        assert_eq!(info.luid, LUID { LowPart: 0x1234, HighPart: 1 });
+        assert!(!info.software && !info.remote);
+        assert!(info.is_hardware());
+        assert_eq!(info.graphics_preemption, GraphicsPreemption::PixelBoundary);
+        assert_eq!(info.compute_preemption, ComputePreemption::ThreadGroupBoundary);
+    }
+
+    #[test]
+    fn test_flags() {
+        let warp = AdapterInfo::from_desc(&desc("Microsoft Basic Render Driver", 0x1414, 0x8c, DXGI_ADAPTER_FLAG3_SOFTWARE.0));
+        assert_eq!(warp.vendor, Vendor::MicrosoftBasicRender);
+        assert!(warp.software && !warp.remote && !warp.is_hardware());
+
+        let remote = AdapterInfo::from_desc(&desc("remote", 0x1414, 0x2, DXGI_ADAPTER_FLAG3_REMOTE.0 | DXGI_ADAPTER_FLAG3_SUPPORT_MONITORED_FENCES.0));
+        assert!(remote.remote && !remote.software && !remote.is_hardware());
+    }
+
+    #[test]
+    fn test_unknown_preemption() {
+        assert_eq!(GraphicsPreemption::from_raw(DXGI_GRAPHICS_PREEMPTION_GRANULARITY(0)), GraphicsPreemption::DmaBufferBoundary);
+        assert_eq!(GraphicsPreemption::from_raw(DXGI_GRAPHICS_PREEMPTION_GRANULARITY(4)), GraphicsPreemption::InstructionBoundary);
+        assert_eq!(GraphicsPreemption::from_raw(DXGI_GRAPHICS_PREEMPTION_GRANULARITY(9)), GraphicsPreemption::Unknown(9));
+        assert_eq!(ComputePreemption::from_raw(DXGI_COMPUTE_PREEMPTION_GRANULARITY(1)), ComputePreemption::DispatchBoundary);
+        assert_eq!(ComputePreemption::from_raw(DXGI_COMPUTE_PREEMPTION_GRANULARITY(-1)), ComputePreemption::Unknown(-1));
+    }
+}
+
+/// maker of an adapter, decoded from its PCI vendor id.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Vendor {
+    /// NVIDIA (`0x10DE`).
+    Nvidia,
+    /// AMD / ATI (`0x1002`, `0x1022`).
+    Amd,
+    /// Intel (`0x8086`).
+    Intel,
+    /// microsoft's software adapters, the basic render driver (WARP) and remote adapters
+    /// (`0x1414`).
+    MicrosoftBasicRender,
+    /// any other vendor id.
+    Other(u32),
+}
+
+impl Vendor {
+    /// decode a PCI vendor id.
+    pub fn from_id(vendor_id: u32) -> Self {
+        match vendor_id {
+            0x10de => Vendor::Nvidia,
+            0x1002 | 0x1022 => Vendor::Amd,
+            0x8086 => Vendor::Intel,
+            0x1414 => Vendor::MicrosoftBasicRender,
+            id => Vendor::Other(id),
+        }
+    }
+
+    /// PCI vendor id of this vendor. returns the primary id for vendors with several ids.
+    pub fn id(self) -> u32 {
+        match self {
+            Vendor::Nvidia => 0x10de,
+            Vendor::Amd => 0x1002,
+            Vendor::Intel => 0x8086,
+            Vendor::MicrosoftBasicRender => 0x1414,
+            Vendor::Other(id) => id,
+        }
+    }
+}
+
+/// granularity at which the gpu can preempt a graphics task.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum GraphicsPreemption {
+    DmaBufferBoundary,
+    PrimitiveBoundary,
+    TriangleBoundary,
+    PixelBoundary,
+    InstructionBoundary,
+    /// a value this crate doesn't know about.
+    Unknown(i32),
+}
+
+impl GraphicsPreemption {
+    /// decode the DXGI value.
+    pub fn from_raw(raw: DXGI_GRAPHICS_PREEMPTION_GRANULARITY) -> Self {
+        match raw.0 {
+            0 => Self::DmaBufferBoundary,
+            1 => Self::PrimitiveBoundary,
+            2 => Self::TriangleBoundary,
+            3 => Self::PixelBoundary,
+            4 => Self::InstructionBoundary,
+            v => Self::Unknown(v),
+        }
+    }
+}
+
+/// granularity at which the gpu can preempt a compute task.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ComputePreemption {
+    DmaBufferBoundary,
+    DispatchBoundary,
+    ThreadGroupBoundary,
+    ThreadBoundary,
+    InstructionBoundary,
+    /// a value this crate doesn't know about.
+    Unknown(i32),
+}
+
+impl ComputePreemption {
+    /// decode the DXGI value.
+    pub fn from_raw(raw: DXGI_COMPUTE_PREEMPTION_GRANULARITY) -> Self {
+        match raw.0 {
+            0 => Self::DmaBufferBoundary,
+            1 => Self::DispatchBoundary,
+            2 => Self::ThreadGroupBoundary,
+            3 => Self::ThreadBoundary,
+            4 => Self::InstructionBoundary,
+            v => Self::Unknown(v),
+        }
+    }
+}
+
+/// Description of an adapter. returned by [Adapter::info][crate::devices::Adapter::info].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdapterInfo {
+    /// name of the adapter.
+    pub description: String,
+    /// vendor decoded from `vendor_id`.
+    pub vendor: Vendor,
+    /// PCI vendor id.
+    pub vendor_id: u32,
+    /// PCI device id.
+    pub device_id: u32,
+    /// PCI subsystem id.
+    pub subsystem_id: u32,
+    /// PCI revision.
+    pub revision: u32,
+    /// bytes of video memory not shared with the cpu.
+    pub dedicated_video_memory: u64,
+    /// bytes of system memory not shared with the cpu.
+    pub dedicated_system_memory: u64,
+    /// bytes of system memory shared with the cpu.
+    pub shared_system_memory: u64,
+    /// locally unique id of the adapter.
+    pub luid: LUID,
+    /// the adapter is a software renderer such as the basic render driver.
+    pub software: bool,
+    /// the adapter is a remote one, e.g. in a remote desktop session.
+    pub remote: bool,
+    /// graphics preemption granularity.
+    pub graphics_preemption: GraphicsPreemption,
+    /// compute preemption granularity.
+    pub compute_preemption: ComputePreemption,
+}
+
+impl AdapterInfo {
+    /// decode an adapter description.
+    pub fn from_desc(desc: &DXGI_ADAPTER_DESC3) -> Self {
+        Self {
+            description: convert_u16_to_string(&desc.Description),
+            vendor: Vendor::from_id(desc.VendorId),
+            vendor_id: desc.VendorId,
+            device_id: desc.DeviceId,
+            subsystem_id: desc.SubSysId,
+            revision: desc.Revision,
+            dedicated_video_memory: desc.DedicatedVideoMemory as u64,
+            dedicated_system_memory: desc.DedicatedSystemMemory as u64,
+            shared_system_memory: desc.SharedSystemMemory as u64,
+            luid: desc.AdapterLuid,
+            software: has_flag(desc.Flags, DXGI_ADAPTER_FLAG3_SOFTWARE),
+            remote: has_flag(desc.Flags, DXGI_ADAPTER_FLAG3_REMOTE),
+            graphics_preemption: GraphicsPreemption::from_raw(desc.GraphicsPreemptionGranularity),
+            compute_preemption: ComputePreemption::from_raw(desc.ComputePreemptionGranularity),
+        }
+    }
+
+    /// returns true for adapters backed by a local gpu, i.e. neither software nor remote.
+    pub fn is_hardware(&self) -> bool {
+        !self.software && !self.remote
+    }
+}
+
+fn has_flag(flags: DXGI_ADAPTER_FLAG3, flag: DXGI_ADAPTER_FLAG3) -> bool {
+    flags.0 & flag.0 != 0
+}
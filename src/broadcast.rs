@@ -0,0 +1,609 @@
+//! Fan out frames of one capture session to multiple consumers.
+//!
+//! DXGI only allows a limited number of duplication sessions per output, so running an encoder, a
+//! recorder and a thumbnail generator off the same monitor should share one session.
+//! [FrameHub] distributes reference counted frames to any number of [subscriptions][Subscription],
+//! each with its own frame rate, [color format][ColorFormat] and [queue policy][QueuePolicy].
+//! [FrameBroadcaster] runs a [FrameSource] on a [capture thread][crate::worker::CaptureWorker]
+//! and publishes every frame to its hub.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::broadcast::{FrameBroadcaster, SubscriberOptions};
+//! use win_desktop_duplication::DesktopDuplicationApi;
+//!
+//! let broadcaster = FrameBroadcaster::spawn(Default::default(), move || {
+//!     DesktopDuplicationApi::new(adapter, output)
+//! })?;
+//!
+//! let encoder = broadcaster.subscribe(SubscriberOptions::default());
+//! let thumbnails = broadcaster.subscribe(SubscriberOptions::with_frame_rate(1.0));
+//!
+//! while let Some(frame) = encoder.recv() {
+//!     let tex = frame?;
+//!     // tex is shared with other subscribers. it returns to the pool once all of them drop it.
+//! }
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use log::trace;
+
+use crate::{DDApiError, Result};
+use crate::errors::ErrorKind;
+use crate::channel::{frame_channel, FrameReceiver, FrameSender, QueuePolicy, TryRecvError};
+use crate::stream::{ErrorPolicy, FramePacing, FrameSource};
+use crate::texture::ColorFormat;
+use crate::worker::{CaptureWorker, CaptureWorkerOptions};
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::Result;
+    use crate::errors::ErrorKind;
+    use crate::broadcast::{FrameBroadcaster, FrameConverter, FrameHub, RateLimiter, SubscriberOptions};
+    use crate::channel::QueuePolicy;
+    use crate::stream::{ErrorPolicy, FramePacing, FrameSource};
+    use crate::texture::ColorFormat;
+    use crate::worker::CaptureWorkerOptions;
+
+    #[derive(Debug, PartialEq)]
+    struct Frame {
+        id: u32,
+        format: ColorFormat,
+    }
+
+    struct Converter {
+        conversions: Arc<std::sync::atomic::AtomicU32>,
+    }
+
+    impl FrameConverter<Frame> for Converter {
+        fn format_of(&self, frame: &Frame) -> ColorFormat {
+            frame.format
+        }
+
+        fn convert(&mut self, frame: &Frame, format: ColorFormat) -> Result<Frame> {
+            self.conversions.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+            Ok(Frame { id: frame.id, format })
+        }
+    }
+
+    fn frame(id: u32) -> Frame {
+        Frame { id, format: ColorFormat::ABGR8UNorm }
+    }
+
+    #[test]
+    fn test_frames_are_shared() {
+        let hub = FrameHub::new();
+        let a = hub.subscribe(Default::default());
+        let b = hub.subscribe(Default::default());
+        hub.publish(frame(1));
+
+        let fa = a.try_recv().unwrap().unwrap();
+        let fb = b.try_recv().unwrap().unwrap();
+        assert!(Arc::ptr_eq(&fa, &fb));
+        assert_eq!(hub.subscriber_count(), 2);
+    }
+
+    #[test]
+    fn test_dropped_subscriber_is_removed() {
+        let hub = FrameHub::new();
+        let a = hub.subscribe(Default::default());
+        drop(hub.subscribe(Default::default()));
+        hub.publish(frame(1));
+        assert_eq!(hub.subscriber_count(), 1);
+        assert_eq!(a.try_recv().unwrap().unwrap().id, 1);
+    }
+
+    #[test]
+    fn test_queue_policy_per_subscriber() {
+        let hub = FrameHub::new();
+        let latest = hub.subscribe(SubscriberOptions {
+            queue_policy: QueuePolicy::LatestOnly,
+            ..Default::default()
+        });
+        let oldest = hub.subscribe(SubscriberOptions {
+            queue_capacity: 3,
+            queue_policy: QueuePolicy::DropNewest,
+            ..Default::default()
+        });
+        for i in 0..5 {
+            hub.publish(frame(i));
+        }
+        assert_eq!(latest.try_recv().unwrap().unwrap().id, 4);
+        let ids: Vec<u32> = (0..3).map(|_| oldest.try_recv().unwrap().unwrap().id).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert_eq!(oldest.dropped(), 2);
+    }
+
+    #[test]
+    fn test_close_ends_subscriptions() {
+        let hub = FrameHub::new();
+        let a = hub.subscribe(Default::default());
+        hub.publish(frame(1));
+        hub.close();
+        assert_eq!(a.recv().unwrap().unwrap().id, 1);
+        assert!(a.recv().is_none());
+        assert!(hub.subscribe(Default::default()).recv().is_none());
+    }
+
+    // source returning the given results in order.
+    struct ScriptedSource {
+        script: Vec<Result<Frame>>,
+    }
+
+    impl FrameSource for ScriptedSource {
+        type Frame = Frame;
+
+        fn acquire_next_frame(&mut self, _timeout: Duration) -> Result<Frame> {
+            if self.script.is_empty() {
+                return Err(ErrorKind::AccessLost.into());
+            }
+            self.script.remove(0)
+        }
+    }
+
+    #[test]
+    fn test_fatal_error_ends_subscriptions() {
+        let options = CaptureWorkerOptions {
+            pacing: FramePacing::Interval(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let broadcaster = FrameBroadcaster::spawn(options, || Ok(ScriptedSource {
+            script: vec![Ok(frame(1)), Err(ErrorKind::Unexpected.into())],
+        })).unwrap();
+        let subs = [broadcaster.subscribe(Default::default()), broadcaster.subscribe(Default::default())];
+        for sub in &subs {
+            // subscribed before or after the first frame went out.
+            let mut item = sub.recv().unwrap();
+            if let Ok(frame) = &item {
+                assert_eq!(frame.id, 1);
+                item = sub.recv().unwrap();
+            }
+            assert!(matches!(item, Err(e) if e.kind() == ErrorKind::Unexpected));
+            assert!(sub.recv().is_none());
+        }
+    }
+
+    #[test]
+    fn test_skipped_errors_are_not_published() {
+        let options = CaptureWorkerOptions {
+            pacing: FramePacing::Interval(Duration::from_millis(20)),
+            error_policy: ErrorPolicy::SkipRecoverable,
+            ..Default::default()
+        };
+        // a lock screen shows up between two frames.
+        let broadcaster = FrameBroadcaster::spawn(options, || Ok(ScriptedSource {
+            script: vec![
+                Err(ErrorKind::AccessDenied.into()),
+                Err(ErrorKind::AccessLost.into()),
+                Ok(frame(1)),
+                Err(ErrorKind::AccessDenied.into()),
+                Ok(frame(2)),
+                Err(ErrorKind::Unexpected.into()),
+            ],
+        })).unwrap();
+        let sub = broadcaster.subscribe(Default::default());
+        let mut items = Vec::new();
+        while let Some(item) = sub.recv() {
+            items.push(item.map(|f| f.id).map_err(|e| e.kind()));
+        }
+        // the first frame may have gone out before subscribing.
+        assert!(items.ends_with(&[Ok(2), Err(ErrorKind::Unexpected)]), "{:?}", items);
+        assert!(items.iter().all(|item| *item != Err(ErrorKind::AccessDenied) && *item != Err(ErrorKind::AccessLost)));
+    }
+
+    #[test]
+    fn test_errors_reach_everyone() {
+        let hub = FrameHub::<Frame>::new();
+        let a = hub.subscribe(Default::default());
+        let b = hub.subscribe(Default::default());
+        hub.publish_error(ErrorKind::AccessLost.into());
+        assert!(matches!(a.try_recv().unwrap(), Err(e) if e.kind() == ErrorKind::AccessLost));
+        assert!(matches!(b.try_recv().unwrap(), Err(e) if e.kind() == ErrorKind::AccessLost));
+    }
+
+    #[test]
+    fn test_format_conversion_is_shared() {
+        let conversions = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let hub = FrameHub::new();
+        hub.set_converter(Converter { conversions: conversions.clone() });
+        let nv12 = SubscriberOptions {
+            format: Some(ColorFormat::NV12),
+            ..Default::default()
+        };
+        let a = hub.subscribe(nv12.clone());
+        let b = hub.subscribe(nv12);
+        let native = hub.subscribe(SubscriberOptions {
+            format: Some(ColorFormat::ABGR8UNorm),
+            ..Default::default()
+        });
+        hub.publish(frame(1));
+
+        let fa = a.try_recv().unwrap().unwrap();
+        let fb = b.try_recv().unwrap().unwrap();
+        assert_eq!(fa.format, ColorFormat::NV12);
+        assert!(Arc::ptr_eq(&fa, &fb));
+        assert_eq!(native.try_recv().unwrap().unwrap().format, ColorFormat::ABGR8UNorm);
+        // converted once for both NV12 subscribers, not at all for the native one.
+        assert_eq!(conversions.load(std::sync::atomic::Ordering::Acquire), 1);
+    }
+
+    #[test]
+    fn test_format_without_converter() {
+        let hub = FrameHub::new();
+        let a = hub.subscribe(SubscriberOptions {
+            format: Some(ColorFormat::NV12),
+            ..Default::default()
+        });
+        hub.publish(frame(1));
+        assert!(matches!(a.try_recv().unwrap(), Err(e) if e.kind() == ErrorKind::Unsupported));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        // 60hz source, 30fps subscriber with some jitter in frame timing.
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(Duration::from_secs_f64(1.0 / 30.0));
+        let jitter = [0.0, 0.4, -0.5, 0.3, -0.2, 0.1];
+        let delivered = (0..60).filter(|i| {
+            let t = *i as f64 * 1000.0 / 60.0 + jitter[*i % jitter.len()];
+            limiter.is_due(start + Duration::from_secs_f64(t / 1000.0))
+        }).count();
+        assert_eq!(delivered, 30);
+
+        let mut unlimited = RateLimiter::new(Duration::ZERO);
+        assert!((0..10).all(|_| unlimited.is_due(start)));
+    }
+}
+
+/// shared, reference counted frame handed out to subscribers.
+pub type SharedFrame<F> = Arc<F>;
+
+/// Converts frames to the [ColorFormat] requested by a subscriber. A converter is called on the
+/// publishing thread, so GPU based converters can use the capture session's device and context.
+pub trait FrameConverter<F>: Send {
+    /// returns color format of given frame.
+    fn format_of(&self, frame: &F) -> ColorFormat;
+
+    /// convert frame to given format.
+    fn convert(&mut self, frame: &F, format: ColorFormat) -> Result<F>;
+}
+
+/// Settings for a single [Subscription].
+#[derive(Clone, Debug)]
+pub struct SubscriberOptions {
+    /// minimum time between two frames delivered to this subscriber. `Duration::ZERO` delivers
+    /// every frame.
+    pub frame_interval: Duration,
+    /// color format the subscriber wants. `None` delivers frames as captured. requires a
+    /// [converter][FrameHub::set_converter] unless frames already have this format.
+    pub format: Option<ColorFormat>,
+    /// maximum number of frames waiting to be received.
+    pub queue_capacity: usize,
+    /// what happens when this subscriber doesn't keep up. [QueuePolicy::Block] stalls every
+    /// other subscriber too.
+    pub queue_policy: QueuePolicy,
+}
+
+impl Default for SubscriberOptions {
+    fn default() -> Self {
+        Self {
+            frame_interval: Duration::ZERO,
+            format: None,
+            queue_capacity: 1,
+            queue_policy: QueuePolicy::DropOldest,
+        }
+    }
+}
+
+impl SubscriberOptions {
+    /// options for a subscriber that wants at most `fps` frames per second.
+    pub fn with_frame_rate(fps: f64) -> Self {
+        Self {
+            frame_interval: if fps > 0.0 { Duration::from_secs_f64(1.0 / fps) } else { Duration::ZERO },
+            ..Default::default()
+        }
+    }
+}
+
+// decides whether a frame should be delivered to a rate limited subscriber. a small tolerance
+// keeps jitter in capture timing from skipping frames, e.g. 30fps out of a 60hz source.
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    next_due: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next_due: None,
+        }
+    }
+
+    pub(crate) fn is_due(&mut self, now: Instant) -> bool {
+        if self.interval.is_zero() {
+            return true;
+        }
+        let tolerance = self.interval / 4;
+        match self.next_due {
+            Some(due) if now + tolerance < due => false,
+            Some(due) => {
+                // don't try to catch up if we fell behind by more than one interval.
+                self.next_due = Some(if now > due + self.interval { now + self.interval } else { due + self.interval });
+                true
+            }
+            None => {
+                self.next_due = Some(now + self.interval);
+                true
+            }
+        }
+    }
+}
+
+struct Subscriber<F> {
+    sender: FrameSender<Result<SharedFrame<F>>>,
+    format: Option<ColorFormat>,
+    limiter: RateLimiter,
+}
+
+struct HubState<F> {
+    subscribers: Vec<Subscriber<F>>,
+    converter: Option<Box<dyn FrameConverter<F>>>,
+    closed: bool,
+}
+
+/// Distributes frames to any number of [subscriptions][Subscription]. cloning the hub returns
+/// another handle to the same hub.
+pub struct FrameHub<F> {
+    state: Arc<Mutex<HubState<F>>>,
+}
+
+impl<F> Clone for FrameHub<F> {
+    fn clone(&self) -> Self {
+        Self { state: self.state.clone() }
+    }
+}
+
+impl<F> Default for FrameHub<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> FrameHub<F> {
+    /// create a new hub without subscribers.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(HubState {
+                subscribers: Vec::new(),
+                converter: None,
+                closed: false,
+            })),
+        }
+    }
+
+    /// register a new subscriber. it receives frames published after this call until the
+    /// subscription is dropped. subscriptions to a [closed][Self::close] hub end right away.
+    pub fn subscribe(&self, options: SubscriberOptions) -> Subscription<F> {
+        let (sender, receiver) = frame_channel(options.queue_capacity, options.queue_policy);
+        let mut state = self.lock();
+        if state.closed {
+            return Subscription { receiver };
+        }
+        state.subscribers.push(Subscriber {
+            sender,
+            format: options.format,
+            limiter: RateLimiter::new(options.frame_interval),
+        });
+        Subscription { receiver }
+    }
+
+    /// set converter used for subscribers that request a specific [ColorFormat].
+    pub fn set_converter<C: FrameConverter<F> + 'static>(&self, converter: C) {
+        self.lock().converter = Some(Box::new(converter));
+    }
+
+    /// number of live subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        let mut state = self.lock();
+        state.subscribers.retain(|s| s.sender.is_connected());
+        state.subscribers.len()
+    }
+
+    /// publish a frame to every subscriber that is due for one. frames are converted at most once
+    /// per format and shared between subscribers.
+    pub fn publish(&self, frame: F) {
+        let now = Instant::now();
+        let frame = Arc::new(frame);
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        // converted versions of this frame, one per requested format.
+        let mut converted: Vec<(ColorFormat, Result<SharedFrame<F>>)> = Vec::new();
+
+        state.subscribers.retain_mut(|sub| {
+            if !sub.limiter.is_due(now) {
+                return sub.sender.is_connected();
+            }
+            let item = match sub.format {
+                None => Ok(frame.clone()),
+                Some(format) => {
+                    if let Some((_, res)) = converted.iter().find(|(f, _)| *f == format) {
+                        res.clone()
+                    } else {
+                        let res = Self::convert(&mut state.converter, &frame, format);
+                        converted.push((format, res.clone()));
+                        res
+                    }
+                }
+            };
+            sub.sender.send(item).is_ok()
+        });
+    }
+
+    /// remove all subscribers. they receive the frames that are already queued and then `None`.
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        state.subscribers.clear();
+    }
+
+    /// publish an error to every subscriber.
+    pub fn publish_error(&self, err: DDApiError) {
+        self.lock().subscribers.retain(|sub| sub.sender.send(Err(err.clone())).is_ok());
+    }
+
+    fn convert(converter: &mut Option<Box<dyn FrameConverter<F>>>, frame: &SharedFrame<F>, format: ColorFormat) -> Result<SharedFrame<F>> {
+        match converter {
+            Some(conv) if conv.format_of(frame) == format => Ok(frame.clone()),
+            Some(conv) => conv.convert(frame, format).map(Arc::new),
+            None => {
+                trace!("subscriber requested {:?} but frame hub has no converter", format);
+                Err(ErrorKind::Unsupported.into())
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HubState<F>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Receiving end of a [FrameHub] subscription. dropping it unsubscribes.
+pub struct Subscription<F> {
+    receiver: FrameReceiver<Result<SharedFrame<F>>>,
+}
+
+impl<F> Subscription<F> {
+    /// wait for the next frame. returns `None` once the hub is gone and all queued frames were
+    /// received.
+    pub fn recv(&self) -> Option<Result<SharedFrame<F>>> {
+        self.receiver.recv()
+    }
+
+    /// wait at most `timeout` for the next frame.
+    pub fn recv_timeout(&self, timeout: Duration) -> core::result::Result<Result<SharedFrame<F>>, TryRecvError> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    /// return the next frame if one is queued without blocking.
+    pub fn try_recv(&self) -> core::result::Result<Result<SharedFrame<F>>, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// number of frames dropped for this subscriber because it didn't keep up.
+    pub fn dropped(&self) -> u64 {
+        self.receiver.dropped()
+    }
+}
+
+// frame source used by the broadcaster's capture thread. publishes every frame to the hub and
+// closes it when the capture thread ends, e.g. after a fatal error.
+struct HubSource<S: FrameSource> {
+    inner: S,
+    hub: FrameHub<S::Frame>,
+    // recoverable errors are only published if the worker yields them.
+    error_policy: ErrorPolicy,
+}
+
+impl<S: FrameSource> Drop for HubSource<S> {
+    fn drop(&mut self) {
+        self.hub.close();
+    }
+}
+
+impl<S: FrameSource> FrameSource for HubSource<S> {
+    type Frame = ();
+
+    fn acquire_next_frame(&mut self, timeout: Duration) -> Result<()> {
+        match self.inner.acquire_next_frame(timeout) {
+            Ok(frame) => {
+                self.hub.publish(frame);
+                Ok(())
+            }
+            Err(e) => {
+                if !e.is_recoverable() || self.error_policy == ErrorPolicy::Yield {
+                    self.hub.publish_error(e.clone());
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn poll_vsync(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<()>>> {
+        self.inner.poll_vsync(cx)
+    }
+}
+
+/// Runs a [FrameSource] on a dedicated capture thread and publishes its frames to a [FrameHub].
+///
+/// frames are delivered to subscribers as [SharedFrame]s. when the source hands out pooled frames
+/// (like [DesktopDuplicationApi][crate::DesktopDuplicationApi]), make sure the
+/// [pool][crate::DuplicationApiOptions::frame_pool_size] is large enough for every frame the
+/// subscribers may hold at once.
+pub struct FrameBroadcaster<S: FrameSource> {
+    worker: CaptureWorker<HubSource<S>>,
+    hub: FrameHub<S::Frame>,
+}
+
+impl<S: FrameSource + 'static> FrameBroadcaster<S>
+    where S::Frame: Send + Sync + 'static {
+    /// spawn a capture thread for the source created by `factory`. check
+    /// [CaptureWorker::spawn] for details on the options and the factory. queue settings in the
+    /// options are ignored, every subscriber has its own queue.
+    pub fn spawn<Fac>(options: CaptureWorkerOptions, factory: Fac) -> Result<Self>
+        where Fac: FnOnce() -> Result<S> + Send + 'static {
+        let hub = FrameHub::new();
+        let worker_hub = hub.clone();
+        let options = CaptureWorkerOptions {
+            queue_capacity: 1,
+            queue_policy: QueuePolicy::LatestOnly,
+            ..options
+        };
+        let error_policy = options.error_policy;
+        let worker = CaptureWorker::spawn(options, move || match factory() {
+            Ok(inner) => Ok(HubSource { inner, hub: worker_hub, error_policy }),
+            Err(e) => {
+                worker_hub.publish_error(e.clone());
+                worker_hub.close();
+                Err(e)
+            }
+        })?;
+        Ok(Self { worker, hub })
+    }
+}
+
+impl<S: FrameSource> FrameBroadcaster<S> {
+    /// register a new subscriber.
+    pub fn subscribe(&self, options: SubscriberOptions) -> Subscription<S::Frame> {
+        self.hub.subscribe(options)
+    }
+
+    /// returns the hub frames are published to.
+    pub fn hub(&self) -> &FrameHub<S::Frame> {
+        &self.hub
+    }
+
+    /// run `f` on the capture thread with mutable access to the source.
+    pub fn reconfigure<F>(&self, f: F)
+        where F: FnOnce(&mut S) + Send + 'static {
+        self.worker.reconfigure(move |source| f(&mut source.inner));
+    }
+
+    /// change the pacing of the capture thread.
+    pub fn set_pacing(&self, pacing: FramePacing) {
+        self.worker.set_pacing(pacing);
+    }
+
+    /// stop the capture thread. subscribers receive the remaining queued frames and then `None`,
+    /// which also happens when the capture thread ends on its own after a fatal error.
+    pub fn stop(&mut self) {
+        self.worker.stop();
+        self.hub.close();
+    }
+}
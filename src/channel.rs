@@ -0,0 +1,326 @@
+//! Provides a bounded frame channel with explicit backpressure [policies][QueuePolicy].
+//!
+//! Frames are large and go stale quickly, so a regular channel that blocks the producer or grows
+//! without bounds is rarely what a capture pipeline wants. [frame_channel] creates a
+//! [FrameSender] / [FrameReceiver] pair where the behaviour on a full queue is chosen explicitly.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::channel::{frame_channel, QueuePolicy};
+//!
+//! let (tx, rx) = frame_channel(2, QueuePolicy::DropOldest);
+//! tx.send(1).unwrap();
+//! tx.send(2).unwrap();
+//! tx.send(3).unwrap(); // drops 1
+//! assert_eq!(rx.recv(), Some(2));
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod test {
+    use std::thread::{sleep, spawn};
+    use std::time::Duration;
+
+    use crate::channel::{frame_channel, QueuePolicy, SendError, TryRecvError};
+
+    #[test]
+    fn test_drop_oldest() {
+        let (tx, rx) = frame_channel(2, QueuePolicy::DropOldest);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.dropped(), 3);
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(rx.try_recv(), Ok(4));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let (tx, rx) = frame_channel(2, QueuePolicy::DropNewest);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(tx.dropped(), 3);
+        assert_eq!(rx.try_recv(), Ok(0));
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_latest_only() {
+        // capacity is ignored for latest only.
+        let (tx, rx) = frame_channel(8, QueuePolicy::LatestOnly);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.len(), 1);
+        assert_eq!(rx.try_recv(), Ok(4));
+    }
+
+    #[test]
+    fn test_block() {
+        let (tx, rx) = frame_channel(1, QueuePolicy::Block);
+        tx.send(0).unwrap();
+        let handle = spawn(move || {
+            for i in 1..4 {
+                tx.send(i).unwrap();
+            }
+        });
+        sleep(Duration::from_millis(20));
+        // the producer is blocked on a full queue, nothing was dropped.
+        assert_eq!(rx.len(), 1);
+        for i in 0..4 {
+            assert_eq!(rx.recv(), Some(i));
+        }
+        handle.join().unwrap();
+        assert_eq!(rx.recv(), None);
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[test]
+    fn test_close_unblocks_sender() {
+        let (tx, rx) = frame_channel(1, QueuePolicy::Block);
+        tx.send(0).unwrap();
+        let handle = spawn(move || tx.send(1));
+        sleep(Duration::from_millis(20));
+        drop(rx);
+        assert_eq!(handle.join().unwrap(), Err(SendError(1)));
+    }
+
+    #[test]
+    fn test_recv_timeout() {
+        let (tx, rx) = frame_channel::<u32>(1, QueuePolicy::Block);
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Err(TryRecvError::Empty));
+        drop(tx);
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Err(TryRecvError::Disconnected));
+    }
+}
+
+/// decides what happens when a frame is sent to a full channel.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum QueuePolicy {
+    /// block the sender until the receiver makes room.
+    Block,
+
+    /// drop the oldest queued frame to make room for the new one.
+    #[default]
+    DropOldest,
+
+    /// drop the new frame and keep the queued ones.
+    DropNewest,
+
+    /// keep only the most recent frame. capacity is ignored.
+    LatestOnly,
+}
+
+/// returned by [FrameSender::send] when the receiving side is gone. contains the frame that
+/// could not be sent.
+#[derive(Debug, Eq, PartialEq)]
+pub struct SendError<T>(pub T);
+
+/// returned by [FrameReceiver::try_recv] and [FrameReceiver::recv_timeout].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TryRecvError {
+    /// no frame is queued right now.
+    Empty,
+    /// no frame is queued and all senders are gone.
+    Disconnected,
+}
+
+/// creates a new bounded frame channel holding at most `capacity` frames (at least 1).
+pub fn frame_channel<T>(capacity: usize, policy: QueuePolicy) -> (FrameSender<T>, FrameReceiver<T>) {
+    let capacity = if policy == QueuePolicy::LatestOnly { 1 } else { capacity.max(1) };
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            senders: 1,
+            receiver_alive: true,
+            dropped: 0,
+        }),
+        available: Condvar::new(),
+        space: Condvar::new(),
+        capacity,
+        policy,
+    });
+    (FrameSender { shared: shared.clone() }, FrameReceiver { shared })
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+    dropped: u64,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    // signalled when a frame is queued or the last sender leaves.
+    available: Condvar,
+    // signalled when a frame is taken or the receiver leaves.
+    space: Condvar,
+    capacity: usize,
+    policy: QueuePolicy,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// sending half of a [frame_channel]. it can be cloned to send from multiple threads.
+pub struct FrameSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> FrameSender<T> {
+    /// send a frame following the channel's [QueuePolicy]. this only blocks with
+    /// [QueuePolicy::Block].
+    ///
+    /// fails when the receiver was dropped or [closed][FrameReceiver::close].
+    pub fn send(&self, frame: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+        if !state.receiver_alive {
+            return Err(SendError(frame));
+        }
+        if state.queue.len() >= self.shared.capacity {
+            match self.shared.policy {
+                QueuePolicy::Block => {
+                    while state.receiver_alive && state.queue.len() >= self.shared.capacity {
+                        state = self.shared.space.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                    if !state.receiver_alive {
+                        return Err(SendError(frame));
+                    }
+                }
+                QueuePolicy::DropOldest | QueuePolicy::LatestOnly => {
+                    state.queue.pop_front();
+                    state.dropped += 1;
+                }
+                QueuePolicy::DropNewest => {
+                    state.dropped += 1;
+                    return Ok(());
+                }
+            }
+        }
+        state.queue.push_back(frame);
+        self.shared.available.notify_one();
+        Ok(())
+    }
+
+    /// returns true if the receiver is still alive.
+    pub fn is_connected(&self) -> bool {
+        self.shared.lock().receiver_alive
+    }
+
+    /// number of frames dropped so far because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+}
+
+impl<T> Clone for FrameSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for FrameSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.available.notify_all();
+        }
+    }
+}
+
+/// receiving half of a [frame_channel].
+pub struct FrameReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> FrameReceiver<T> {
+    /// wait for the next frame. returns `None` once the channel is empty and all senders are gone.
+    pub fn recv(&self) -> Option<T> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(frame) = state.queue.pop_front() {
+                self.shared.space.notify_one();
+                return Some(frame);
+            }
+            if state.senders == 0 {
+                return None;
+            }
+            state = self.shared.available.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// return the next frame if one is queued without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.lock();
+        Self::take(&self.shared, &mut state)
+    }
+
+    /// wait at most `timeout` for the next frame.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, TryRecvError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            match Self::take(&self.shared, &mut state) {
+                Err(TryRecvError::Empty) => {}
+                res => return res,
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(TryRecvError::Empty);
+            }
+            state = self.shared.available.wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+
+    /// close the channel. queued frames can still be received but new sends fail and blocked
+    /// senders are woken up.
+    pub fn close(&self) {
+        self.shared.lock().receiver_alive = false;
+        self.shared.space.notify_all();
+    }
+
+    /// number of frames currently queued.
+    pub fn len(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// returns true if no frames are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// number of frames dropped so far because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    fn take(shared: &Shared<T>, state: &mut State<T>) -> Result<T, TryRecvError> {
+        if let Some(frame) = state.queue.pop_front() {
+            shared.space.notify_one();
+            Ok(frame)
+        } else if state.senders == 0 {
+            Err(TryRecvError::Disconnected)
+        } else {
+            Err(TryRecvError::Empty)
+        }
+    }
+}
+
+impl<T> Drop for FrameReceiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
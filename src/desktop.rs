@@ -0,0 +1,622 @@
+//! Capture of the whole virtual desktop into one frame.
+//!
+//! [DesktopDuplicationApi] duplicates a single output. [DesktopCapture] owns one [FrameSource] per
+//! display and composites their frames on the cpu into a single [CpuFrame] laid out by desktop
+//! coordinates. The frame's top left corner is the top left corner of the
+//! [bounds][DesktopLayout::bounds] of all displays, so displays left of or above the primary one
+//! (negative coordinates) are handled, and gaps between displays are filled with a background
+//! color. Frames of rotated displays are rotated into their desktop orientation.
+//!
+//! A [region][DesktopCapture::set_region] of the desktop, e.g. the bounds of a window, can be
+//! captured instead of the whole desktop. Only displays intersecting the region are captured and
+//! their frames are cropped to it. The region can be changed between frames or
+//! [tracked][DesktopCapture::track_region] with a callback.
+//!
+//! [DesktopLayout] and [compose] only work on plain data and can be used with synthetic sources.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::desktop::DesktopCapture;
+//! use win_desktop_duplication::stream::FrameSource;
+//!
+//! let mut capture = DesktopCapture::all_displays()?.with_background([0, 0, 0, 255]);
+//! loop {
+//!     let frame = capture.acquire_next_frame(Duration::from_millis(100))?;
+//!     // frame covers capture.layout().bounds()
+//! }
+//!
+//! // only a window, following it when it moves.
+//! capture.track_region(move || window_rect(hwnd));
+//! ```
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use log::{debug, warn};
+
+use crate::{DesktopDuplicationApi, Result};
+use crate::errors::{DDApiError, ErrorKind};
+use crate::geometry::{DisplayGeometry, Point, Rect};
+use crate::outputs::DisplayOrientation;
+use crate::stream::FrameSource;
+use crate::tex_reader::{CpuFrame, TextureReader};
+use crate::texture::ColorFormat;
+use crate::topology::Topology;
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::time::{Duration, Instant};
+
+    use crate::desktop::{compose, DesktopCapture, DesktopLayout};
+    use crate::errors::ErrorKind;
+    use crate::geometry::{DisplayGeometry, Point, Rect};
+    use crate::outputs::DisplayOrientation;
+    use crate::Result;
+    use crate::stream::FrameSource;
+    use crate::tex_reader::CpuFrame;
+    use crate::texture::ColorFormat;
+
+    fn display(desktop: Rect, orientation: DisplayOrientation) -> DisplayGeometry {
+        DisplayGeometry {
+            desktop,
+            work_area: desktop,
+            orientation,
+            attached: true,
+            ..Default::default()
+        }
+    }
+
+    // every pixel holds its own coordinates and a tag, so placement and rotation can be checked.
+    fn frame(width: u32, height: u32, tag: u8) -> CpuFrame {
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&[x as u8, y as u8, tag, 255]);
+            }
+        }
+        CpuFrame { width, height, format: ColorFormat::ABGR8UNorm, data }
+    }
+
+    fn pixel(frame: &CpuFrame, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * frame.width + x) * 4) as usize;
+        frame.data[i..i + 4].try_into().unwrap()
+    }
+
+    struct ScriptedSource(VecDeque<Result<CpuFrame>>);
+
+    impl FrameSource for ScriptedSource {
+        type Frame = CpuFrame;
+
+        fn acquire_next_frame(&mut self, _timeout: Duration) -> Result<CpuFrame> {
+            self.0.pop_front().unwrap_or(Err(ErrorKind::AccessLost.into()))
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        // 4x2 display left of and above the primary 4x2 one, with a gap of 2 pixels, and a
+        // detached display.
+        let mut detached = display(Rect::new(100, 100, 200, 200), DisplayOrientation::NoRotation);
+        detached.attached = false;
+        let layout = DesktopLayout::new(&[
+            display(Rect::new(0, 0, 4, 2), DisplayOrientation::NoRotation),
+            display(Rect::new(-6, -1, -2, 1), DisplayOrientation::NoRotation),
+            detached,
+        ]);
+        assert_eq!(layout.bounds(), Rect::new(-6, -1, 4, 2));
+        assert_eq!((layout.width(), layout.height()), (10, 3));
+        assert_eq!(layout.placements()[0].unwrap().rect, Rect::new(6, 1, 10, 3));
+        assert_eq!(layout.placements()[1].unwrap().rect, Rect::new(0, 0, 4, 2));
+        assert!(layout.placements()[2].is_none());
+        assert_eq!(layout.to_frame(Point::new(0, 0)), Point::new(6, 1));
+
+        assert_eq!(DesktopLayout::new(&[]).bounds(), Rect::default());
+    }
+
+    #[test]
+    fn test_compose_gaps() {
+        let layout = DesktopLayout::new(&[
+            display(Rect::new(0, 0, 4, 2), DisplayOrientation::NoRotation),
+            display(Rect::new(-6, -1, -2, 1), DisplayOrientation::NoRotation),
+        ]);
+        let primary = frame(4, 2, 1);
+        let mut left = frame(4, 2, 2);
+        left.format = ColorFormat::ARGB8UNorm;
+        let out = compose(&layout, &[Some(&primary), Some(&left)], [9, 8, 7, 255]).unwrap();
+        assert_eq!((out.width, out.height, out.format), (10, 3, ColorFormat::ABGR8UNorm));
+        assert_eq!(pixel(&out, 6, 1), [0, 0, 1, 255]);
+        assert_eq!(pixel(&out, 9, 2), [3, 1, 1, 255]);
+        // ARGB frames are converted.
+        assert_eq!(pixel(&out, 3, 1), [2, 1, 3, 255]);
+        // gaps are filled with the background, given as rgba.
+        assert_eq!(pixel(&out, 5, 0), [7, 8, 9, 255]);
+        assert_eq!(pixel(&out, 0, 2), [7, 8, 9, 255]);
+
+        // missing frames leave the background.
+        let out = compose(&layout, &[Some(&primary), None], [9, 8, 7, 255]).unwrap();
+        assert_eq!(pixel(&out, 0, 0), [7, 8, 9, 255]);
+
+        let mut nv12 = frame(4, 2, 1);
+        nv12.format = ColorFormat::NV12;
+        assert!(matches!(compose(&layout, &[Some(&nv12), None], [0; 4]), Err(e) if e.kind() == ErrorKind::BadParam));
+    }
+
+    #[test]
+    fn test_compose_rotation() {
+        // frames arrive in the native 3x2 orientation of each panel.
+        let native = frame(3, 2, 0);
+        for (orientation, top_left, top_right, bottom_left) in [
+            (DisplayOrientation::Rotate90, [0, 1], [0, 0], [2, 1]),
+            (DisplayOrientation::Rotate180, [2, 1], [0, 1], [2, 0]),
+            (DisplayOrientation::Rotate270, [2, 0], [2, 1], [0, 0]),
+        ] {
+            let size = if orientation == DisplayOrientation::Rotate180 { (3, 2) } else { (2, 3) };
+            let layout = DesktopLayout::new(&[display(Rect::new(0, 0, size.0, size.1), orientation)]);
+            let out = compose(&layout, &[Some(&native)], [0; 4]).unwrap();
+            let (w, h) = (out.width, out.height);
+            assert_eq!((w, h), (size.0 as u32, size.1 as u32));
+            assert_eq!(pixel(&out, 0, 0)[..2], top_left, "{:?}", orientation);
+            assert_eq!(pixel(&out, w - 1, 0)[..2], top_right, "{:?}", orientation);
+            assert_eq!(pixel(&out, 0, h - 1)[..2], bottom_left, "{:?}", orientation);
+        }
+    }
+
+    #[test]
+    fn test_capture_keeps_last_frame() {
+        let displays = vec![
+            (display(Rect::new(0, 0, 2, 1), DisplayOrientation::NoRotation),
+             ScriptedSource(VecDeque::from([Ok(frame(2, 1, 1)), Ok(frame(2, 1, 3))]))),
+            (display(Rect::new(2, 0, 4, 1), DisplayOrientation::NoRotation),
+             ScriptedSource(VecDeque::from([Ok(frame(2, 1, 2)), Err(ErrorKind::AccessLost.into())]))),
+        ];
+        let mut capture = DesktopCapture::new(displays);
+        let out = capture.acquire_next_frame(Duration::ZERO).unwrap();
+        assert_eq!((pixel(&out, 0, 0)[2], pixel(&out, 2, 0)[2]), (1, 2));
+
+        // the second display failed, its last frame is reused.
+        let out = capture.acquire_next_frame(Duration::ZERO).unwrap();
+        assert_eq!((pixel(&out, 0, 0)[2], pixel(&out, 2, 0)[2]), (3, 2));
+
+        // all displays failed.
+        let err = capture.acquire_next_frame(Duration::ZERO).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessLost);
+    }
+
+    #[test]
+    fn test_capture_fatal_error() {
+        let displays = vec![
+            (display(Rect::new(0, 0, 2, 1), DisplayOrientation::NoRotation),
+             ScriptedSource(VecDeque::from([Ok(frame(2, 1, 1))]))),
+            (display(Rect::new(2, 0, 4, 1), DisplayOrientation::NoRotation),
+             ScriptedSource(VecDeque::from([Err(ErrorKind::Disconnected.into())]))),
+        ];
+        let mut capture = DesktopCapture::new(displays);
+        let err = capture.acquire_next_frame(Duration::ZERO).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Disconnected);
+    }
+
+    #[test]
+    fn test_region_layout() {
+        let displays = [
+            display(Rect::new(0, 0, 4, 2), DisplayOrientation::NoRotation),
+            display(Rect::new(-6, -1, -2, 1), DisplayOrientation::NoRotation),
+        ];
+        // spans the gap between both displays.
+        let layout = DesktopLayout::with_region(&displays, Rect::new(-3, 0, 2, 2));
+        assert_eq!((layout.width(), layout.height()), (5, 2));
+        assert_eq!(layout.placements()[0].unwrap().rect, Rect::new(3, 0, 7, 2));
+        assert_eq!(layout.placements()[1].unwrap().rect, Rect::new(-3, -1, 1, 1));
+        assert_eq!(layout.to_frame(Point::new(0, 0)), Point::new(3, 0));
+
+        // only touches the primary display.
+        let layout = DesktopLayout::with_region(&displays, Rect::new(1, 1, 3, 2));
+        assert!(layout.placements()[0].is_some() && layout.placements()[1].is_none());
+        // right and bottom edges are exclusive.
+        let layout = DesktopLayout::with_region(&displays, Rect::new(-2, 1, 0, 2));
+        assert!(layout.placements()[0].is_none() && layout.placements()[1].is_none());
+    }
+
+    #[test]
+    fn test_compose_region() {
+        let displays = [
+            display(Rect::new(0, 0, 4, 2), DisplayOrientation::NoRotation),
+            display(Rect::new(-6, -1, -2, 1), DisplayOrientation::NoRotation),
+        ];
+        let layout = DesktopLayout::with_region(&displays, Rect::new(-3, 0, 2, 2));
+        let out = compose(&layout, &[Some(&frame(4, 2, 1)), Some(&frame(4, 2, 2))], [0; 4]).unwrap();
+        assert_eq!((out.width, out.height), (5, 2));
+        // desktop (-3, 0) is pixel (3, 1) of the left display.
+        assert_eq!(pixel(&out, 0, 0), [3, 1, 2, 255]);
+        // the left display ends at y = 1, desktop (-2, 0) is in the gap.
+        assert_eq!(pixel(&out, 0, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(&out, 1, 0), [0, 0, 0, 0]);
+        // desktop (1, 1) is pixel (1, 1) of the primary display.
+        assert_eq!(pixel(&out, 4, 1), [1, 1, 1, 255]);
+
+        // crop of a rotated display: native 3x2, shown as 2x3.
+        let layout = DesktopLayout::with_region(&[display(Rect::new(10, 10, 12, 13), DisplayOrientation::Rotate90)], Rect::new(11, 11, 12, 13));
+        let out = compose(&layout, &[Some(&frame(3, 2, 0))], [0; 4]).unwrap();
+        assert_eq!((out.width, out.height), (1, 2));
+        // rotated pixel (1, 1) is native (1, 0), rotated (1, 2) is native (2, 0).
+        assert_eq!(pixel(&out, 0, 0)[..2], [1, 0]);
+        assert_eq!(pixel(&out, 0, 1)[..2], [2, 0]);
+    }
+
+    #[test]
+    fn test_region_capture() {
+        let displays = vec![
+            (display(Rect::new(0, 0, 2, 1), DisplayOrientation::NoRotation),
+             ScriptedSource(VecDeque::from([Ok(frame(2, 1, 1)), Ok(frame(2, 1, 3))]))),
+            (display(Rect::new(2, 0, 4, 1), DisplayOrientation::NoRotation),
+             ScriptedSource(VecDeque::from([Ok(frame(2, 1, 2))]))),
+        ];
+        let mut capture = DesktopCapture::new(displays);
+        assert!(matches!(capture.set_region(Some(Rect::new(1, 0, 1, 1))), Err(e) if e.kind() == ErrorKind::BadParam));
+
+        // the second display is outside the region and isn't captured.
+        capture.set_region(Some(Rect::new(1, 0, 2, 1))).unwrap();
+        let out = capture.acquire_next_frame(Duration::ZERO).unwrap();
+        assert_eq!((out.width, out.height), (1, 1));
+        assert_eq!(pixel(&out, 0, 0), [1, 0, 1, 255]);
+        assert_eq!(capture.sources[1].0.len(), 1);
+
+        // the region moves with the tracker, empty regions are ignored.
+        let mut regions = VecDeque::from([Some(Rect::new(1, 0, 3, 1)), Some(Rect::new(5, 5, 5, 5)), None]);
+        capture.track_region(move || regions.pop_front().flatten());
+        let out = capture.acquire_next_frame(Duration::ZERO).unwrap();
+        assert_eq!(capture.region(), Some(Rect::new(1, 0, 3, 1)));
+        assert_eq!((pixel(&out, 0, 0), pixel(&out, 1, 0)), ([1, 0, 3, 255], [0, 0, 2, 255]));
+
+        // both sources are drained now, the last frames are reused.
+        capture.acquire_next_frame(Duration::ZERO).unwrap_err();
+        assert_eq!(capture.region(), Some(Rect::new(1, 0, 3, 1)));
+        capture.stop_tracking();
+        capture.set_region(None).unwrap();
+        assert_eq!(capture.layout().width(), 4);
+    }
+
+    #[test]
+    fn test_region_outside_displays() {
+        let displays = vec![
+            (display(Rect::new(0, 0, 2, 1), DisplayOrientation::NoRotation),
+             ScriptedSource(VecDeque::from([Ok(frame(2, 1, 1))]))),
+        ];
+        let mut capture = DesktopCapture::new(displays);
+        capture.set_region(Some(Rect::new(10, 10, 20, 20))).unwrap();
+
+        // no background frame right away, the timeout is waited out instead.
+        let start = Instant::now();
+        let err = capture.acquire_next_frame(Duration::from_millis(20)).unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(err.kind(), ErrorKind::AccessLost);
+        assert!(err.is_recoverable());
+        // the display wasn't polled.
+        assert_eq!(capture.sources[0].0.len(), 1);
+    }
+}
+
+/// where a display's frame goes in the composited frame.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Placement {
+    /// target rect in frame coordinates. it may extend past the frame when capturing a region,
+    /// only the part inside the frame is drawn.
+    pub rect: Rect,
+    /// the display's frame is rotated by this before it is copied.
+    pub orientation: DisplayOrientation,
+}
+
+/// Layout of displays in a composited frame.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DesktopLayout {
+    bounds: Rect,
+    placements: Vec<Option<Placement>>,
+}
+
+impl DesktopLayout {
+    /// lay out given displays to cover the whole desktop. detached displays get no placement.
+    pub fn new(displays: &[DisplayGeometry]) -> Self {
+        let bounds = displays.iter()
+            .filter(|d| d.attached)
+            .map(|d| d.desktop)
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or_default();
+        Self::with_region(displays, bounds)
+    }
+
+    /// lay out given displays to cover only `region`, in desktop coordinates. displays not
+    /// intersecting the region get no placement, the others are cropped to it.
+    pub fn with_region(displays: &[DisplayGeometry], region: Rect) -> Self {
+        let placements = displays.iter()
+            .map(|d| (d.attached && d.desktop.intersection(&region).is_some()).then_some(Placement {
+                rect: d.desktop.offset(-region.left, -region.top),
+                orientation: d.orientation,
+            }))
+            .collect();
+        Self { bounds: region, placements }
+    }
+
+    /// rect of the virtual desktop covered by the composited frame, in desktop coordinates.
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    /// width of the composited frame.
+    pub fn width(&self) -> u32 {
+        self.bounds.width()
+    }
+
+    /// height of the composited frame.
+    pub fn height(&self) -> u32 {
+        self.bounds.height()
+    }
+
+    /// placement of every display, in the order the layout was created with.
+    pub fn placements(&self) -> &[Option<Placement>] {
+        &self.placements
+    }
+
+    /// convert a point in desktop coordinates to frame coordinates.
+    pub fn to_frame(&self, p: Point) -> Point {
+        Point::new(p.x - self.bounds.left, p.y - self.bounds.top)
+    }
+}
+
+/// composite `frames` into one [ABGR8UNorm][ColorFormat::ABGR8UNorm] frame. `frames[i]` belongs
+/// to the i-th display of `layout`; `None` leaves its area filled with `background` (rgba).
+///
+/// frames are expected in the display's native orientation, as desktop duplication returns them,
+/// and are rotated by their placement's orientation. frames larger than their placement, e.g.
+/// right after a mode change, are cropped. fails with [ErrorKind::BadParam][crate::errors::ErrorKind::BadParam]
+/// for frames that are not 8 bit rgba.
+pub fn compose(layout: &DesktopLayout, frames: &[Option<&CpuFrame>], background: [u8; 4]) -> Result<CpuFrame> {
+    let mut out = CpuFrame {
+        width: layout.width(),
+        height: layout.height(),
+        format: ColorFormat::ABGR8UNorm,
+        data: vec![0; layout.width() as usize * layout.height() as usize * 4],
+    };
+    let [r, g, b, a] = background;
+    for px in out.data.chunks_exact_mut(4) {
+        px.copy_from_slice(&[b, g, r, a]);
+    }
+    for (placement, frame) in layout.placements.iter().zip(frames) {
+        let (Some(placement), Some(frame)) = (placement, frame) else { continue };
+        if !is_rgba8(frame) {
+            return Err(DDApiError::bad_param(format!("can't compose frames of format {:?}", frame.format)));
+        }
+        blit(frame, placement, &mut out);
+    }
+    Ok(out)
+}
+
+/// Capture of all displays into one frame. check the [module docs][self].
+///
+/// it's a [FrameSource] itself, so it can run on a [CaptureWorker][crate::worker::CaptureWorker].
+/// it isn't backed by a single display, use [FramePacing::Interval][crate::stream::FramePacing::Interval]
+/// with it.
+pub struct DesktopCapture<S: FrameSource<Frame=CpuFrame>> {
+    layout: DesktopLayout,
+    displays: Vec<DisplayGeometry>,
+    sources: Vec<S>,
+    last_frames: Vec<Option<CpuFrame>>,
+    background: [u8; 4],
+    region: Option<Rect>,
+    tracker: Option<Box<dyn FnMut() -> Option<Rect> + Send>>,
+}
+
+impl<S: FrameSource<Frame=CpuFrame>> DesktopCapture<S> {
+    /// capture given displays, each with its own source.
+    pub fn new(displays: Vec<(DisplayGeometry, S)>) -> Self {
+        let (displays, sources): (Vec<_>, Vec<_>) = displays.into_iter().unzip();
+        Self {
+            layout: DesktopLayout::new(&displays),
+            last_frames: vec![None; displays.len()],
+            displays,
+            sources,
+            background: [0, 0, 0, 255],
+            region: None,
+            tracker: None,
+        }
+    }
+
+    /// color of gaps between displays and of displays without a frame yet, as rgba. defaults to
+    /// opaque black.
+    pub fn with_background(mut self, background: [u8; 4]) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// capture only `region`, in desktop coordinates, or the whole desktop with `None`. takes
+    /// effect with the next frame, whose size is the size of the region. parts of the region
+    /// outside every display are filled with the background.
+    ///
+    /// fails with [ErrorKind::BadParam][crate::errors::ErrorKind::BadParam] for empty regions.
+    pub fn set_region(&mut self, region: Option<Rect>) -> Result<()> {
+        if region.is_some_and(|r| r.is_empty()) {
+            return Err(DDApiError::bad_param(format!("empty capture region {:?}", region)));
+        }
+        if region == self.region {
+            return Ok(());
+        }
+        self.region = region;
+        self.layout = match region {
+            Some(region) => DesktopLayout::with_region(&self.displays, region),
+            None => DesktopLayout::new(&self.displays),
+        };
+        // frames of displays that left the region would be stale once they're back in it.
+        for (frame, placement) in self.last_frames.iter_mut().zip(&self.layout.placements) {
+            if placement.is_none() {
+                *frame = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// the captured region. `None` if the whole desktop is captured.
+    pub fn region(&self) -> Option<Rect> {
+        self.region
+    }
+
+    /// call `tracker` before every frame and capture the region it returns, e.g. the current
+    /// bounds of a window. `None` captures the whole desktop. empty regions, e.g. of a minimized
+    /// window, are ignored and the previous region is kept.
+    pub fn track_region<F>(&mut self, tracker: F)
+        where F: FnMut() -> Option<Rect> + Send + 'static {
+        self.tracker = Some(Box::new(tracker));
+    }
+
+    /// stop [tracking][Self::track_region] the region. the last tracked region stays in place.
+    pub fn stop_tracking(&mut self) {
+        self.tracker = None;
+    }
+
+    /// layout of the composited frames.
+    pub fn layout(&self) -> &DesktopLayout {
+        &self.layout
+    }
+
+    /// the captured displays, in the order of [layout placements][DesktopLayout::placements].
+    pub fn displays(&self) -> &[DisplayGeometry] {
+        &self.displays
+    }
+}
+
+impl DesktopCapture<CpuDuplication> {
+    /// capture every display attached to the desktop, each with its own
+    /// [DesktopDuplicationApi] on the adapter owning the display.
+    pub fn all_displays() -> Result<Self> {
+        let topology = Topology::capture()?;
+        let mut displays = Vec::new();
+        for location in topology.displays() {
+            let Some((adapter, display)) = topology.locate(&location.display_name) else { continue };
+            let geometry = display.geometry()?;
+            if !geometry.attached {
+                continue;
+            }
+            displays.push((geometry, CpuDuplication::new(DesktopDuplicationApi::new(adapter, display)?)));
+        }
+        Ok(Self::new(displays))
+    }
+}
+
+/// acquires a frame from every display in the captured region and composites them. `timeout`
+/// applies to the first of these displays, the others are polled without waiting.
+///
+/// when a display fails with a recoverable error its last frame is reused. the error is returned
+/// if every display failed, or right away if it isn't recoverable. if the region covers no
+/// display, it waits for `timeout` and fails with the recoverable [ErrorKind::AccessLost].
+impl<S: FrameSource<Frame=CpuFrame>> FrameSource for DesktopCapture<S> {
+    type Frame = CpuFrame;
+
+    fn acquire_next_frame(&mut self, timeout: Duration) -> Result<CpuFrame> {
+        if let Some(tracker) = self.tracker.as_mut() {
+            let region = tracker();
+            if let Err(e) = self.set_region(region) {
+                debug!("keeping the previous capture region. {}", e);
+            }
+        }
+
+        if self.layout.placements.iter().all(Option::is_none) {
+            // nothing to wait for a frame on. don't return background frames in a busy loop.
+            sleep(timeout);
+            return Err(DDApiError::new(ErrorKind::AccessLost).with_message("the capture region covers no display"));
+        }
+
+        let mut first_err = None;
+        let mut acquired = 0;
+        let mut timeout = Some(timeout);
+        for (idx, source) in self.sources.iter_mut().enumerate() {
+            if self.layout.placements[idx].is_none() {
+                continue;
+            }
+            match source.acquire_next_frame(timeout.take().unwrap_or_default()) {
+                Ok(frame) => {
+                    self.last_frames[idx] = Some(frame);
+                    acquired += 1;
+                }
+                Err(e) if e.is_recoverable() => {
+                    warn!("failed to capture {}, reusing its last frame. {}", self.displays[idx].name, e);
+                    first_err.get_or_insert(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if acquired == 0 {
+            if let Some(e) = first_err {
+                return Err(e);
+            }
+        }
+        let frames: Vec<_> = self.last_frames.iter().map(Option::as_ref).collect();
+        compose(&self.layout, &frames, self.background)
+    }
+}
+
+/// [DesktopDuplicationApi] returning frames in system memory.
+pub struct CpuDuplication {
+    api: DesktopDuplicationApi,
+    reader: TextureReader,
+}
+
+impl CpuDuplication {
+    pub fn new(api: DesktopDuplicationApi) -> Self {
+        let (device, ctx) = api.get_device_and_ctx();
+        Self {
+            api,
+            reader: TextureReader::new(device, ctx),
+        }
+    }
+
+    /// the wrapped api.
+    pub fn api(&mut self) -> &mut DesktopDuplicationApi {
+        &mut self.api
+    }
+}
+
+impl FrameSource for CpuDuplication {
+    type Frame = CpuFrame;
+
+    fn acquire_next_frame(&mut self, timeout: Duration) -> Result<CpuFrame> {
+        let tex = self.api.acquire_next_frame(timeout)?;
+        let mut frame = CpuFrame::default();
+        self.reader.read_frame(&tex, &mut frame)?;
+        Ok(frame)
+    }
+}
+
+fn is_rgba8(frame: &CpuFrame) -> bool {
+    matches!(frame.format, ColorFormat::ABGR8UNorm | ColorFormat::ARGB8UNorm)
+        && frame.data.len() >= frame.width as usize * frame.height as usize * 4
+}
+
+// copy `src` rotated into its placement in `dst`, converting to ABGR8UNorm.
+fn blit(src: &CpuFrame, placement: &Placement, dst: &mut CpuFrame) {
+    let swap = src.format == ColorFormat::ARGB8UNorm;
+    let (sw, sh) = (src.width as usize, src.height as usize);
+    let (rw, rh) = match placement.orientation {
+        DisplayOrientation::Rotate90 | DisplayOrientation::Rotate270 => (sh, sw),
+        _ => (sw, sh),
+    };
+    // part of the placement covered by the frame and inside `dst`, in frame coordinates.
+    let rect = placement.rect;
+    let covered = Rect::new(rect.left, rect.top,
+                            rect.right.min(rect.left.saturating_add(rw as i32)),
+                            rect.bottom.min(rect.top.saturating_add(rh as i32)));
+    let Some(visible) = covered.intersection(&Rect::new(0, 0, dst.width as i32, dst.height as i32)) else { return };
+    let dw = dst.width as usize;
+    for dy in visible.top..visible.bottom {
+        for dx in visible.left..visible.right {
+            let (x, y) = ((dx - rect.left) as usize, (dy - rect.top) as usize);
+            let (sx, sy) = match placement.orientation {
+                DisplayOrientation::NoRotation => (x, y),
+                DisplayOrientation::Rotate90 => (y, sh - 1 - x),
+                DisplayOrientation::Rotate180 => (sw - 1 - x, sh - 1 - y),
+                DisplayOrientation::Rotate270 => (sw - 1 - y, x),
+            };
+            let s = (sy * sw + sx) * 4;
+            let d = (dy as usize * dw + dx as usize) * 4;
+            let px = &src.data[s..s + 4];
+            dst.data[d..d + 4].copy_from_slice(&if swap { [px[2], px[1], px[0], px[3]] } else { [px[0], px[1], px[2], px[3]] });
+        }
+    }
+}
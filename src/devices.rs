@@ -3,20 +3,28 @@
 
 use windows::core::{Interface, Result as WinResult};
 use windows::Win32::Foundation::LUID;
-use windows::Win32::Graphics::Dxgi::{CreateDXGIFactory2, DXGI_ADAPTER_DESC3, DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE, IDXGIAdapter4, IDXGIFactory6};
+use windows::Win32::Graphics::Dxgi::{CreateDXGIFactory2, DXGI_ADAPTER_DESC3, DXGI_GPU_PREFERENCE, DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE, DXGI_GPU_PREFERENCE_MINIMUM_POWER, DXGI_GPU_PREFERENCE_UNSPECIFIED, IDXGIAdapter4, IDXGIFactory6};
+use log::warn;
 
+use crate::adapter_info::{AdapterInfo, Vendor};
+use crate::errors::DDApiError;
 use crate::outputs::Display;
+use crate::Result;
 use crate::utils::convert_u16_to_string;
 
 #[cfg(test)]
 mod test {
-    use crate::devices::AdapterFactory;
+    use windows::Win32::Graphics::Dxgi::{DXGI_ADAPTER_DESC3, DXGI_ADAPTER_FLAG3_REMOTE, DXGI_ADAPTER_FLAG3_SOFTWARE};
+
+    use crate::adapter_info::AdapterInfo;
+    use crate::devices::{AdapterFactory, AdapterOptions, GpuPreference};
 
     #[test]
     fn test_adapter_methods() {
         for adapter in AdapterFactory::new() {
             println!("{}", adapter.name());
             println!("{:?}", adapter.luid());
+            println!("{:?}", adapter.info().unwrap());
             for out in adapter.iter_displays() {
                 println!("{}", out.name());
                 for mode in out.get_display_modes().unwrap() {
@@ -32,6 +40,57 @@ mod test {
             println!("{}", adapter.name());
         }
     }
+
+    #[test]
+    fn test_fallible_methods() {
+        let fac = AdapterFactory::try_new().unwrap();
+        for adapter in fac {
+            let name = adapter.try_name().unwrap();
+            assert_eq!(name, adapter.name());
+            assert_eq!(adapter.try_luid().unwrap(), adapter.luid());
+            for out in adapter.iter_displays() {
+                assert_eq!(out.try_name().unwrap(), out.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_options_filter_info() {
+        let mut desc = DXGI_ADAPTER_DESC3::default();
+        let hardware = AdapterInfo::from_desc(&desc);
+        desc.Flags = DXGI_ADAPTER_FLAG3_SOFTWARE;
+        let software = AdapterInfo::from_desc(&desc);
+        desc.Flags = DXGI_ADAPTER_FLAG3_REMOTE;
+        let remote = AdapterInfo::from_desc(&desc);
+
+        let all = AdapterOptions::default();
+        assert!(all.accepts_info(&hardware) && all.accepts_info(&software) && all.accepts_info(&remote));
+        let local = AdapterOptions { exclude_software: true, exclude_remote: true, ..Default::default() };
+        assert!(local.accepts_info(&hardware));
+        assert!(!local.accepts_info(&software));
+        assert!(!local.accepts_info(&remote));
+    }
+
+    #[test]
+    fn test_enumeration_options() {
+        let mut fac = AdapterFactory::new();
+        fac.configure(AdapterOptions {
+            preference: GpuPreference::MinimumPower,
+            exclude_software: true,
+            require_displays: true,
+            ..Default::default()
+        });
+        for adapter in fac.iter_adapters() {
+            assert!(!adapter.info().unwrap().software);
+            assert!(adapter.has_displays());
+        }
+        if let Some(first) = fac.get_adapter_by_idx(0) {
+            let name = first.name();
+            let found = fac.find_adapter_by_name(&name.to_lowercase()).unwrap();
+            assert_eq!(found.luid(), first.luid());
+            assert!(fac.find_adapter_by_vendor(first.info().unwrap().vendor).is_some());
+        }
+    }
 }
 
 /**
@@ -53,17 +112,40 @@ unsafe impl Sync for Adapter {}
 
 impl Adapter {
     /// Returns name of the adapter
+    ///
+    /// panics if the driver fails to describe the adapter. use [try_name][Self::try_name] to
+    /// handle the error instead.
     pub fn name(&self) -> String {
-        let mut desc: DXGI_ADAPTER_DESC3 = Default::default();
-        unsafe { self.0.GetDesc3(&mut desc).unwrap() };
-        convert_u16_to_string(&desc.Description)
+        self.try_name().unwrap()
+    }
+
+    /// returns name of the adapter or the error of `GetDesc3`.
+    pub fn try_name(&self) -> Result<String> {
+        Ok(convert_u16_to_string(&self.desc()?.Description))
     }
 
     /// returns LUID of the Adapter.
+    ///
+    /// panics if the driver fails to describe the adapter. use [try_luid][Self::try_luid] to
+    /// handle the error instead.
     pub fn luid(&self) -> LUID {
+        self.try_luid().unwrap()
+    }
+
+    /// returns LUID of the adapter or the error of `GetDesc3`.
+    pub fn try_luid(&self) -> Result<LUID> {
+        Ok(self.desc()?.AdapterLuid)
+    }
+
+    /// returns vendor, ids, memory sizes and flags of the adapter. check [AdapterInfo].
+    pub fn info(&self) -> Result<AdapterInfo> {
+        Ok(AdapterInfo::from_desc(&self.desc()?))
+    }
+
+    fn desc(&self) -> Result<DXGI_ADAPTER_DESC3> {
         let mut desc: DXGI_ADAPTER_DESC3 = Default::default();
-        unsafe { self.0.GetDesc3(&mut desc).unwrap() };
-        desc.AdapterLuid
+        unsafe { self.0.GetDesc3(&mut desc) }.map_err(|e| DDApiError::from_win("GetDesc3", e))?;
+        Ok(desc)
     }
 
     /// returns DXGI Adapter reference.
@@ -87,6 +169,11 @@ impl Adapter {
     pub fn get_display_by_idx(&self, idx: u32) -> Option<Display> {
         DisplayIterator::get_display_by_idx(&self, idx)
     }
+
+    /// returns true if at least one display is attached to this adapter.
+    pub fn has_displays(&self) -> bool {
+        self.get_display_by_idx(0).is_some()
+    }
 }
 
 /**
@@ -117,12 +204,9 @@ impl DisplayIterator {
         }
     }
     fn get_display_by_idx(adapter: &Adapter, idx: u32) -> Option<Display> {
-        let output = unsafe { adapter.0.EnumOutputs(idx) };
-        if output.is_err() {
-            None
-        } else {
-            Some(Display::new(output.unwrap().cast().unwrap()))
-        }
+        let output = unsafe { adapter.0.EnumOutputs(idx) }.ok()?;
+        // outputs that don't support IDXGIOutput6 are skipped like missing ones.
+        Some(Display::new(output.cast().ok()?))
     }
 }
 
@@ -152,6 +236,14 @@ for adapter in fac {
 }
 ```
 
+[configure][AdapterFactory::configure] changes the gpu preference and filters out adapters.
+
+```
+use win_desktop_duplication::devices::{AdapterFactory, AdapterOptions};
+let mut fac = AdapterFactory::new();
+fac.configure(AdapterOptions { exclude_software: true, require_displays: true, ..Default::default() });
+```
+
 you can also retrieve adapters by their specific index or LUID (unique identifier for current system)
 
 ```
@@ -168,6 +260,7 @@ let adapter = fac.get_adapter_by_luid(luid);
 pub struct AdapterFactory {
     fac: IDXGIFactory6,
     count: u32,
+    options: AdapterOptions,
 }
 
 unsafe impl Send for AdapterFactory {}
@@ -182,34 +275,71 @@ impl Default for AdapterFactory {
 
 impl AdapterFactory {
     /// Create new instance of AdapterFactory
+    ///
+    /// panics if the dxgi factory can't be created. use [try_new][Self::try_new] to handle the
+    /// error instead.
     pub fn new() -> Self {
-        unsafe {
-            let dxgi_factory: IDXGIFactory6 = CreateDXGIFactory2(0).unwrap();
-            Self {
-                fac: dxgi_factory,
-                count: 0,
-            }
-        }
+        Self::try_new().unwrap()
+    }
+
+    /// Create new instance of AdapterFactory or return the error of `CreateDXGIFactory2`.
+    pub fn try_new() -> Result<Self> {
+        let dxgi_factory: IDXGIFactory6 = unsafe { CreateDXGIFactory2(0) }
+            .map_err(|e| DDApiError::from_win("CreateDXGIFactory2", e))?;
+        Ok(Self {
+            fac: dxgi_factory,
+            count: 0,
+            options: Default::default(),
+        })
+    }
+
+    /// change the order and filters of enumerated adapters. resets the iterator.
+    pub fn configure(&mut self, options: AdapterOptions) {
+        self.options = options;
+        self.count = 0;
+    }
+
+    /// current enumeration options.
+    pub fn options(&self) -> &AdapterOptions {
+        &self.options
     }
 
-    /// retrieve an adapter by index
+    /// retrieve an adapter by index. the index counts only adapters passing the
+    /// [filters][AdapterOptions].
     pub fn get_adapter_by_idx(&self, idx: u32) -> Option<Adapter> {
-        let adapter: WinResult<IDXGIAdapter4> = unsafe { self.fac.EnumAdapterByGpuPreference(idx, DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE) };
-        if adapter.is_ok() {
-            Some(Adapter(adapter.unwrap().cast().unwrap()))
-        } else {
-            None
-        }
+        self.iter_adapters().nth(idx as _)
+    }
+
+    /// iterate over adapters in order of [preference][AdapterOptions::preference], skipping the
+    /// ones that don't pass the filters. unlike iterating the factory itself this doesn't change
+    /// the factory's iterator state.
+    pub fn iter_adapters(&self) -> impl Iterator<Item=Adapter> + '_ {
+        (0..).map_while(|idx| self.raw_adapter(idx)).filter(|adapter| self.options.accepts(adapter))
+    }
+
+    /// retrieve the first adapter whose name contains `name`, ignoring case.
+    pub fn find_adapter_by_name(&self, name: &str) -> Option<Adapter> {
+        let name = name.to_lowercase();
+        self.iter_adapters().find(|adapter| {
+            adapter.try_name().is_ok_and(|n| n.to_lowercase().contains(&name))
+        })
+    }
+
+    /// retrieve the first adapter made by `vendor`.
+    pub fn find_adapter_by_vendor(&self, vendor: Vendor) -> Option<Adapter> {
+        self.iter_adapters().find(|adapter| adapter.info().is_ok_and(|info| info.vendor == vendor))
+    }
+
+    // adapter at given DXGI index, ignoring filters.
+    fn raw_adapter(&self, idx: u32) -> Option<Adapter> {
+        let adapter: WinResult<IDXGIAdapter4> = unsafe { self.fac.EnumAdapterByGpuPreference(idx, self.options.preference.as_raw()) };
+        adapter.ok().map(Adapter)
     }
 
     /// retrieve an adapter by LUID
     pub fn get_adapter_by_luid(&self, luid: LUID) -> Option<Adapter> {
         let adapter = unsafe { self.fac.EnumAdapterByLuid(luid) };
-        if adapter.is_ok() {
-            Some(Adapter(adapter.unwrap()))
-        } else {
-            None
-        }
+        adapter.ok().map(Adapter)
     }
 
     /// reset the iterator status of AdapterFactory
@@ -227,11 +357,73 @@ impl Iterator for AdapterFactory {
     type Item = Adapter;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let adapter = self.get_adapter_by_idx(self.count);
-        self.count += 1;
-        if adapter.is_none() {
-            self.count = 0;
+        loop {
+            let Some(adapter) = self.raw_adapter(self.count) else {
+                self.count = 0;
+                return None;
+            };
+            self.count += 1;
+            if self.options.accepts(&adapter) {
+                return Some(adapter);
+            }
+        }
+    }
+}
+
+/// order in which [AdapterFactory] enumerates adapters.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum GpuPreference {
+    /// the order DXGI enumerates adapters in, the adapter of the primary display first.
+    Unspecified,
+    /// integrated gpus first.
+    MinimumPower,
+    /// discrete gpus first.
+    #[default]
+    HighPerformance,
+}
+
+impl GpuPreference {
+    fn as_raw(self) -> DXGI_GPU_PREFERENCE {
+        match self {
+            GpuPreference::Unspecified => DXGI_GPU_PREFERENCE_UNSPECIFIED,
+            GpuPreference::MinimumPower => DXGI_GPU_PREFERENCE_MINIMUM_POWER,
+            GpuPreference::HighPerformance => DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE,
+        }
+    }
+}
+
+/// Options of [AdapterFactory]. the default enumerates every adapter, high performance ones first.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AdapterOptions {
+    /// order of enumeration.
+    pub preference: GpuPreference,
+    /// skip software adapters such as the microsoft basic render driver.
+    pub exclude_software: bool,
+    /// skip remote adapters.
+    pub exclude_remote: bool,
+    /// skip adapters without any display attached.
+    pub require_displays: bool,
+}
+
+impl AdapterOptions {
+    /// returns true if `adapter` passes the filters.
+    pub fn accepts(&self, adapter: &Adapter) -> bool {
+        if self.exclude_software || self.exclude_remote {
+            match adapter.info() {
+                Ok(info) if !self.accepts_info(&info) => return false,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("skipping adapter that can't be described. {}", e);
+                    return false;
+                }
+            }
         }
-        adapter
+        !self.require_displays || adapter.has_displays()
+    }
+
+    // the filters that only need the adapter description.
+    pub(crate) fn accepts_info(&self, info: &AdapterInfo) -> bool {
+        let rejected = (self.exclude_software && info.software) || (self.exclude_remote && info.remote);
+        !rejected
     }
 }
@@ -0,0 +1,324 @@
+//! Stable identity of a physical monitor.
+//!
+//! [Display::name][crate::outputs::Display::name] (`\\.\DISPLAY1`) is handed out by windows in
+//! the order monitors appear and changes when they are replugged. A [DisplayId] combines what
+//! identifies the monitor itself: the EDID manufacturer, product code and serial number, the device
+//! interface path and the connector it is plugged into. Saved ids can be matched against the
+//! current displays with [DisplayId::find_in], exact matches first and fuzzy ones otherwise.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::display_id::DisplayId;
+//!
+//! // save the id of the monitor the user picked.
+//! let saved = display.id()?.to_string();
+//!
+//! // after a reboot or topology change.
+//! let saved: DisplayId = saved.parse()?;
+//! let displays: Vec<_> = adapter.iter_displays().collect();
+//! let ids = displays.iter().map(|d| d.id()).collect::<Result<Vec<_>, _>>()?;
+//! if let Some((idx, quality)) = saved.find_in(&ids) {
+//!     println!("found {} ({:?})", displays[idx].name(), quality);
+//! }
+//! ```
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::edid::Edid;
+use crate::errors::DDApiError;
+use crate::Result;
+
+#[cfg(test)]
+mod test {
+    use crate::display_id::{DisplayId, MatchQuality};
+
+    const PATH: &str = "\\\\?\\DISPLAY#DEL40F4#5&2a5d0a5e&0&UID4352#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}";
+
+    // header of an EDID with manufacturer DEL, product 0x40f4 and serial 0x4c4a4d30.
+    fn edid(product: u16, serial: u32) -> Vec<u8> {
+        let mut edid = vec![0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x10, 0xac];
+        edid.extend_from_slice(&product.to_le_bytes());
+        edid.extend_from_slice(&serial.to_le_bytes());
+        edid.resize(128, 0);
+        edid
+    }
+
+    fn monitor(serial: u32, connector: &str, gdi_name: &str) -> DisplayId {
+        DisplayId {
+            device_path: None,
+            manufacturer: Some("DEL".to_owned()),
+            product_code: Some(0x40f4),
+            serial: (serial != 0).then_some(serial),
+            connector: Some(connector.to_owned()),
+            gdi_name: gdi_name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_from_parts() {
+        let id = DisplayId::from_parts(Some(PATH), Some(&edid(0x40f4, 0x4c4a4d30)), "\\\\.\\DISPLAY1");
+        assert_eq!(id.manufacturer.as_deref(), Some("DEL"));
+        assert_eq!(id.product_code, Some(0x40f4));
+        assert_eq!(id.serial, Some(0x4c4a4d30));
+        assert_eq!(id.connector.as_deref(), Some("UID4352"));
+        assert_eq!(id.device_path.as_deref(), Some(PATH));
+
+        // without EDID the model comes from the hardware id in the path. zero serials mean none.
+        let id = DisplayId::from_parts(Some(PATH), None, "\\\\.\\DISPLAY1");
+        assert_eq!((id.manufacturer.as_deref(), id.product_code, id.serial), (Some("DEL"), Some(0x40f4), None));
+        let id = DisplayId::from_parts(None, Some(&edid(0x40f4, 0)), "\\\\.\\DISPLAY1");
+        assert_eq!((id.product_code, id.serial, id.connector), (Some(0x40f4), None, None));
+
+        // garbage is ignored.
+        let id = DisplayId::from_parts(Some("garbage"), Some(&[1, 2, 3]), "\\\\.\\DISPLAY1");
+        assert_eq!(id, DisplayId { gdi_name: "\\\\.\\DISPLAY1".to_owned(), device_path: Some("garbage".to_owned()), ..Default::default() });
+    }
+
+    #[test]
+    fn test_registry_key() {
+        assert_eq!(DisplayId::registry_key(PATH).unwrap(),
+                   "SYSTEM\\CurrentControlSet\\Enum\\DISPLAY\\DEL40F4\\5&2a5d0a5e&0&UID4352\\Device Parameters");
+        assert!(DisplayId::registry_key("\\\\.\\DISPLAY1").is_none());
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let saved = monitor(1234, "UID1", "\\\\.\\DISPLAY1");
+        // replugged into another port and renamed.
+        let moved = monitor(1234, "UID2", "\\\\.\\DISPLAY3");
+        assert_eq!(saved.match_quality(&moved), Some(MatchQuality::Exact));
+
+        // same model, different serial is a different monitor.
+        assert_eq!(saved.match_quality(&monitor(999, "UID1", "\\\\.\\DISPLAY1")), None);
+
+        let mut other_model = moved.clone();
+        other_model.product_code = Some(0x1234);
+        assert_eq!(saved.match_quality(&other_model), None);
+    }
+
+    #[test]
+    fn test_swapped_ports() {
+        // two monitors of the same model swapped ports, so each has the other's device path.
+        let with_path = |serial, port: &str| DisplayId {
+            device_path: Some(format!("\\\\?\\DISPLAY#DEL40F4#5&2a5d0a5e&0&{}#{{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}}", port)),
+            ..monitor(serial, port, "\\\\.\\DISPLAY1")
+        };
+        let saved = with_path(1234, "UID1");
+        let candidates = [with_path(5678, "UID1"), with_path(1234, "UID2")];
+        assert_eq!(saved.match_quality(&candidates[0]), None);
+        assert_eq!(saved.find_in(&candidates), Some((1, MatchQuality::Exact)));
+
+        // without serial numbers the path still decides.
+        let saved = with_path(0, "UID1");
+        assert_eq!(saved.match_quality(&with_path(0, "UID1")), Some(MatchQuality::Exact));
+        assert_eq!(saved.match_quality(&with_path(5678, "UID1")), Some(MatchQuality::Exact));
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        // two identical monitors without serial numbers.
+        let saved = monitor(0, "UID2", "\\\\.\\DISPLAY2");
+        let candidates = [monitor(0, "UID1", "\\\\.\\DISPLAY2"), monitor(0, "UID2", "\\\\.\\DISPLAY1")];
+        assert!(saved.match_quality(&candidates[1]) > saved.match_quality(&candidates[0]));
+        assert_eq!(saved.find_in(&candidates).map(|(idx, _)| idx), Some(1));
+
+        // nothing tells them apart.
+        let saved = monitor(0, "UID9", "\\\\.\\DISPLAY9");
+        assert_eq!(saved.find_in(&candidates), None);
+
+        // exact matches win over better fuzzy ones.
+        let saved = monitor(7, "UID1", "\\\\.\\DISPLAY1");
+        let candidates = [monitor(0, "UID1", "\\\\.\\DISPLAY1"), monitor(7, "UID5", "\\\\.\\DISPLAY5")];
+        assert_eq!(saved.find_in(&candidates), Some((1, MatchQuality::Exact)));
+    }
+
+    #[test]
+    fn test_string_round_trip() {
+        let id = DisplayId::from_parts(Some(PATH), Some(&edid(0x40f4, 0x4c4a4d30)), "\\\\.\\DISPLAY1");
+        let parsed: DisplayId = id.to_string().parse().unwrap();
+        assert_eq!(parsed, id);
+
+        let empty = DisplayId::default();
+        assert_eq!(empty.to_string().parse::<DisplayId>().unwrap(), empty);
+        assert!("DEL|zz".parse::<DisplayId>().is_err());
+    }
+}
+
+/// how well two [DisplayId]s match. `Exact` is better than any `Fuzzy` and higher fuzzy scores are
+/// better than lower ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum MatchQuality {
+    /// same model, no serial number to compare. the score counts matching hints (connector,
+    /// display name).
+    Fuzzy(u32),
+    /// same model and serial number, or the same device path if a serial number is missing.
+    Exact,
+}
+
+/// Identity of a physical monitor. check the [module docs][self].
+///
+/// every part is optional as some monitors have no EDID or a serial number of zero. the string form
+/// (`to_string` / `parse`) is meant for saving ids in settings.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct DisplayId {
+    /// device interface path of the monitor, e.g.
+    /// `\\?\DISPLAY#DEL40F4#5&2a5d0a5e&0&UID4352#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}`.
+    pub device_path: Option<String>,
+    /// three letter PNP manufacturer id, e.g. `DEL`.
+    pub manufacturer: Option<String>,
+    /// manufacturer's product code.
+    pub product_code: Option<u16>,
+    /// serial number from the EDID. `None` if the monitor reports zero.
+    pub serial: Option<u32>,
+    /// connector the monitor is plugged into, e.g. `UID4352`.
+    pub connector: Option<String>,
+    /// gdi name such as `\\.\DISPLAY1`. only used as a hint when matching.
+    pub gdi_name: String,
+}
+
+impl DisplayId {
+    /// build an id from the device interface path and the EDID of a monitor. the manufacturer and
+    /// product code come from the EDID, or from the path if there is no valid EDID.
+    pub fn from_parts(device_path: Option<&str>, edid: Option<&[u8]>, gdi_name: &str) -> Self {
+        let mut id = Self {
+            device_path: device_path.map(str::to_owned),
+            gdi_name: gdi_name.to_owned(),
+            ..Default::default()
+        };
+        if let Some((hardware_id, instance)) = device_path.and_then(split_device_path) {
+            if let Some((manufacturer, product)) = parse_hardware_id(hardware_id) {
+                id.manufacturer = Some(manufacturer);
+                id.product_code = Some(product);
+            }
+            id.connector = instance.rsplit('&').next()
+                .filter(|part| part.starts_with("UID"))
+                .map(str::to_owned);
+        }
+        if let Some(edid) = edid.and_then(|edid| Edid::parse(edid).ok()) {
+            id.manufacturer = Some(edid.manufacturer);
+            id.product_code = Some(edid.product_code);
+            id.serial = (edid.serial != 0).then_some(edid.serial);
+        }
+        id
+    }
+
+    /// how well `other` matches this id. `None` if they are different monitors or there is nothing
+    /// to compare.
+    pub fn match_quality(&self, other: &DisplayId) -> Option<MatchQuality> {
+        let model = |id: &DisplayId| id.manufacturer.clone().zip(id.product_code);
+        let same_model = match (model(self), model(other)) {
+            (Some(a), Some(b)) if a != b => return None,
+            (Some(_), Some(_)) => true,
+            _ => false,
+        };
+        // the device path belongs to the port, so serials decide when both monitors have one.
+        if let (Some(a), Some(b)) = (self.serial, other.serial) {
+            return (same_model && a == b).then_some(MatchQuality::Exact);
+        }
+        if self.device_path.is_some() && self.device_path == other.device_path {
+            return Some(MatchQuality::Exact);
+        }
+        if !same_model {
+            return None;
+        }
+        let mut score = 0;
+        if self.connector.is_some() && self.connector == other.connector {
+            score += 2;
+        }
+        if !self.gdi_name.is_empty() && self.gdi_name == other.gdi_name {
+            score += 1;
+        }
+        Some(MatchQuality::Fuzzy(score))
+    }
+
+    /// find this monitor in `candidates`. returns the index of the first exact match, or of the
+    /// best fuzzy match if no other candidate matches equally well.
+    pub fn find_in<'a>(&self, candidates: impl IntoIterator<Item=&'a DisplayId>) -> Option<(usize, MatchQuality)> {
+        let mut best: Option<(usize, MatchQuality)> = None;
+        let mut ambiguous = false;
+        for (idx, candidate) in candidates.into_iter().enumerate() {
+            let Some(quality) = self.match_quality(candidate) else { continue };
+            if quality == MatchQuality::Exact {
+                return Some((idx, quality));
+            }
+            match best {
+                Some((_, q)) if q > quality => {}
+                Some((_, q)) if q == quality => ambiguous = true,
+                _ => {
+                    best = Some((idx, quality));
+                    ambiguous = false;
+                }
+            }
+        }
+        if ambiguous { None } else { best }
+    }
+
+    /// registry key holding the monitor's `EDID` value, relative to `HKEY_LOCAL_MACHINE`.
+    pub(crate) fn registry_key(device_path: &str) -> Option<String> {
+        let (hardware_id, instance) = split_device_path(device_path)?;
+        Some(format!("SYSTEM\\CurrentControlSet\\Enum\\DISPLAY\\{}\\{}\\Device Parameters", hardware_id, instance))
+    }
+}
+
+// `\\?\DISPLAY#DEL40F4#5&2a5d0a5e&0&UID4352#{guid}` -> ("DEL40F4", "5&2a5d0a5e&0&UID4352")
+fn split_device_path(path: &str) -> Option<(&str, &str)> {
+    let mut parts = path.split('#');
+    if !parts.next()?.to_ascii_uppercase().ends_with("DISPLAY") {
+        return None;
+    }
+    let hardware_id = parts.next().filter(|p| !p.is_empty())?;
+    let instance = parts.next().filter(|p| !p.is_empty())?;
+    Some((hardware_id, instance))
+}
+
+// `DEL40F4` -> ("DEL", 0x40f4)
+fn parse_hardware_id(hardware_id: &str) -> Option<(String, u16)> {
+    if hardware_id.len() != 7 || !hardware_id.is_char_boundary(3) {
+        return None;
+    }
+    let (manufacturer, product) = hardware_id.split_at(3);
+    if !manufacturer.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((manufacturer.to_ascii_uppercase(), u16::from_str_radix(product, 16).ok()?))
+}
+
+// fields are separated by `|`. none of them can contain one.
+impl Display for DisplayId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}|{}|{}|{}|{}|{}",
+               self.manufacturer.as_deref().unwrap_or_default(),
+               self.product_code.map(|p| format!("{:04X}", p)).unwrap_or_default(),
+               self.serial.map(|s| format!("{:08X}", s)).unwrap_or_default(),
+               self.connector.as_deref().unwrap_or_default(),
+               self.gdi_name,
+               self.device_path.as_deref().unwrap_or_default())
+    }
+}
+
+impl FromStr for DisplayId {
+    type Err = DDApiError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<_> = s.splitn(6, '|').collect();
+        let [manufacturer, product, serial, connector, gdi_name, device_path] = parts[..] else {
+            return Err(DDApiError::bad_param(format!("invalid display id: {}", s)));
+        };
+        let hex = |v: &str, what: &str| -> Result<Option<u32>> {
+            if v.is_empty() {
+                return Ok(None);
+            }
+            u32::from_str_radix(v, 16).map(Some).map_err(|_| DDApiError::bad_param(format!("invalid {} in display id: {}", what, v)))
+        };
+        let non_empty = |v: &str| (!v.is_empty()).then(|| v.to_owned());
+        Ok(Self {
+            device_path: non_empty(device_path),
+            manufacturer: non_empty(manufacturer),
+            product_code: hex(product, "product code")?.map(|p| p as u16),
+            serial: hex(serial, "serial")?,
+            connector: non_empty(connector),
+            gdi_name: gdi_name.to_owned(),
+        })
+    }
+}
@@ -0,0 +1,408 @@
+//! Arranging several displays in one step.
+//!
+//! A [DisplayLayout] says where each display sits on the virtual desktop, which one is primary and
+//! which mode it uses. [DisplayLayout::problems] finds what windows would reject or silently fix
+//! up: overlapping displays, displays not touching the rest, and a primary display missing or not
+//! at the desktop origin. [DisplayLayout::apply] stages the settings of every display with
+//! `CDS_NORESET` and switches to all of them with a single commit, so the desktop never passes
+//! through a half applied layout.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::display_layout::DisplayLayout;
+//! use win_desktop_duplication::geometry::Point;
+//!
+//! // move the second display from the right to the left of the primary one.
+//! let mut layout = DisplayLayout::current()?;
+//! let second = layout.find_mut("\\\\.\\DISPLAY2").unwrap();
+//! second.position = Point::new(-(second.rect().width() as i32), 0);
+//! layout.apply()?;
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use log::error;
+use windows::core::PCSTR;
+use windows::Win32::Graphics::Gdi::{CDS_NORESET, CDS_SET_PRIMARY, CDS_TEST, CDS_TYPE, CDS_UPDATEREGISTRY, ChangeDisplaySettingsExA, DM_POSITION};
+use windows::Win32::Foundation::POINTL;
+
+use crate::errors::DDApiError;
+use crate::geometry::{Point, Rect};
+use crate::outputs::{c_display_name, change_display_settings, devmode, disp_change_result, DisplayMode, DisplayOrientation};
+use crate::watcher::Snapshot;
+use crate::Result;
+
+#[cfg(test)]
+mod test {
+    use crate::display_layout::{DisplayLayout, DisplayPlacement, LayoutProblem};
+    use crate::geometry::{Point, Rect};
+    use crate::outputs::{DisplayMode, DisplayOrientation};
+
+    fn placement(name: &str, x: i32, y: i32, width: u32, height: u32, primary: bool) -> DisplayPlacement {
+        DisplayPlacement {
+            name: name.to_owned(),
+            position: Point::new(x, y),
+            mode: DisplayMode { width, height, refresh_num: 60, refresh_den: 1, ..Default::default() },
+            primary,
+        }
+    }
+
+    // 1080p primary, a 1440p display on its right and a 1080p one on its left, bottom aligned.
+    fn layout() -> DisplayLayout {
+        DisplayLayout {
+            displays: vec![
+                placement("\\\\.\\DISPLAY1", 0, 0, 1920, 1080, true),
+                placement("\\\\.\\DISPLAY2", 1920, -360, 2560, 1440, false),
+                placement("\\\\.\\DISPLAY3", -1920, 0, 1920, 1080, false),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_valid_layout() {
+        let layout = layout();
+        assert_eq!(layout.problems(), vec![]);
+        assert!(layout.validate().is_ok());
+        assert_eq!(layout.bounds(), Rect::new(-1920, -360, 4480, 1080));
+    }
+
+    #[test]
+    fn test_overlap_and_gap() {
+        let mut overlapping = layout();
+        overlapping.displays[1].position = Point::new(1800, 0);
+        assert_eq!(overlapping.problems(), vec![
+            LayoutProblem::Overlap("\\\\.\\DISPLAY1".to_owned(), "\\\\.\\DISPLAY2".to_owned()),
+        ]);
+
+        let mut gap = layout();
+        gap.displays[2].position = Point::new(-1930, 0);
+        assert_eq!(gap.problems(), vec![LayoutProblem::NotTouching("\\\\.\\DISPLAY3".to_owned())]);
+
+        // touching at a corner only doesn't count.
+        let mut corner = layout();
+        corner.displays[2].position = Point::new(-1920, -1080);
+        assert_eq!(corner.problems(), vec![LayoutProblem::NotTouching("\\\\.\\DISPLAY3".to_owned())]);
+        assert!(corner.validate().is_err());
+
+        // rotated displays take their rotated size.
+        let mut rotated = layout();
+        rotated.displays[1].mode.orientation = DisplayOrientation::Rotate90;
+        rotated.displays[1].position = Point::new(1920, -1440);
+        assert_eq!(rotated.displays[1].rect(), Rect::new(1920, -1440, 3360, 1120));
+        assert_eq!(rotated.problems(), vec![]);
+    }
+
+    #[test]
+    fn test_primary() {
+        let mut none = layout();
+        none.displays[0].primary = false;
+        assert_eq!(none.problems(), vec![LayoutProblem::NoPrimary]);
+
+        let mut two = layout();
+        two.displays[2].primary = true;
+        assert_eq!(two.problems(), vec![
+            LayoutProblem::MultiplePrimaries(vec!["\\\\.\\DISPLAY1".to_owned(), "\\\\.\\DISPLAY3".to_owned()]),
+        ]);
+
+        let mut moved = layout();
+        for display in moved.displays.iter_mut() {
+            display.position.x += 10;
+        }
+        assert_eq!(moved.problems(), vec![LayoutProblem::PrimaryNotAtOrigin("\\\\.\\DISPLAY1".to_owned())]);
+
+        // switching the primary display moves the origin with it.
+        let mut switched = layout();
+        switched.set_primary("\\\\.\\DISPLAY2").unwrap();
+        assert_eq!(switched.problems(), vec![]);
+        assert_eq!(switched.displays[0].rect(), Rect::new(-1920, 360, 0, 1440));
+        assert!(!switched.displays[0].primary && switched.displays[1].primary);
+        assert!(switched.set_primary("\\\\.\\DISPLAY9").is_err());
+    }
+
+    #[test]
+    fn test_unlisted_displays() {
+        let current = layout();
+        // only the display on the right is listed, moved onto the one on the left.
+        let partial = DisplayLayout::new(vec![placement("\\\\.\\DISPLAY2", -2000, 0, 2560, 1440, false)]);
+        assert_eq!(partial.problems(), vec![LayoutProblem::NoPrimary]);
+
+        let complete = partial.complete(&current);
+        assert_eq!(complete.displays.len(), 3);
+        assert_eq!(complete.displays[0].position, Point::new(-2000, 0));
+        assert_eq!(complete.problems(), vec![
+            LayoutProblem::Overlap("\\\\.\\DISPLAY2".to_owned(), "\\\\.\\DISPLAY1".to_owned()),
+            LayoutProblem::Overlap("\\\\.\\DISPLAY2".to_owned(), "\\\\.\\DISPLAY3".to_owned()),
+        ]);
+
+        // a new primary display demotes the old one.
+        let mut primary = current.clone();
+        primary.set_primary("\\\\.\\DISPLAY3").unwrap();
+        let primary = DisplayLayout::new(vec![primary.displays[2].clone()]).complete(&current);
+        assert_eq!(primary.displays.iter().filter(|d| d.primary).count(), 1);
+        assert!(primary.find("\\\\.\\DISPLAY3").unwrap().primary);
+    }
+
+    #[test]
+    fn test_bad_displays() {
+        assert_eq!(DisplayLayout::default().problems(), vec![LayoutProblem::Empty]);
+
+        let mut layout = layout();
+        layout.displays.push(placement("\\\\.\\DISPLAY3", -3840, 0, 1920, 1080, false));
+        layout.displays.push(placement("\\\\.\\DISPLAY4", 0, 1080, 0, 0, false));
+        assert_eq!(layout.problems(), vec![
+            LayoutProblem::DuplicateDisplay("\\\\.\\DISPLAY3".to_owned()),
+            LayoutProblem::EmptyMode("\\\\.\\DISPLAY4".to_owned()),
+        ]);
+    }
+}
+
+/// where a display goes in a [DisplayLayout] and the mode it uses.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DisplayPlacement {
+    /// name of the display, as returned by [Display::name][crate::outputs::Display::name].
+    pub name: String,
+    /// top left corner on the virtual desktop.
+    pub position: Point,
+    pub mode: DisplayMode,
+    pub primary: bool,
+}
+
+impl DisplayPlacement {
+    /// rect covered on the virtual desktop. width and height are swapped for portrait
+    /// orientations.
+    pub fn rect(&self) -> Rect {
+        let (width, height) = match self.mode.orientation {
+            DisplayOrientation::Rotate90 | DisplayOrientation::Rotate270 => (self.mode.height, self.mode.width),
+            DisplayOrientation::NoRotation | DisplayOrientation::Rotate180 => (self.mode.width, self.mode.height),
+        };
+        Rect::new(self.position.x, self.position.y,
+                  self.position.x + width as i32, self.position.y + height as i32)
+    }
+}
+
+/// something wrong with a [DisplayLayout].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum LayoutProblem {
+    /// the layout has no displays.
+    Empty,
+    /// the display is placed more than once.
+    DuplicateDisplay(String),
+    /// the display's mode has no width or height.
+    EmptyMode(String),
+    NoPrimary,
+    MultiplePrimaries(Vec<String>),
+    /// the primary display's top left corner is not at `(0, 0)`.
+    PrimaryNotAtOrigin(String),
+    /// both displays cover the same part of the desktop.
+    Overlap(String, String),
+    /// the display shares no edge with the displays connected to the primary one.
+    NotTouching(String),
+}
+
+impl Display for LayoutProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutProblem::Empty => write!(f, "the layout has no displays"),
+            LayoutProblem::DuplicateDisplay(name) => write!(f, "{} is placed more than once", name),
+            LayoutProblem::EmptyMode(name) => write!(f, "{} has a display mode without width or height", name),
+            LayoutProblem::NoPrimary => write!(f, "no display is primary"),
+            LayoutProblem::MultiplePrimaries(names) => write!(f, "more than one primary display: {}", names.join(", ")),
+            LayoutProblem::PrimaryNotAtOrigin(name) => write!(f, "the primary display {} is not at (0, 0)", name),
+            LayoutProblem::Overlap(a, b) => write!(f, "{} and {} overlap", a, b),
+            LayoutProblem::NotTouching(name) => write!(f, "{} doesn't touch the other displays", name),
+        }
+    }
+}
+
+/// Positions, modes and primary display of several displays. check the [module docs][self].
+///
+/// displays that are not part of the layout keep their settings, [apply][Self::apply] makes sure
+/// the layout fits around them.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DisplayLayout {
+    pub displays: Vec<DisplayPlacement>,
+}
+
+impl DisplayLayout {
+    pub fn new(displays: Vec<DisplayPlacement>) -> Self {
+        Self { displays }
+    }
+
+    /// layout of all attached displays right now.
+    pub fn current() -> Result<Self> {
+        Ok(Self::from_snapshot(&Snapshot::capture()?))
+    }
+
+    /// layout of the attached displays of `snapshot`.
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        Self::new(snapshot.displays.iter()
+            .filter(|d| d.geometry.attached)
+            .map(|d| DisplayPlacement {
+                name: d.name.clone(),
+                position: Point::new(d.geometry.desktop.left, d.geometry.desktop.top),
+                mode: d.mode.clone(),
+                primary: d.geometry.primary,
+            })
+            .collect())
+    }
+
+    /// find a display by its name.
+    pub fn find(&self, name: &str) -> Option<&DisplayPlacement> {
+        self.displays.iter().find(|d| d.name == name)
+    }
+
+    /// find a display by its name to change its placement.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut DisplayPlacement> {
+        self.displays.iter_mut().find(|d| d.name == name)
+    }
+
+    /// make the display `name` the primary one, moving all displays so it sits at the origin.
+    /// fails with [ErrorKind::BadParam][crate::errors::ErrorKind::BadParam] if the display is not
+    /// part of the layout.
+    pub fn set_primary(&mut self, name: &str) -> Result<()> {
+        let origin = self.find(name)
+            .ok_or_else(|| DDApiError::bad_param(format!("{} is not part of the layout", name)))?
+            .position;
+        for display in self.displays.iter_mut() {
+            display.primary = display.name == name;
+            display.position = Point::new(display.position.x - origin.x, display.position.y - origin.y);
+        }
+        Ok(())
+    }
+
+    /// smallest rect containing all displays.
+    pub fn bounds(&self) -> Rect {
+        self.displays.iter()
+            .map(|d| d.rect())
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or_default()
+    }
+
+    /// everything keeping this layout from being applied. empty if the layout is valid. only the
+    /// listed displays are checked, use [complete][Self::complete] to include the others.
+    pub fn problems(&self) -> Vec<LayoutProblem> {
+        if self.displays.is_empty() {
+            return vec![LayoutProblem::Empty];
+        }
+        let mut problems = Vec::new();
+
+        let mut names = HashSet::new();
+        for display in &self.displays {
+            if !names.insert(display.name.as_str()) {
+                problems.push(LayoutProblem::DuplicateDisplay(display.name.clone()));
+            }
+        }
+        for display in self.displays.iter().filter(|d| d.rect().is_empty()) {
+            problems.push(LayoutProblem::EmptyMode(display.name.clone()));
+        }
+
+        let primaries: Vec<_> = self.displays.iter().filter(|d| d.primary).collect();
+        match primaries.as_slice() {
+            [] => problems.push(LayoutProblem::NoPrimary),
+            [primary] if primary.position != Point::new(0, 0) => {
+                problems.push(LayoutProblem::PrimaryNotAtOrigin(primary.name.clone()));
+            }
+            [_] => {}
+            _ => problems.push(LayoutProblem::MultiplePrimaries(primaries.iter().map(|d| d.name.clone()).collect())),
+        }
+
+        let rects: Vec<_> = self.displays.iter().map(|d| d.rect()).collect();
+        for (i, a) in rects.iter().enumerate() {
+            for (j, b) in rects.iter().enumerate().skip(i + 1) {
+                if a.intersection(b).is_some() {
+                    problems.push(LayoutProblem::Overlap(self.displays[i].name.clone(), self.displays[j].name.clone()));
+                }
+            }
+        }
+
+        // walk from the primary display (or the first one) to every display sharing an edge.
+        // overlapping displays are already reported and count as connected.
+        let start = self.displays.iter().position(|d| d.primary).unwrap_or(0);
+        let mut reached = vec![false; rects.len()];
+        reached[start] = true;
+        let mut pending = vec![start];
+        while let Some(i) = pending.pop() {
+            for j in 0..rects.len() {
+                if !reached[j] && (touches(&rects[i], &rects[j]) || rects[i].intersection(&rects[j]).is_some()) {
+                    reached[j] = true;
+                    pending.push(j);
+                }
+            }
+        }
+        for (display, _) in self.displays.iter().zip(reached).filter(|(d, reached)| !reached && !d.rect().is_empty()) {
+            problems.push(LayoutProblem::NotTouching(display.name.clone()));
+        }
+        problems
+    }
+
+    /// fails with [ErrorKind::BadParam][crate::errors::ErrorKind::BadParam] naming the first
+    /// [problem][Self::problems] of this layout.
+    pub fn validate(&self) -> Result<()> {
+        match self.problems().first() {
+            None => Ok(()),
+            Some(problem) => Err(DDApiError::bad_param(problem.to_string()).with_operation("DisplayLayout::validate")),
+        }
+    }
+
+    /// this layout plus the displays of `current` it doesn't list, which keep their placement.
+    /// they stop being primary if this layout has a primary display.
+    pub fn complete(&self, current: &DisplayLayout) -> DisplayLayout {
+        let has_primary = self.displays.iter().any(|d| d.primary);
+        let mut displays = self.displays.clone();
+        for display in current.displays.iter().filter(|d| self.find(&d.name).is_none()) {
+            displays.push(DisplayPlacement { primary: display.primary && !has_primary, ..display.clone() });
+        }
+        Self::new(displays)
+    }
+
+    /// switch all displays to this layout at once.
+    ///
+    /// the layout together with the attached displays it doesn't list (see [complete][Self::complete])
+    /// is validated, and every mode is checked with `CDS_TEST` before anything changes. then the
+    /// settings of each display are written to the registry with `CDS_NORESET` and applied together
+    /// by a single commit, so the layout persists across restarts. if staging or the commit fails,
+    /// the layout from before is staged and committed again and the error is returned.
+    pub fn apply(&self) -> Result<()> {
+        let original = Self::current()?;
+        self.complete(&original).validate()?;
+        for display in &self.displays {
+            change_display_settings(&c_display_name(&display.name)?, &display.mode, CDS_TEST)?;
+        }
+
+        if let Err(e) = self.stage_and_commit() {
+            if let Err(restore) = original.stage_and_commit() {
+                error!("failed to restore the display layout after a failed change. {}", restore);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // writes every display to the registry without applying it, then applies all of them.
+    fn stage_and_commit(&self) -> Result<()> {
+        for display in &self.displays {
+            let mut display_mode = devmode(&display.mode);
+            display_mode.Anonymous1.Anonymous2.dmPosition = POINTL { x: display.position.x, y: display.position.y };
+            display_mode.dmFields |= DM_POSITION;
+            let mut flags = CDS_UPDATEREGISTRY | CDS_NORESET;
+            if display.primary {
+                flags |= CDS_SET_PRIMARY;
+            }
+            let name = c_display_name(&display.name)?;
+            let resp = unsafe { ChangeDisplaySettingsExA(PCSTR(name.as_ptr() as _), Some(&display_mode), None, flags, None) };
+            disp_change_result(resp)?;
+        }
+
+        // a call without a display applies everything staged.
+        let resp = unsafe { ChangeDisplaySettingsExA(PCSTR::null(), None, None, CDS_TYPE(0), None) };
+        disp_change_result(resp)
+    }
+}
+
+// the displays share an edge, not just a corner.
+fn touches(a: &Rect, b: &Rect) -> bool {
+    let overlap = |a0: i32, a1: i32, b0: i32, b1: i32| a0.max(b0) < a1.min(b1);
+    ((a.right == b.left || b.right == a.left) && overlap(a.top, a.bottom, b.top, b.bottom))
+        || ((a.bottom == b.top || b.bottom == a.top) && overlap(a.left, a.right, b.left, b.right))
+}
@@ -12,15 +12,12 @@ use std::time::Duration;
 
 use futures::StreamExt;
 use log::{debug, error, trace, warn};
-use tokio::task::spawn_blocking;
-use tokio::time;
-use tokio::time::{Instant, Interval, MissedTickBehavior, sleep};
 use windows::core::Interface;
 use windows::core::Result as WinResult;
-use windows::Win32::Foundation::{BOOL, E_ACCESSDENIED, E_INVALIDARG, GENERIC_READ, GetLastError, POINT};
+use windows::Win32::Foundation::{BOOL, GENERIC_READ, POINT};
 use windows::Win32::Graphics::Direct3D::{D3D_DRIVER_TYPE_UNKNOWN, D3D_FEATURE_LEVEL, D3D_FEATURE_LEVEL_11_1};
-use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_FLAG, D3D11_BIND_RENDER_TARGET, D3D11_CREATE_DEVICE_FLAG, D3D11_RESOURCE_MISC_FLAG, D3D11_RESOURCE_MISC_GDI_COMPATIBLE, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE, D3D11_USAGE_DEFAULT, D3D11CreateDevice, ID3D11Device4, ID3D11DeviceContext4};
-use windows::Win32::Graphics::Dxgi::{DXGI_ERROR_ACCESS_DENIED, DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_INVALID_CALL, DXGI_ERROR_MORE_DATA, DXGI_ERROR_SESSION_DISCONNECTED, DXGI_ERROR_UNSUPPORTED, DXGI_ERROR_WAIT_TIMEOUT, DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTDUPL_POINTER_SHAPE_INFO, IDXGIDevice4, IDXGIOutputDuplication, IDXGIResource, IDXGISurface1};
+use windows::Win32::Graphics::Direct3D11::{D3D11_BIND_FLAG, D3D11_BIND_RENDER_TARGET, D3D11_CREATE_DEVICE_FLAG, D3D11_RESOURCE_MISC_FLAG, D3D11_RESOURCE_MISC_GDI_COMPATIBLE, D3D11_SDK_VERSION, D3D11_SUBRESOURCE_DATA, D3D11_TEXTURE2D_DESC, D3D11_USAGE, D3D11_USAGE_DEFAULT, D3D11CreateDevice, ID3D11Device4, ID3D11DeviceContext4};
+use windows::Win32::Graphics::Dxgi::{DXGI_ERROR_ACCESS_DENIED, DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_INVALID_CALL, DXGI_ERROR_MORE_DATA, DXGI_ERROR_WAIT_TIMEOUT, DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTDUPL_POINTER_SHAPE_INFO, IDXGIDevice4, IDXGIOutputDuplication, IDXGIResource, IDXGISurface1};
 use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_SAMPLE_DESC};
 use windows::Win32::Graphics::Gdi::DeleteObject;
 use windows::Win32::System::StationsAndDesktops::{DESKTOP_ACCESS_FLAGS, OpenInputDesktop, SetThreadDesktop};
@@ -28,10 +25,14 @@ use windows::Win32::System::StationsAndDesktops::DF_ALLOWOTHERACCOUNTHOOK;
 use windows::Win32::UI::WindowsAndMessaging::{CURSOR_SHOWING, CURSORINFO, DI_NORMAL, DrawIconEx, GetCursorInfo, GetIconInfo, HCURSOR};
 
 use crate::devices::Adapter;
-use crate::errors::DDApiError;
-use crate::outputs::{Display, DisplayVSyncStream};
+use crate::errors::{DDApiError, ErrorKind};
+use crate::outputs::{Display, DisplayMode, DisplayVSyncStream};
+use crate::placeholder::{convert_placeholder, PlaceholderOptions, PlaceholderStyle, render_placeholder};
+use crate::pool::{FramePool, PooledTexture};
 use crate::Result;
-use crate::texture::{Texture, TextureDesc};
+use crate::session::{SessionEvent, SessionEvents, SessionState, SessionStateMachine};
+use crate::tex_reader::{CpuFrame, TextureReader};
+use crate::texture::{ColorFormat, Texture, TextureDesc};
 
 #[cfg(test)]
 mod test {
@@ -43,9 +44,11 @@ mod test {
     use log::LevelFilter::Debug;
     use tokio::time::interval;
 
-    use crate::{DDApiError, DuplicationApiOptions};
+    use crate::DuplicationApiOptions;
+    use crate::errors::ErrorKind;
     use crate::devices::AdapterFactory;
     use crate::duplication::DesktopDuplicationApi;
+    use crate::mode_change::ModeChangeGuard;
     use crate::outputs::DisplayMode;
     use crate::utils::{co_init, set_process_dpi_awareness};
 
@@ -73,7 +76,8 @@ mod test {
             let mut dupl = DesktopDuplicationApi::new(adapter, output.clone()).unwrap();
             let curr_mode = output.get_current_display_mode().unwrap();
             dupl.configure(DuplicationApiOptions {
-                skip_cursor: true
+                skip_cursor: true,
+                ..Default::default()
             });
             // let new_mode = DisplayMode {
             //     width: 1920,
@@ -93,7 +97,7 @@ mod test {
                 select! {
                     tex = dupl.acquire_next_vsync_frame().fuse()=>{
                         match &tex {
-                            Err(DDApiError::AccessDenied)| Err(DDApiError::AccessLost)  =>  {
+                            Err(e) if matches!(e.kind(), ErrorKind::AccessDenied | ErrorKind::AccessLost)  =>  {
                                 println!("error: {:?}",tex.err())
                             }
                             Err(e)=>{
@@ -141,6 +145,7 @@ mod test {
             hdr: false,
         };
 
+        let mut guard = None;
         let mut counter = 0;
         let mut secs = 0;
         let instant = Instant::now();
@@ -158,9 +163,9 @@ mod test {
                 secs += 1;
                 if secs == 1 {
                     println!("1 secs");
-                    output.set_display_mode(&new_mode).unwrap();
+                    guard = Some(ModeChangeGuard::apply(&output, &new_mode, Default::default()).unwrap());
                 } else if secs == 5 {
-                    output.set_display_mode(&curr_mode).unwrap();
+                    guard.take().unwrap().restore().unwrap();
                     break;
                 }
             }
@@ -212,15 +217,28 @@ pub struct DesktopDuplicationApi {
     d3d_device: ID3D11Device4,
     d3d_ctx: ID3D11DeviceContext4,
     output: Display,
-    vsync_stream: DisplayVSyncStream,
+    pub(crate) vsync_stream: DisplayVSyncStream,
     dupl: Option<IDXGIOutputDuplication>,
 
     options: DuplicationApiOptions,
 
     state: DuplicationState,
+    session: SessionStateMachine,
+    frame_pool: FramePool<Texture>,
+    frame_pool_desc: Option<TextureDesc>,
 
     last_frame_info: Option<DXGI_OUTDUPL_FRAME_INFO>,
     last_cursor_shape: Option<CursorShape>,
+
+    // placeholder shown while the desktop can't be captured, and the frame captured before that.
+    synthetic: bool,
+    placeholder_frame: Option<Texture>,
+    last_good_frame: Option<Texture>,
+    reader: Option<TextureReader>,
+    // format of the last duplicated frame and display mode the placeholder is generated for.
+    // the mode is queried again after the duplication is recreated, e.g. after a mode change.
+    last_format: Option<ColorFormat>,
+    placeholder_mode: Option<DisplayMode>,
 }
 #[repr(C)]
 #[derive(Clone, Debug, Default)]
@@ -230,6 +248,9 @@ pub struct FrameInfo {
     pub accumulated_frames: u32,
     pub protected_content_masked_out: bool,
     pub pointer_info: CursorInfo,
+    /// the frame is a generated [placeholder][crate::placeholder] and not a capture of the
+    /// desktop.
+    pub synthetic: bool,
 }
 
 #[repr(C)]
@@ -298,7 +319,9 @@ impl DesktopDuplicationApi {
     /// If you wish to use your own directx device, context, use [new_with][Self::new_with] method
     ///
     /// this method fails with
-    /// * [DDApiError::Unsupported] when the application's dpi awareness is not set. use [crate::set_process_dpi_awareness]
+    /// * [ErrorKind::Unsupported] when the application's dpi awareness is not set. use [crate::set_process_dpi_awareness]
+    /// * [ErrorKind::Unexpected] when other applications hold all duplication slots of the output.
+    ///   creating the api again later may succeed.
     pub fn new(adapter: Adapter, output: Display) -> Result<Self> {
         let (device, ctx) = Self::create_device(&adapter)?;
         Self::new_with(device, ctx, output)
@@ -313,10 +336,19 @@ impl DesktopDuplicationApi {
             vsync_stream: output.get_vsync_stream(),
             output,
             dupl: Some(dupl),
+            frame_pool: FramePool::new(DuplicationApiOptions::default().frame_pool_size),
+            frame_pool_desc: None,
             options: Default::default(),
             state: Default::default(),
+            session: SessionStateMachine::new(),
             last_frame_info: None,
             last_cursor_shape: None,
+            synthetic: false,
+            placeholder_frame: None,
+            last_good_frame: None,
+            reader: None,
+            last_format: None,
+            placeholder_mode: None,
         })
     }
 
@@ -329,18 +361,20 @@ impl DesktopDuplicationApi {
     ///
     /// ## Recoverable errors
     /// these can be recovered by just calling the function again after this error.
-    /// * [DDApiError::AccessLost] - when desktop mode switch happens (resolution change) or desktop
+    /// * [ErrorKind::AccessLost] - when desktop mode switch happens (resolution change) or desktop
     /// changes. (going to lock screen etc).
-    /// * [DDApiError::AccessDenied] - when windows opens a secure environment, this application
+    /// * [ErrorKind::AccessDenied] - when windows opens a secure environment, this application
     /// will be denied access.
     ///
     /// ## Non-recoverable errors
-    /// * [DDApiError::Unexpected] - this type of error cant be recovered from. the application should
+    /// * [ErrorKind::Unexpected] - this type of error cant be recovered from. the application should
     /// drop the struct and re-create a new instance.
+    ///
+    /// [Supervisor][crate::supervisor::Supervisor] does that automatically.
     pub async fn acquire_next_vsync_frame(&mut self) -> Result<Texture> {
         // wait for vsync
         if (self.vsync_stream.next().await).is_some_and(|r| r.is_err()) {
-            return Err(DDApiError::Unexpected("DisplayVSyncStream failed unexpectedly".to_owned()));
+            return Err(DDApiError::unexpected("DisplayVSyncStream failed unexpectedly"));
         }
         // adding 2 ms sleep to ensure frame would be available
         // this is moved from vsync code for better control
@@ -355,6 +389,39 @@ impl DesktopDuplicationApi {
         res
     }
 
+    /// same as [acquire_next_vsync_frame][Self::acquire_next_vsync_frame] but returns a frame leased
+    /// from the frame pool. check [acquire_next_pooled_frame][Self::acquire_next_pooled_frame] for
+    /// more details.
+    pub async fn acquire_next_vsync_pooled_frame(&mut self) -> Result<PooledTexture> {
+        if (self.vsync_stream.next().await).is_some_and(|r| r.is_err()) {
+            return Err(DDApiError::unexpected("DisplayVSyncStream failed unexpectedly"));
+        }
+        self.acquire_next_pooled_frame(Duration::from_millis(0))
+    }
+
+    /// unlike [acquire_next_frame][Self::acquire_next_frame], which always returns the same
+    /// texture, this copies the frame into a texture leased from a pool of
+    /// [frame_pool_size][DuplicationApiOptions::frame_pool_size] textures. the texture is not
+    /// touched by later frames until the returned lease is dropped.
+    ///
+    /// this fails with the same errors as [acquire_next_frame][Self::acquire_next_frame] and
+    /// additionally with the recoverable [ErrorKind::PoolExhausted] when all textures of the pool
+    /// are still leased.
+    pub fn acquire_next_pooled_frame(&mut self, timeout: Duration) -> Result<PooledTexture> {
+        let frame = self.acquire_next_frame(timeout)?;
+        let desc = frame.desc();
+        if self.frame_pool_desc != Some(desc) {
+            // frame shape changed (display mode switch etc), old textures can't be reused.
+            self.frame_pool.clear();
+            self.frame_pool_desc = Some(desc);
+        }
+        let pooled = self.frame_pool.lease_with(|| {
+            self.create_texture(desc, D3D11_USAGE_DEFAULT, D3D11_BIND_RENDER_TARGET, Default::default())
+        })?;
+        unsafe { self.d3d_ctx.CopyResource(pooled.as_raw_ref(), frame.as_raw_ref()); }
+        Ok(pooled)
+    }
+
     pub fn create_device(adapter: &Adapter) -> Result<(ID3D11Device4, ID3D11DeviceContext4)> {
         let feature_levels = [D3D_FEATURE_LEVEL_11_1];
         let mut feature_level: D3D_FEATURE_LEVEL = Default::default();
@@ -368,62 +435,80 @@ impl DesktopDuplicationApi {
                               Some(&mut d3d_device), Some(&mut feature_level),
                               Some(&mut d3d_ctx))
         };
-        if resp.is_err() {
-            Err(DDApiError::Unexpected(format!("faild d3d11 create device. {:?}", resp)))
-        } else {
-            Ok((d3d_device.unwrap().cast().unwrap(), d3d_ctx.unwrap().cast().unwrap()))
-        }
+        resp.map_err(|e| DDApiError::from_win("D3D11CreateDevice", e))?;
+        let (Some(d3d_device), Some(d3d_ctx)) = (d3d_device, d3d_ctx) else {
+            return Err(DDApiError::unexpected("D3D11CreateDevice succeeded without a device").with_operation("D3D11CreateDevice"));
+        };
+        let device = d3d_device.cast().map_err(|e| DDApiError::from_win("ID3D11Device4", e))?;
+        let ctx = d3d_ctx.cast().map_err(|e| DDApiError::from_win("ID3D11DeviceContext4", e))?;
+        Ok((device, ctx))
     }
 
     fn create_dupl_output(dev: &ID3D11Device4, output: &Display) -> Result<IDXGIOutputDuplication> {
         let supported_formats = [DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT];
-        let device: IDXGIDevice4 = dev.cast().unwrap();
+        let device: IDXGIDevice4 = dev.cast().map_err(|e| DDApiError::from_win("IDXGIDevice4", e))?;
         let dupl: WinResult<IDXGIOutputDuplication> = unsafe { output.as_raw_ref().DuplicateOutput1(&device, 0, &supported_formats) };
 
-        if let Err(err) = dupl {
-            return match err.code() {
-                E_INVALIDARG => {
-                    Err(DDApiError::BadParam(format!("failed to create duplicate output. {:?}", err)))
-                }
-                E_ACCESSDENIED => {
-                    Err(DDApiError::AccessDenied)
-                }
-                DXGI_ERROR_UNSUPPORTED => {
-                    Err(DDApiError::Unsupported)
-                }
-                DXGI_ERROR_SESSION_DISCONNECTED => {
-                    Err(DDApiError::Disconnected)
-                }
-                _ => {
-                    Err(DDApiError::Unexpected(err.to_string()))
-                }
-            };
-        }
-        Ok(dupl.unwrap())
+        // E_INVALIDARG, E_ACCESSDENIED, DXGI_ERROR_UNSUPPORTED and DXGI_ERROR_SESSION_DISCONNECTED
+        // map to their own error kinds.
+        dupl.map_err(|err| DDApiError::from_win("DuplicateOutput1", err))
     }
 
     /// unlike [acquire_next_vsync_frame][Self::acquire_next_vsync_frame], this is a blocking call and immediately returns the texture
     /// without waiting for vsync.
     ///
-    /// the method handles any switches in desktop automatically.
+    /// the method handles any switches in desktop automatically. the resulting changes of the
+    /// [session state][Self::session_state] can be observed with
+    /// [subscribe_session_events][Self::subscribe_session_events].
     ///
     /// this fails with following results:
     ///
     /// ## Recoverable errors
     /// these can be recovered by just calling the function again after this error.
-    /// * [DDApiError::AccessLost] - when desktop mode switch happens (resolution change) or desktop
+    /// * [ErrorKind::AccessLost] - when desktop mode switch happens (resolution change) or desktop
     /// changes. (going to lock screen etc).
-    /// * [DDApiError::AccessDenied] - when windows opens a secure environment, this application
+    /// * [ErrorKind::AccessDenied] - when windows opens a secure environment, this application
     /// will be denied access.
     ///
     /// ## Non-recoverable errors
-    /// * [DDApiError::Unexpected] - this type of error cant be recovered from. the application should
+    /// * [ErrorKind::Unexpected] - this type of error cant be recovered from. the application should
     /// drop the struct and re create a new instance.
+    ///
+    /// [Supervisor][crate::supervisor::Supervisor] does that automatically.
     pub fn acquire_next_frame_now(&mut self) -> Result<Texture> {
         self.acquire_next_frame(Duration::from_millis(0))
     }
 
+    /// acquire the next frame waiting at most `timeout` for a new one. check
+    /// [acquire_next_frame_now][Self::acquire_next_frame_now] for the errors.
+    ///
+    /// if [placeholder][DuplicationApiOptions::placeholder] is configured, a placeholder frame is
+    /// returned instead of [ErrorKind::AccessLost] / [ErrorKind::AccessDenied] while the
+    /// [session][Self::session_state] is lost or on the secure desktop. such frames are flagged
+    /// as [synthetic][FrameInfo::synthetic].
     pub fn acquire_next_frame(&mut self, timeout: Duration) -> Result<Texture> {
+        match self.acquire_captured_frame(timeout) {
+            Ok(tex) => {
+                if self.synthetic {
+                    debug!("capture is available again, leaving placeholder frames");
+                    self.synthetic = false;
+                    self.placeholder_frame = None;
+                    self.last_good_frame = None;
+                }
+                Ok(tex)
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::AccessLost | ErrorKind::AccessDenied)
+                && self.options.placeholder.is_some()
+                && matches!(self.session.state(), SessionState::Lost | SessionState::SecureDesktop) => {
+                self.placeholder().inspect_err(|pe| {
+                    warn!("failed to generate placeholder frame. {:?}", pe);
+                }).map_err(|_| e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn acquire_captured_frame(&mut self, timeout: Duration) -> Result<Texture> {
         let mut frame_info = Default::default();
 
         if self.dupl.is_none() {
@@ -436,27 +521,34 @@ impl DesktopDuplicationApi {
             match e.code() {
                 DXGI_ERROR_ACCESS_LOST => {
                     warn!("display access lost. maybe desktop mode switch?, {:?}",e);
+                    self.session.handle(SessionEvent::AccessLost);
                     self.reacquire_dup()?;
-                    return Err(DDApiError::AccessLost);
+                    return Err(DDApiError::from_win("AcquireNextFrame", e));
                 }
                 DXGI_ERROR_ACCESS_DENIED => {
                     warn!("display access is denied. Maybe running in a secure environment?");
+                    self.session.handle(SessionEvent::AccessDenied);
                     self.reacquire_dup()?;
-                    return Err(DDApiError::AccessDenied);
+                    return Err(DDApiError::from_win("AcquireNextFrame", e));
                 }
                 DXGI_ERROR_INVALID_CALL => {
                     warn!("dxgi_error_invalid_call. maybe forgot to ReleaseFrame()?");
+                    self.session.handle(SessionEvent::AccessLost);
                     self.reacquire_dup()?;
-                    return Err(DDApiError::AccessLost);
+                    return Err(DDApiError::new(ErrorKind::AccessLost).with_operation("AcquireNextFrame")
+                        .with_code(e.code()).with_source(e));
                 }
                 DXGI_ERROR_WAIT_TIMEOUT => {
                     trace!("no new frame is available");
                 }
                 _ => {
-                    return Err(DDApiError::Unexpected(format!("acquire frame failed {:?}", e)));
+                    let err = DDApiError::from_win("AcquireNextFrame", e);
+                    self.session.handle_error(&err);
+                    return Err(err);
                 }
             }
         } else {
+            self.session.handle(SessionEvent::FrameAcquired);
             self.last_frame_info = Some(frame_info);
             if frame_info.PointerShapeBufferSize != 0 {
                 let mut shape = CursorShape::default();
@@ -469,7 +561,13 @@ impl DesktopDuplicationApi {
         if let Some(resource) = self.state.last_resource.as_ref() {
             debug!("got fresh resource. accumulated {} frames",frame_info.AccumulatedFrames);
             self.state.frame_locked = true;
-            let new_frame = Texture::new(resource.cast().unwrap());
+            let new_frame = match resource.cast() {
+                Ok(tex) => Texture::new(tex),
+                Err(e) => {
+                    self.release_locked_frame();
+                    return Err(DDApiError::from_win("ID3D11Texture2D", e));
+                }
+            };
             self.ensure_cache_frame(&new_frame).inspect_err(|_| {
                 self.release_locked_frame();
             })?;
@@ -478,7 +576,7 @@ impl DesktopDuplicationApi {
             debug!("no fresh resource. accumulated {} frames",frame_info.AccumulatedFrames);
         }
         if self.state.frame.is_none() {
-            return Err(DDApiError::AccessLost);
+            return Err(ErrorKind::AccessLost.into());
         }
 
 
@@ -512,6 +610,7 @@ impl DesktopDuplicationApi {
             last_mouse_update_time: last_frame.LastMouseUpdateTime,
             accumulated_frames: last_frame.AccumulatedFrames,
             protected_content_masked_out: last_frame.ProtectedContentMaskedOut.as_bool(),
+            synthetic: self.synthetic,
             pointer_info: CursorInfo {
                 visible: last_frame.PointerPosition.Visible.as_bool(),
                 updated: last_frame.PointerShapeBufferSize != 0,
@@ -527,7 +626,7 @@ impl DesktopDuplicationApi {
         if shape.buffer.capacity() < last_frame.PointerShapeBufferSize as _ {
             shape.buffer = Vec::with_capacity(last_frame.PointerShapeBufferSize as _)
         }
-        let dupl = self.dupl.as_ref().ok_or(DDApiError::Unexpected("duplication instance doesn't exist??".to_owned()))?;
+        let dupl = self.dupl.as_ref().ok_or(DDApiError::unexpected("duplication instance doesn't exist??"))?;
 
         let mut shape_info: DXGI_OUTDUPL_POINTER_SHAPE_INFO = Default::default();
         let mut required_size: u32 = 0;
@@ -538,8 +637,8 @@ impl DesktopDuplicationApi {
             shape.buffer = Vec::with_capacity(required_size as _);
             unsafe { result = dupl.GetFramePointerShape(last_frame.PointerShapeBufferSize, shape.buffer.as_mut_ptr() as _, &mut required_size, &mut shape_info); }
         }
-        if result.is_err() {
-            return Err(DDApiError::Unexpected(format!("{:?}", &result)));
+        if let Err(e) = result {
+            return Err(DDApiError::from_win("GetFramePointerShape", e));
         } else {
             unsafe { shape.buffer.set_len(last_frame.PointerShapeBufferSize as _) };
             shape.height = shape_info.Height;
@@ -561,11 +660,22 @@ impl DesktopDuplicationApi {
             *shape = cshape.clone();
             Ok(())
         } else {
-            Err(DDApiError::BadParam("requested before frame!!!".to_owned()))
+            Err(DDApiError::bad_param("requested before frame!!!"))
         }
     }
 
 
+    /// current [state][SessionState] of the duplication session.
+    pub fn session_state(&self) -> SessionState {
+        self.session.state()
+    }
+
+    /// receive every [transition][crate::session::SessionTransition] of the session state from
+    /// now on. check [session][crate::session] module for details.
+    pub fn subscribe_session_events(&mut self) -> SessionEvents {
+        self.session.subscribe()
+    }
+
     /// this method is used to retrieve device and context used in this api. These can be used
     /// to build directx color conversion and image scale.
     pub fn get_device_and_ctx(&self) -> (ID3D11Device4, ID3D11DeviceContext4) {
@@ -574,6 +684,11 @@ impl DesktopDuplicationApi {
 
     /// configure duplication manager with given options.
     pub fn configure(&mut self, opt: DuplicationApiOptions) {
+        if opt.frame_pool_size != self.options.frame_pool_size {
+            self.frame_pool.resize(opt.frame_pool_size);
+        }
+        // regenerated with the new options when it's needed next.
+        self.placeholder_frame = None;
         self.options = opt;
     }
 
@@ -599,10 +714,10 @@ impl DesktopDuplicationApi {
             self.state.hotspot_x = point.x as _;
             self.state.hotspot_y = point.y as _;
         }
-        let surface: IDXGISurface1 = tex.as_raw_ref().cast().unwrap();
+        let surface: IDXGISurface1 = tex.as_raw_ref().cast().map_err(|e| DDApiError::from_win("IDXGISurface1", e))?;
         let hdc = unsafe { surface.GetDC(BOOL::from(false)) };
         if let Err(err) = hdc {
-            return Err(DDApiError::Unexpected(format!("failed to get DC for cursor image. {:?}", err)));
+            return Err(DDApiError::from_win("GetDC", err).with_message("failed to get DC for cursor image"));
         }
         let hdc = hdc.unwrap();
 
@@ -616,8 +731,8 @@ impl DesktopDuplicationApi {
             )
         };
 
-        if result.is_err() {
-            unsafe { return Err(DDApiError::Unexpected(format!("failed to draw icon. {:?}", GetLastError()))); }
+        if let Err(e) = result {
+            return Err(DDApiError::from_win("DrawIconEx", e));
         }
 
         let _ = unsafe { surface.ReleaseDC(None) };
@@ -628,8 +743,8 @@ impl DesktopDuplicationApi {
         // get icon information
         let mut icon_info = Default::default();
         let result = unsafe { GetIconInfo(cursor, &mut icon_info) };
-        if result.is_err() {
-            unsafe { return Err(DDApiError::Unexpected(format!("failed to get icon info. `{:?}`", GetLastError()))); }
+        if let Err(e) = result {
+            return Err(DDApiError::from_win("GetIconInfo", e));
         }
 
         if !icon_info.hbmMask.is_invalid() {
@@ -643,19 +758,96 @@ impl DesktopDuplicationApi {
     }
 
     fn reacquire_dup(&mut self) -> Result<()> {
+        if let Some(frame) = self.state.frame.as_ref().filter(|_| self.options.placeholder.is_some()) {
+            // keep the last captured frame around to generate placeholders from.
+            self.last_format = Some(frame.desc().format);
+            self.last_good_frame = Some(frame.clone());
+        }
         self.state.reset();
         self.dupl = None;
 
         let dupl = Self::create_dupl_output(&self.d3d_device, &self.output);
-        if dupl.is_err() {
+        if let Err(e) = &dupl {
+            self.session.handle_error(e);
             let _ = Self::switch_thread_desktop();
         }
         let dupl = dupl?;
         debug!("successfully acquired new duplication instance");
         self.dupl = Some(dupl);
+        self.placeholder_mode = None;
         Ok(())
     }
 
+    // returns the placeholder frame for the current display size and the format of the
+    // duplicated frames, generating it if needed.
+    fn placeholder(&mut self) -> Result<Texture> {
+        let mode = match &self.placeholder_mode {
+            Some(mode) => mode.clone(),
+            None => self.placeholder_mode.insert(self.output.get_current_display_mode()?).clone(),
+        };
+        // nothing was duplicated yet, guess the format from the display mode.
+        let format = self.last_format.unwrap_or(if mode.hdr { ColorFormat::ARGB16Float } else { ColorFormat::ABGR8UNorm });
+        if let Some(tex) = &self.placeholder_frame {
+            let desc = tex.desc();
+            if desc.width == mode.width && desc.height == mode.height && desc.format == format {
+                self.synthetic = true;
+                return Ok(tex.clone());
+            }
+        }
+        let options = self.options.placeholder.clone().unwrap_or_default();
+        let last = match options.style {
+            PlaceholderStyle::BlurredLastFrame { .. } => self.read_last_good_frame(),
+            _ => None,
+        };
+        let frame = convert_placeholder(render_placeholder(&options, mode.width, mode.height, last.as_ref()), format)?;
+        let tex = self.upload_frame(&frame)?;
+        debug!("generated {}x{} {:?} placeholder frame", mode.width, mode.height, format);
+        self.placeholder_frame = Some(tex.clone());
+        self.synthetic = true;
+        Ok(tex)
+    }
+
+    fn read_last_good_frame(&mut self) -> Option<CpuFrame> {
+        let tex = self.last_good_frame.as_ref()?;
+        if !matches!(tex.desc().format, ColorFormat::ABGR8UNorm | ColorFormat::ARGB8UNorm) {
+            return None;
+        }
+        let reader = self.reader.get_or_insert_with(|| TextureReader::new(self.d3d_device.clone(), self.d3d_ctx.clone()));
+        let mut frame = CpuFrame::default();
+        reader.read_frame(tex, &mut frame).ok()?;
+        Some(frame)
+    }
+
+    fn upload_frame(&self, frame: &CpuFrame) -> Result<Texture> {
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: frame.width,
+            Height: frame.height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: frame.format.into(),
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_RENDER_TARGET.0 as u32,
+            CPUAccessFlags: Default::default(),
+            MiscFlags: Default::default(),
+        };
+        let data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: frame.data.as_ptr() as _,
+            SysMemPitch: (frame.data.len() / frame.height.max(1) as usize) as u32,
+            SysMemSlicePitch: 0,
+        };
+        let mut tex = None;
+        let result = unsafe { self.d3d_device.CreateTexture2D(&desc, Some(&data), Some(&mut tex)) };
+        if let Err(e) = result {
+            Err(DDApiError::from_win("CreateTexture2D", e))
+        } else {
+            Ok(Texture::new(tex.unwrap()))
+        }
+    }
+
     fn release_locked_frame(&mut self) {
         if self.state.last_resource.is_some() {
             self.state.last_resource = None;
@@ -708,7 +900,7 @@ impl DesktopDuplicationApi {
         let mut tex = None;
         let result = unsafe { self.d3d_device.CreateTexture2D(&desc, None, Some(&mut tex)) };
         if let Err(e) = result {
-            Err(DDApiError::Unexpected(format!("failed to create texture. {:?}", e)))
+            Err(DDApiError::from_win("CreateTexture2D", e))
         } else {
             Ok(Texture::new(tex.unwrap()))
         }
@@ -719,12 +911,14 @@ impl DesktopDuplicationApi {
         let desk = unsafe { OpenInputDesktop(DF_ALLOWOTHERACCOUNTHOOK as _, true, DESKTOP_ACCESS_FLAGS(GENERIC_READ.0)) };
         if let Err(err) = desk {
             error!("dint get desktop : {:?}", err);
-            return Err(DDApiError::AccessDenied);
+            return Err(DDApiError::new(ErrorKind::AccessDenied).with_operation("OpenInputDesktop")
+                .with_code(err.code()).with_source(err));
         }
         let result = unsafe { SetThreadDesktop(desk.unwrap()) };
-        if result.is_err() {
-            error!("dint switch desktop: {:?}",unsafe{GetLastError().to_hresult()});
-            return Err(DDApiError::AccessDenied);
+        if let Err(err) = result {
+            error!("dint switch desktop: {:?}", err);
+            return Err(DDApiError::new(ErrorKind::AccessDenied).with_operation("SetThreadDesktop")
+                .with_code(err.code()).with_source(err));
         }
         Ok(())
     }
@@ -733,10 +927,35 @@ impl DesktopDuplicationApi {
 
 /// Settings to configure Desktop duplication api. these can be configured even after initialized.
 ///
-/// currently it only supports option to skip drawing cursor
-#[derive(Default)]
+/// new fields get added to this struct over time, so build it with `..Default::default()`.
+/// `frame_pool_size` and `placeholder` were added after 0.10.11, which breaks literals that list
+/// only `skip_cursor`:
+///
+/// ```
+/// dupl.configure(DuplicationApiOptions { skip_cursor: true, ..Default::default() });
+/// ```
+#[derive(Clone, Debug)]
 pub struct DuplicationApiOptions {
+    /// skip drawing cursor onto the frame
     pub skip_cursor: bool,
+
+    /// number of textures in the pool used by
+    /// [acquire_next_pooled_frame][DesktopDuplicationApi::acquire_next_pooled_frame]. defaults to 4.
+    pub frame_pool_size: usize,
+
+    /// generate [placeholder][crate::placeholder] frames while the desktop can't be captured
+    /// (secure desktop, lock screen). `None`, the default, returns the errors instead.
+    pub placeholder: Option<PlaceholderOptions>,
+}
+
+impl Default for DuplicationApiOptions {
+    fn default() -> Self {
+        Self {
+            skip_cursor: false,
+            frame_pool_size: 4,
+            placeholder: None,
+        }
+    }
 }
 
 // these are state variables for duplication sync stream
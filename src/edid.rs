@@ -0,0 +1,431 @@
+//! EDID retrieval and parsing.
+//!
+//! [Display::edid][crate::outputs::Display::edid] returns the raw EDID windows cached for a
+//! monitor and [Edid::parse] decodes the parts of the base block and the CTA-861 extension that
+//! matter for capturing: who made the monitor, its physical size, native timing and HDR
+//! capabilities. The parser is plain rust and doesn't touch any windows api.
+//!
+//! # Example
+//! ```
+//! let edid = display.edid_info()?;
+//! println!("{} {:?}, native {:?}", edid.manufacturer, edid.model_name, edid.native_timing());
+//! if let Some(hdr) = &edid.hdr {
+//!     println!("PQ: {}, peak {:?} nits", hdr.eotf.pq, hdr.max_luminance);
+//! }
+//! ```
+
+use windows::core::HSTRING;
+use windows::Win32::Foundation::ERROR_SUCCESS;
+use windows::Win32::System::Registry::{HKEY_LOCAL_MACHINE, RegGetValueW, RRF_RT_REG_BINARY};
+
+use crate::display_id::DisplayId;
+use crate::errors::DDApiError;
+use crate::Result;
+
+#[cfg(test)]
+mod test {
+    use crate::edid::Edid;
+
+    // EDIDs as reported by a 4k HDR monitor, a laptop panel without serial or name, a TV with
+    // HLG support, an old analog monitor and an old TV with a 1080i native timing.
+    const MONITOR: &str = "00ffffffffffff0010aca241374a4b4c0c1f0104b53c22783a00000000000000000000000000010101010101010101010101010101014dd000a0f0703e803020350055502100001e000000ff00374a324b4c34330a2020202020000000fc0044454c4c205532373230510a20000000fd00183c1e8c3c000a20202020202001d202030f41e606050160503083010000023a801871382d40582c450055502100001e00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000be";
+    const LAPTOP: &str = "00ffffffffffff0009e5680800000000001d0104b52213783a00000000000000000000000000010101010101010101010101010101011a3680a070381f403020350058c21000001e112480a070381f403020350058c21000001e000000fe00424f452043510a202020202020000000fe004e5631353646484d2d4e34380a0011";
+    const TV: &str = "00ffffffffffff004c2d2473000e0001ff200104b50000783a0000000000000000000000000001010101010101010101010101010101023a801871382d40582c4500b9a84200001e000000fd00184b0f873c000a202020202020000000fc0053414d53554e470a2020202020000000ff004831414b3530303030300a202001a402030840e3060d010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000bc";
+    const ANALOG: &str = "00ffffffffffff0004722400785634121e1201040e2f1e783a000000000000000000000000000101010101010101010101010101010121399030621a274068b03600d9281100001e000000fd00384c1f530f000a202020202020000000fc00414c32323136570a2020202020000000ff004c343630393031320a20202020002f";
+
+    const INTERLACED: &str = "00ffffffffffff004dd9010a000000000114010380000000000000000000000000000000000001010101010101010101010101010101011d8018711c1620582c2500b9a84200009e000000fc00534f4e592054560a202020202000000010000000000000000000000000000000000010000000000000000000000000000000f0";
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_hdr_monitor() {
+        let edid = Edid::parse(&bytes(MONITOR)).unwrap();
+        assert_eq!(edid.manufacturer, "DEL");
+        assert_eq!(edid.product_code, 0x41a2);
+        assert_eq!(edid.serial, 0x4c4b4a37);
+        assert_eq!(edid.serial_string.as_deref(), Some("7J2KL43"));
+        assert_eq!(edid.model_name.as_deref(), Some("DELL U2720Q"));
+        assert_eq!((edid.manufacture_week, edid.manufacture_year), (Some(12), 2021));
+        assert_eq!(edid.version, (1, 4));
+        assert!(edid.digital && edid.checksum_valid);
+        assert_eq!(edid.extension_count, 1);
+        assert_eq!(edid.physical_size_mm, Some((597, 336)));
+
+        let native = edid.native_timing().unwrap();
+        assert_eq!((native.width, native.height), (3840, 2160));
+        assert_eq!(native.pixel_clock_khz, 533_250);
+        assert_eq!((native.h_front_porch, native.h_sync_width, native.v_front_porch, native.v_sync_width), (48, 32, 3, 5));
+        assert!((native.refresh_rate() - 59.997).abs() < 0.001);
+        // second timing comes from the CTA extension.
+        assert_eq!(edid.detailed_timings.len(), 2);
+        assert_eq!((edid.detailed_timings[1].width, edid.detailed_timings[1].height), (1920, 1080));
+
+        let range = edid.range_limits.unwrap();
+        assert_eq!((range.min_vertical_hz, range.max_vertical_hz), (24, 60));
+        assert_eq!((range.min_horizontal_khz, range.max_horizontal_khz, range.max_pixel_clock_mhz), (30, 140, 600));
+
+        let hdr = edid.hdr.unwrap();
+        assert!(hdr.eotf.sdr && hdr.eotf.pq && !hdr.eotf.traditional_hdr && !hdr.eotf.hlg);
+        assert!(hdr.static_metadata_type1);
+        assert!((hdr.max_luminance.unwrap() - 400.0).abs() < 0.01);
+        assert!((hdr.max_frame_average_luminance.unwrap() - 282.84).abs() < 0.01);
+        assert!((hdr.min_luminance.unwrap() - 0.1417).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_laptop_panel() {
+        let edid = Edid::parse(&bytes(LAPTOP)).unwrap();
+        assert_eq!((edid.manufacturer.as_str(), edid.product_code, edid.serial), ("BOE", 0x0868, 0));
+        assert_eq!(edid.model_name, None);
+        assert_eq!(edid.serial_string, None);
+        assert_eq!(edid.text, vec!["BOE CQ".to_owned(), "NV156FHM-N48".to_owned()]);
+        assert_eq!((edid.manufacture_week, edid.manufacture_year), (None, 2019));
+        assert_eq!(edid.detailed_timings.len(), 2);
+        assert!((edid.native_timing().unwrap().refresh_rate() - 59.93).abs() < 0.01);
+        assert!((edid.detailed_timings[1].refresh_rate() - 39.95).abs() < 0.01);
+        assert_eq!(edid.physical_size_mm, Some((344, 194)));
+        assert!(edid.hdr.is_none() && edid.range_limits.is_none());
+    }
+
+    #[test]
+    fn test_hlg_tv() {
+        let edid = Edid::parse(&bytes(TV)).unwrap();
+        assert_eq!(edid.model_name.as_deref(), Some("SAMSUNG"));
+        assert_eq!(edid.serial_string.as_deref(), Some("H1AK500000"));
+        // week 0xff marks a model year.
+        assert_eq!((edid.manufacture_week, edid.manufacture_year), (None, 2022));
+        // the base block has no size, the timing does.
+        assert_eq!(edid.physical_size_mm, Some((1209, 680)));
+        assert!((edid.native_timing().unwrap().refresh_rate() - 60.0).abs() < 0.001);
+
+        let hdr = edid.hdr.unwrap();
+        assert!(hdr.eotf.sdr && hdr.eotf.pq && hdr.eotf.hlg);
+        assert_eq!((hdr.max_luminance, hdr.max_frame_average_luminance, hdr.min_luminance), (None, None, None));
+    }
+
+    #[test]
+    fn test_analog_monitor() {
+        let edid = Edid::parse(&bytes(ANALOG)).unwrap();
+        assert_eq!((edid.manufacturer.as_str(), edid.model_name.as_deref()), ("ACR", Some("AL2216W")));
+        assert!(!edid.digital);
+        assert_eq!(edid.version, (1, 4));
+        let native = edid.native_timing().unwrap();
+        assert_eq!((native.width, native.height, native.width_mm, native.height_mm), (1680, 1050, 473, 296));
+        assert!((native.refresh_rate() - 59.954).abs() < 0.001);
+        assert_eq!(edid.range_limits.unwrap().max_pixel_clock_mhz, 150);
+    }
+
+    #[test]
+    fn test_interlaced_tv() {
+        let edid = Edid::parse(&bytes(INTERLACED)).unwrap();
+        assert_eq!((edid.manufacturer.as_str(), edid.model_name.as_deref()), ("SNY", Some("SONY TV")));
+        assert!(edid.checksum_valid);
+        let native = edid.native_timing().unwrap();
+        // 1080i describes fields of 540 lines at 60 Hz.
+        assert!(native.interlaced);
+        assert_eq!((native.width, native.height), (1920, 540));
+        assert!((native.refresh_rate() - 60.05).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_damaged_edid() {
+        assert!(Edid::parse(&[]).is_err());
+        assert!(Edid::parse(&bytes(MONITOR)[1..]).is_err());
+
+        // a bad checksum is reported, not rejected.
+        let mut data = bytes(LAPTOP);
+        data[20] ^= 0x80;
+        let edid = Edid::parse(&data).unwrap();
+        assert!(!edid.checksum_valid && !edid.digital);
+
+        // truncated extensions are skipped.
+        let data = bytes(MONITOR);
+        let edid = Edid::parse(&data[..200]).unwrap();
+        assert!(edid.hdr.is_none());
+        assert_eq!(edid.detailed_timings.len(), 1);
+    }
+}
+
+const MAGIC: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+const BLOCK: usize = 128;
+
+/// a detailed timing descriptor.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct DetailedTiming {
+    pub pixel_clock_khz: u32,
+    pub width: u32,
+    /// active lines, per field for interlaced timings.
+    pub height: u32,
+    pub h_blank: u32,
+    pub v_blank: u32,
+    pub h_front_porch: u32,
+    pub h_sync_width: u32,
+    pub v_front_porch: u32,
+    pub v_sync_width: u32,
+    /// physical width of the image in millimeters.
+    pub width_mm: u32,
+    /// physical height of the image in millimeters.
+    pub height_mm: u32,
+    pub interlaced: bool,
+}
+
+impl DetailedTiming {
+    /// vertical refresh rate in Hz. for interlaced timings this is the field rate, e.g. 60 for
+    /// 1080i60, twice the frame rate.
+    pub fn refresh_rate(&self) -> f64 {
+        let total = (self.width + self.h_blank) as f64 * (self.height + self.v_blank) as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+        // interlaced timings give lines per field, so this is already the field rate.
+        self.pixel_clock_khz as f64 * 1000.0 / total
+    }
+
+    fn parse(d: &[u8]) -> Option<Self> {
+        let clock = u16::from_le_bytes([d[0], d[1]]) as u32;
+        if clock == 0 {
+            return None;
+        }
+        let hi = |byte: u8, shift: u8, mask: u8| (((byte >> shift) & mask) as u32) << 8;
+        Some(Self {
+            pixel_clock_khz: clock * 10,
+            width: d[2] as u32 | hi(d[4], 4, 0xf),
+            h_blank: d[3] as u32 | hi(d[4], 0, 0xf),
+            height: d[5] as u32 | hi(d[7], 4, 0xf),
+            v_blank: d[6] as u32 | hi(d[7], 0, 0xf),
+            h_front_porch: d[8] as u32 | hi(d[11], 6, 0x3),
+            h_sync_width: d[9] as u32 | hi(d[11], 4, 0x3),
+            v_front_porch: (d[10] >> 4) as u32 | ((((d[11] >> 2) & 0x3) as u32) << 4),
+            v_sync_width: (d[10] & 0xf) as u32 | (((d[11] & 0x3) as u32) << 4),
+            width_mm: d[12] as u32 | hi(d[14], 4, 0xf),
+            height_mm: d[13] as u32 | hi(d[14], 0, 0xf),
+            interlaced: d[17] & 0x80 != 0,
+        })
+    }
+}
+
+/// display range limits descriptor.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct RangeLimits {
+    pub min_vertical_hz: u16,
+    pub max_vertical_hz: u16,
+    pub min_horizontal_khz: u16,
+    pub max_horizontal_khz: u16,
+    pub max_pixel_clock_mhz: u16,
+}
+
+/// transfer functions supported by the display.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SupportedEotf {
+    /// traditional gamma, SDR luminance range.
+    pub sdr: bool,
+    /// traditional gamma, HDR luminance range.
+    pub traditional_hdr: bool,
+    /// SMPTE ST 2084, used by HDR10.
+    pub pq: bool,
+    /// hybrid log-gamma.
+    pub hlg: bool,
+}
+
+/// CTA-861.3 HDR static metadata data block.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HdrStaticMetadata {
+    pub eotf: SupportedEotf,
+    /// static metadata type 1 (mastering display and content light level) is supported.
+    pub static_metadata_type1: bool,
+    /// desired content max luminance in cd/m².
+    pub max_luminance: Option<f32>,
+    /// desired content max frame-average luminance in cd/m².
+    pub max_frame_average_luminance: Option<f32>,
+    /// desired content min luminance in cd/m².
+    pub min_luminance: Option<f32>,
+}
+
+impl HdrStaticMetadata {
+    // payload after the extended tag code.
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let eotf = *payload.first()?;
+        let luminance = |cv: u8| 50.0 * 2f32.powf(cv as f32 / 32.0);
+        let max_luminance = payload.get(2).filter(|cv| **cv != 0).map(|cv| luminance(*cv));
+        Some(Self {
+            eotf: SupportedEotf {
+                sdr: eotf & 0x1 != 0,
+                traditional_hdr: eotf & 0x2 != 0,
+                pq: eotf & 0x4 != 0,
+                hlg: eotf & 0x8 != 0,
+            },
+            static_metadata_type1: payload.get(1).is_some_and(|d| d & 0x1 != 0),
+            max_luminance,
+            max_frame_average_luminance: payload.get(3).filter(|cv| **cv != 0).map(|cv| luminance(*cv)),
+            // min luminance is relative to the max one.
+            min_luminance: payload.get(4).zip(max_luminance)
+                .map(|(cv, max)| max * (*cv as f32 / 255.0).powi(2) / 100.0),
+        })
+    }
+}
+
+/// Parsed EDID. check the [module docs][self].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Edid {
+    /// three letter PNP manufacturer id, e.g. `DEL`.
+    pub manufacturer: String,
+    /// manufacturer's product code.
+    pub product_code: u16,
+    /// numeric serial number. zero if not set.
+    pub serial: u32,
+    /// serial number descriptor.
+    pub serial_string: Option<String>,
+    /// monitor name descriptor.
+    pub model_name: Option<String>,
+    /// unspecified text descriptors. laptop panels often put their model here.
+    pub text: Vec<String>,
+    /// week of manufacture, if given.
+    pub manufacture_week: Option<u8>,
+    /// year of manufacture, or model year if there is no week.
+    pub manufacture_year: u16,
+    /// EDID version and revision, e.g. `(1, 4)`.
+    pub version: (u8, u8),
+    /// the video input is digital.
+    pub digital: bool,
+    /// physical size of the image in millimeters, from the native timing or the base block.
+    pub physical_size_mm: Option<(u32, u32)>,
+    /// detailed timings of the base block followed by those of CTA extensions. the first one is
+    /// the native timing.
+    pub detailed_timings: Vec<DetailedTiming>,
+    /// display range limits, if given.
+    pub range_limits: Option<RangeLimits>,
+    /// HDR capabilities from the CTA extension, if given.
+    pub hdr: Option<HdrStaticMetadata>,
+    /// number of extension blocks the base block announces.
+    pub extension_count: u8,
+    /// all blocks that were read have valid checksums.
+    pub checksum_valid: bool,
+}
+
+impl Edid {
+    /// parse an EDID. fails if there is no complete base block. extension blocks that are
+    /// truncated or unknown are skipped and bad checksums only clear
+    /// [checksum_valid][Self::checksum_valid].
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < BLOCK || data[..8] != MAGIC {
+            return Err(DDApiError::bad_param("not an EDID base block"));
+        }
+        let base = &data[..BLOCK];
+        let packed = u16::from_be_bytes([base[8], base[9]]);
+        let mut edid = Self {
+            manufacturer: [10, 5, 0].iter()
+                .map(|shift| (b'A' - 1 + ((packed >> shift) & 0x1f) as u8) as char)
+                .collect(),
+            product_code: u16::from_le_bytes([base[10], base[11]]),
+            serial: u32::from_le_bytes([base[12], base[13], base[14], base[15]]),
+            manufacture_week: Some(base[16]).filter(|w| (1..=54).contains(w)),
+            manufacture_year: 1990 + base[17] as u16,
+            version: (base[18], base[19]),
+            digital: base[20] & 0x80 != 0,
+            extension_count: base[126],
+            checksum_valid: checksum_ok(base),
+            ..Default::default()
+        };
+
+        for d in base[54..126].chunks_exact(18) {
+            if d[0] != 0 || d[1] != 0 {
+                edid.detailed_timings.extend(DetailedTiming::parse(d));
+                continue;
+            }
+            match d[3] {
+                0xff => edid.serial_string = Some(descriptor_text(d)),
+                0xfc => edid.model_name = Some(descriptor_text(d)),
+                0xfe => edid.text.push(descriptor_text(d)),
+                0xfd => edid.range_limits = Some(range_limits(d)),
+                _ => {}
+            }
+        }
+
+        for block in data[BLOCK..].chunks_exact(BLOCK).take(edid.extension_count as usize) {
+            edid.checksum_valid &= checksum_ok(block);
+            if block[0] == 0x02 {
+                edid.parse_cta(block);
+            }
+        }
+
+        let native = edid.native_timing().map(|t| (t.width_mm, t.height_mm));
+        let base_size = (base[21] as u32 * 10, base[22] as u32 * 10);
+        edid.physical_size_mm = native.into_iter().chain([base_size])
+            .find(|(w, h)| *w != 0 && *h != 0);
+        Ok(edid)
+    }
+
+    /// the preferred timing, which is the panel's native resolution and refresh rate.
+    pub fn native_timing(&self) -> Option<&DetailedTiming> {
+        self.detailed_timings.first()
+    }
+
+    // CTA-861 extension: data block collection followed by detailed timings.
+    fn parse_cta(&mut self, block: &[u8]) {
+        let dtd_start = (block[2] as usize).min(127);
+        if dtd_start >= 4 {
+            let mut pos = 4;
+            while pos < dtd_start {
+                let header = block[pos];
+                let len = (header & 0x1f) as usize;
+                let Some(payload) = block.get(pos + 1..pos + 1 + len).filter(|_| pos + 1 + len <= dtd_start) else { break };
+                // extended tag 6 is the HDR static metadata data block.
+                if header >> 5 == 7 && payload.first() == Some(&6) {
+                    self.hdr = HdrStaticMetadata::parse(&payload[1..]);
+                }
+                pos += 1 + len;
+            }
+            for d in block[dtd_start..127].chunks_exact(18) {
+                match DetailedTiming::parse(d) {
+                    Some(timing) => self.detailed_timings.push(timing),
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+/// read the EDID windows cached for the monitor with given device interface path.
+pub(crate) fn read_edid(device_path: &str) -> Result<Vec<u8>> {
+    let key = DisplayId::registry_key(device_path)
+        .ok_or_else(|| DDApiError::bad_param(format!("not a monitor device path: {}", device_path)))?;
+    let key = HSTRING::from(key);
+    let value = HSTRING::from("EDID");
+    let mut len = 0u32;
+    let status = unsafe { RegGetValueW(HKEY_LOCAL_MACHINE, &key, &value, RRF_RT_REG_BINARY, None, None, Some(&mut len)) };
+    if status != ERROR_SUCCESS {
+        return Err(DDApiError::from_win("RegGetValueW", status.to_hresult().into()));
+    }
+    let mut edid = vec![0u8; len as usize];
+    let status = unsafe { RegGetValueW(HKEY_LOCAL_MACHINE, &key, &value, RRF_RT_REG_BINARY, None, Some(edid.as_mut_ptr() as _), Some(&mut len)) };
+    if status != ERROR_SUCCESS {
+        return Err(DDApiError::from_win("RegGetValueW", status.to_hresult().into()));
+    }
+    edid.truncate(len as usize);
+    Ok(edid)
+}
+
+fn checksum_ok(block: &[u8]) -> bool {
+    block.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) == 0
+}
+
+// text is terminated by a line feed and padded with spaces.
+fn descriptor_text(d: &[u8]) -> String {
+    let text = &d[5..18];
+    let end = text.iter().position(|c| *c == 0x0a).unwrap_or(text.len());
+    String::from_utf8_lossy(&text[..end]).trim_end().to_owned()
+}
+
+fn range_limits(d: &[u8]) -> RangeLimits {
+    // EDID 1.4 adds 255 to rates whose offset flag is set.
+    let offset = |flag: u8| if d[4] & flag != 0 { 255 } else { 0 };
+    RangeLimits {
+        min_vertical_hz: d[5] as u16 + offset(0x1),
+        max_vertical_hz: d[6] as u16 + offset(0x2),
+        min_horizontal_khz: d[7] as u16 + offset(0x4),
+        max_horizontal_khz: d[8] as u16 + offset(0x8),
+        max_pixel_clock_mhz: d[9] as u16 * 10,
+    }
+}
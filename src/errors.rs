@@ -1,10 +1,283 @@
-#[derive(Debug)]
-pub enum DDApiError {
+//! Error type of this crate.
+//!
+//! [DDApiError] tells what went wrong ([ErrorKind]), which operation failed, the underlying
+//! `HRESULT` if there is one, and whether it makes sense to try again ([RetryHint]). It implements
+//! [std::error::Error], so it works with `?` in functions returning `anyhow::Result` or errors
+//! built with `thiserror`.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::errors::{ErrorKind, RetryHint};
+//!
+//! match dupl.acquire_next_frame_now() {
+//!     Ok(tex) => { /* use the frame */ }
+//!     Err(e) if e.is_recoverable() => { /* try again with the next frame */ }
+//!     Err(e) if e.retry_hint() == RetryHint::Recreate => { /* re-create the api */ }
+//!     Err(e) => return Err(e.into()),
+//! }
+//! ```
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+use windows::core::HRESULT;
+use windows::Win32::Foundation::{E_ACCESSDENIED, E_INVALIDARG};
+use windows::Win32::Graphics::Dxgi::{DXGI_ERROR_ACCESS_DENIED, DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_NOT_CURRENTLY_AVAILABLE, DXGI_ERROR_SESSION_DISCONNECTED, DXGI_ERROR_UNSUPPORTED};
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::time::Duration;
+
+    use windows::Win32::Graphics::Dxgi::{DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_NOT_CURRENTLY_AVAILABLE, DXGI_ERROR_UNSUPPORTED};
+
+    use crate::errors::{DDApiError, ErrorKind, RetryHint};
+
+    #[test]
+    fn test_classification() {
+        let recoverable = [ErrorKind::AccessLost, ErrorKind::AccessDenied, ErrorKind::PoolExhausted, ErrorKind::CursorNotAvailable];
+        for kind in recoverable {
+            assert!(DDApiError::from(kind).is_recoverable(), "{:?}", kind);
+        }
+        for kind in [ErrorKind::Disconnected, ErrorKind::Unsupported, ErrorKind::BadParam, ErrorKind::Unexpected] {
+            assert!(!DDApiError::from(kind).is_recoverable(), "{:?}", kind);
+        }
+        assert_eq!(DDApiError::from(ErrorKind::AccessDenied).retry_hint(), RetryHint::After(Duration::from_millis(100)));
+        assert_eq!(DDApiError::unexpected("boom").retry_hint(), RetryHint::Recreate);
+        assert_eq!(DDApiError::bad_param("no").retry_hint(), RetryHint::Never);
+    }
+
+    #[test]
+    fn test_from_windows_error() {
+        let err = DDApiError::from_win("AcquireNextFrame", DXGI_ERROR_ACCESS_LOST.into());
+        assert_eq!(err.kind(), ErrorKind::AccessLost);
+        assert_eq!(err.operation(), Some("AcquireNextFrame"));
+        assert_eq!(err.code(), Some(DXGI_ERROR_ACCESS_LOST));
+        assert!(err.source().is_some());
+
+        let err = DDApiError::from_win("CreateTexture2D", DXGI_ERROR_DEVICE_REMOVED.into());
+        assert_eq!(err.kind(), ErrorKind::Unexpected);
+        let text = err.to_string();
+        assert!(text.starts_with("unexpected error in CreateTexture2D"), "{}", text);
+        assert!(text.contains("0x887A0005"), "{}", text);
+
+        let err = DDApiError::from_win("DuplicateOutput1", DXGI_ERROR_NOT_CURRENTLY_AVAILABLE.into());
+        assert_eq!((err.kind(), err.retry_hint()), (ErrorKind::Unexpected, RetryHint::Recreate));
+        let err = DDApiError::from_win("DuplicateOutput1", DXGI_ERROR_UNSUPPORTED.into());
+        assert_eq!((err.kind(), err.retry_hint()), (ErrorKind::Unsupported, RetryHint::Never));
+    }
+
+    #[test]
+    fn test_display() {
+        let err = DDApiError::bad_param("width must be positive").with_operation("set_display_mode");
+        assert_eq!(err.to_string(), "bad parameter in set_display_mode: width must be positive");
+        assert_eq!(DDApiError::from(ErrorKind::PoolExhausted).to_string(), "all frames of the pool are leased");
+
+        // errors can be boxed like any other error.
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(err.clone());
+        assert_eq!(boxed.to_string(), err.to_string());
+    }
+}
+
+/// category of a [DDApiError].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ErrorKind {
+    /// the session was disconnected (remote desktop etc).
     Disconnected,
+    /// the operation is not supported on this system, e.g. dpi awareness is not set.
     Unsupported,
+    /// access to the desktop is denied, windows is showing a secure desktop (UAC prompt, lock
+    /// screen).
     AccessDenied,
+    /// access to the desktop was lost because of a mode or desktop switch.
     AccessLost,
+    /// the cursor shape is not available yet.
     CursorNotAvailable,
-    BadParam(String),
-    Unexpected(String),
-}
\ No newline at end of file
+    /// all frames of a [FramePool][crate::pool::FramePool] are leased.
+    PoolExhausted,
+    /// a parameter passed to the api is invalid.
+    BadParam,
+    /// anything else. the api instance should be dropped and re-created.
+    Unexpected,
+}
+
+impl ErrorKind {
+    /// suggested way to handle errors of this kind.
+    pub fn retry_hint(self) -> RetryHint {
+        match self {
+            ErrorKind::AccessLost | ErrorKind::CursorNotAvailable | ErrorKind::PoolExhausted => RetryHint::Immediately,
+            ErrorKind::AccessDenied => RetryHint::After(Duration::from_millis(100)),
+            ErrorKind::Disconnected | ErrorKind::Unexpected => RetryHint::Recreate,
+            ErrorKind::Unsupported | ErrorKind::BadParam => RetryHint::Never,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            ErrorKind::Disconnected => "session disconnected",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::AccessDenied => "access denied",
+            ErrorKind::AccessLost => "access lost",
+            ErrorKind::CursorNotAvailable => "cursor not available",
+            ErrorKind::PoolExhausted => "all frames of the pool are leased",
+            ErrorKind::BadParam => "bad parameter",
+            ErrorKind::Unexpected => "unexpected error",
+        }
+    }
+}
+
+/// what to do after an error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RetryHint {
+    /// the same call can be retried right away, e.g. with the next frame.
+    Immediately,
+    /// the same call is likely to succeed after waiting a little.
+    After(Duration),
+    /// the instance is unusable. drop it and create a new one, see
+    /// [Supervisor][crate::supervisor::Supervisor].
+    Recreate,
+    /// retrying won't help.
+    Never,
+}
+
+/// Error returned by every fallible function of this crate.
+///
+/// cloning is cheap, the [source][Error::source] is shared.
+#[derive(Clone, Debug)]
+pub struct DDApiError {
+    kind: ErrorKind,
+    operation: Option<&'static str>,
+    message: Option<String>,
+    code: Option<HRESULT>,
+    source: Option<Arc<dyn Error + Send + Sync>>,
+}
+
+impl DDApiError {
+    /// create an error of given kind without any details.
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            operation: None,
+            message: None,
+            code: None,
+            source: None,
+        }
+    }
+
+    /// shorthand for an [ErrorKind::Unexpected] error with a message.
+    pub fn unexpected(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Unexpected).with_message(message)
+    }
+
+    /// shorthand for an [ErrorKind::BadParam] error with a message.
+    pub fn bad_param(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::BadParam).with_message(message)
+    }
+
+    /// create an error from a failed windows call. the kind is derived from the `HRESULT`.
+    pub fn from_win(operation: &'static str, err: windows::core::Error) -> Self {
+        Self::from(err).with_operation(operation)
+    }
+
+    /// set the name of the operation that failed.
+    pub fn with_operation(mut self, operation: &'static str) -> Self {
+        self.operation = Some(operation);
+        self
+    }
+
+    /// set a message with more details.
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// set the `HRESULT` returned by windows.
+    pub fn with_code(mut self, code: HRESULT) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// set the underlying error.
+    pub fn with_source(mut self, source: impl Error + Send + Sync + 'static) -> Self {
+        self.source = Some(Arc::new(source));
+        self
+    }
+
+    /// category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// name of the operation that failed, if known.
+    pub fn operation(&self) -> Option<&'static str> {
+        self.operation
+    }
+
+    /// details about the error, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// `HRESULT` of the failed windows call, if any.
+    pub fn code(&self) -> Option<HRESULT> {
+        self.code
+    }
+
+    /// returns true if calling the same function again can succeed without re-creating anything.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self.retry_hint(), RetryHint::Immediately | RetryHint::After(_))
+    }
+
+    /// suggested way to handle this error.
+    pub fn retry_hint(&self) -> RetryHint {
+        self.kind.retry_hint()
+    }
+}
+
+impl From<ErrorKind> for DDApiError {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl From<windows::core::Error> for DDApiError {
+    fn from(err: windows::core::Error) -> Self {
+        let code = err.code();
+        let kind = match code {
+            DXGI_ERROR_ACCESS_LOST => ErrorKind::AccessLost,
+            DXGI_ERROR_ACCESS_DENIED | E_ACCESSDENIED => ErrorKind::AccessDenied,
+            DXGI_ERROR_SESSION_DISCONNECTED => ErrorKind::Disconnected,
+            DXGI_ERROR_UNSUPPORTED => ErrorKind::Unsupported,
+            // other applications hold all duplication slots of the output, re-creating later
+            // may succeed.
+            DXGI_ERROR_NOT_CURRENTLY_AVAILABLE => ErrorKind::Unexpected,
+            E_INVALIDARG => ErrorKind::BadParam,
+            _ => ErrorKind::Unexpected,
+        };
+        Self::new(kind).with_code(code).with_source(err)
+    }
+}
+
+impl Display for DDApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.kind.describe())?;
+        if let Some(operation) = self.operation {
+            write!(f, " in {}", operation)?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if let Some(code) = self.code {
+            write!(f, " (HRESULT 0x{:08X})", code.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for DDApiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
+    }
+}
@@ -0,0 +1,244 @@
+//! Where displays sit on the virtual desktop.
+//!
+//! [Display::geometry][crate::outputs::Display::geometry] returns a [DisplayGeometry]: the
+//! display's rect in desktop coordinates, its work area (without the taskbar), rotation and
+//! effective dpi. [display_at] and [display_for_rect] find the display showing a point or a window,
+//! the same way `MonitorFromPoint` and `MonitorFromRect` do, but on plain data so they can be used
+//! with saved or synthetic layouts.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::geometry::{display_for_rect, Rect};
+//!
+//! let displays: Vec<_> = adapter.iter_displays().collect();
+//! let geometries = displays.iter().map(|d| d.geometry()).collect::<Result<Vec<_>, _>>()?;
+//! let window = Rect::new(1800, 200, 2600, 800);
+//! if let Some(idx) = display_for_rect(&geometries, window) {
+//!     println!("capture {} at {}x scale", displays[idx].name(), geometries[idx].scale_factor());
+//! }
+//! ```
+
+use windows::Win32::Foundation::{POINT, RECT};
+
+use crate::outputs::DisplayOrientation;
+
+#[cfg(test)]
+mod test {
+    use crate::geometry::{display_at, display_for_rect, DisplayGeometry, Point, Rect};
+
+    fn geometry(name: &str, desktop: Rect, dpi: u32) -> DisplayGeometry {
+        DisplayGeometry {
+            name: name.to_owned(),
+            desktop,
+            work_area: desktop,
+            dpi_x: dpi,
+            dpi_y: dpi,
+            attached: true,
+            ..Default::default()
+        }
+    }
+
+    // a 4k display at 150% left of the primary 1080p one, and a portrait one above it.
+    fn layout() -> Vec<DisplayGeometry> {
+        let mut primary = geometry("\\\\.\\DISPLAY1", Rect::new(0, 0, 1920, 1080), 96);
+        primary.primary = true;
+        primary.work_area = Rect::new(0, 0, 1920, 1040);
+        vec![
+            primary,
+            geometry("\\\\.\\DISPLAY2", Rect::new(-3840, -1080, 0, 1080), 144),
+            geometry("\\\\.\\DISPLAY3", Rect::new(0, -1920, 1080, 0), 96),
+        ]
+    }
+
+    #[test]
+    fn test_rect() {
+        let rect = Rect::new(-10, -20, 30, 40);
+        assert_eq!((rect.width(), rect.height(), rect.area()), (40, 60, 2400));
+        assert!(rect.contains(Point::new(-10, -20)));
+        // right and bottom edges are exclusive.
+        assert!(!rect.contains(Point::new(30, 0)) && !rect.contains(Point::new(0, 40)));
+
+        assert_eq!(rect.intersection(&Rect::new(20, 30, 100, 100)), Some(Rect::new(20, 30, 30, 40)));
+        assert_eq!(rect.intersection(&Rect::new(30, 0, 100, 100)), None);
+        assert_eq!(rect.union(&Rect::new(0, 0, 50, 10)), Rect::new(-10, -20, 50, 40));
+        assert_eq!(rect.offset(10, 20), Rect::new(0, 0, 40, 60));
+        assert!(Rect::new(5, 5, 5, 10).is_empty());
+        assert_eq!(Rect::new(5, 5, 0, 0).width(), 0);
+    }
+
+    #[test]
+    fn test_display_at() {
+        let displays = layout();
+        assert_eq!(display_at(&displays, Point::new(100, 100)), Some(0));
+        assert_eq!(display_at(&displays, Point::new(-1, 1079)), Some(1));
+        assert_eq!(display_at(&displays, Point::new(500, -1)), Some(2));
+        assert_eq!(display_at(&displays, Point::new(1500, -1)), None);
+    }
+
+    #[test]
+    fn test_display_for_rect() {
+        let mut displays = layout();
+        // mostly on the primary display.
+        assert_eq!(display_for_rect(&displays, Rect::new(-100, 0, 500, 500)), Some(0));
+        // mostly on the 4k display.
+        assert_eq!(display_for_rect(&displays, Rect::new(-500, 0, 100, 500)), Some(1));
+        assert_eq!(display_for_rect(&displays, Rect::new(5000, 0, 5100, 100)), None);
+
+        // detached displays are ignored.
+        displays[1].attached = false;
+        assert_eq!(display_for_rect(&displays, Rect::new(-500, 0, 100, 500)), Some(0));
+        assert_eq!(display_at(&displays, Point::new(-1, 0)), None);
+    }
+
+    #[test]
+    fn test_scale_factor() {
+        let displays = layout();
+        assert_eq!(displays[0].scale_factor(), 1.0);
+        assert_eq!(displays[1].scale_factor(), 1.5);
+        assert_eq!(DisplayGeometry::default().scale_factor(), 1.0);
+    }
+}
+
+/// a point in desktop coordinates.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<POINT> for Point {
+    fn from(p: POINT) -> Self {
+        Self::new(p.x, p.y)
+    }
+}
+
+impl From<Point> for POINT {
+    fn from(p: Point) -> Self {
+        POINT { x: p.x, y: p.y }
+    }
+}
+
+/// a rectangle in desktop coordinates. `right` and `bottom` are exclusive, like in `RECT`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Self { left, top, right, bottom }
+    }
+
+    /// width in pixels. zero for inverted rects.
+    pub fn width(&self) -> u32 {
+        (self.right as i64 - self.left as i64).max(0) as u32
+    }
+
+    /// height in pixels. zero for inverted rects.
+    pub fn height(&self) -> u32 {
+        (self.bottom as i64 - self.top as i64).max(0) as u32
+    }
+
+    /// number of pixels covered.
+    pub fn area(&self) -> u64 {
+        self.width() as u64 * self.height() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.area() == 0
+    }
+
+    /// returns true if the point lies in this rect.
+    pub fn contains(&self, p: Point) -> bool {
+        (self.left..self.right).contains(&p.x) && (self.top..self.bottom).contains(&p.y)
+    }
+
+    /// overlapping part of both rects. `None` if they don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect::new(self.left.max(other.left), self.top.max(other.top),
+                             self.right.min(other.right), self.bottom.min(other.bottom));
+        (!rect.is_empty()).then_some(rect)
+    }
+
+    /// smallest rect containing both rects.
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::new(self.left.min(other.left), self.top.min(other.top),
+                  self.right.max(other.right), self.bottom.max(other.bottom))
+    }
+
+    /// this rect moved by `dx` and `dy`.
+    pub fn offset(&self, dx: i32, dy: i32) -> Rect {
+        Rect::new(self.left + dx, self.top + dy, self.right + dx, self.bottom + dy)
+    }
+}
+
+impl From<RECT> for Rect {
+    fn from(r: RECT) -> Self {
+        Self::new(r.left, r.top, r.right, r.bottom)
+    }
+}
+
+impl From<Rect> for RECT {
+    fn from(r: Rect) -> Self {
+        RECT { left: r.left, top: r.top, right: r.right, bottom: r.bottom }
+    }
+}
+
+/// Position, size and scaling of a display. check the [module docs][self].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DisplayGeometry {
+    /// name of the display, as returned by [Display::name][crate::outputs::Display::name].
+    pub name: String,
+    /// the display's rect on the virtual desktop. width and height are swapped for portrait
+    /// orientations.
+    pub desktop: Rect,
+    /// part of `desktop` not covered by the taskbar and docked toolbars.
+    pub work_area: Rect,
+    /// rotation of the display.
+    pub orientation: DisplayOrientation,
+    /// effective horizontal dpi. 96 is 100% scaling.
+    pub dpi_x: u32,
+    /// effective vertical dpi.
+    pub dpi_y: u32,
+    /// the display is part of the desktop. detached displays have no meaningful rects.
+    pub attached: bool,
+    /// the display is the primary one, its top left corner is the desktop origin.
+    pub primary: bool,
+}
+
+impl DisplayGeometry {
+    /// scaling set for this display, e.g. `1.5` for 150%.
+    pub fn scale_factor(&self) -> f32 {
+        if self.dpi_x == 0 {
+            return 1.0;
+        }
+        self.dpi_x as f32 / 96.0
+    }
+}
+
+/// index of the attached display containing `point`.
+pub fn display_at(displays: &[DisplayGeometry], point: Point) -> Option<usize> {
+    displays.iter().position(|d| d.attached && d.desktop.contains(point))
+}
+
+/// index of the attached display with the largest intersection with `rect`. the first one wins
+/// ties. `None` if `rect` doesn't touch any display.
+pub fn display_for_rect(displays: &[DisplayGeometry], rect: Rect) -> Option<usize> {
+    let mut best: Option<(usize, u64)> = None;
+    for (idx, display) in displays.iter().enumerate().filter(|(_, d)| d.attached) {
+        let area = display.desktop.intersection(&rect).map_or(0, |r| r.area());
+        if area > best.map_or(0, |(_, a)| a) {
+            best = Some((idx, area));
+        }
+    }
+    best.map(|(idx, _)| idx)
+}
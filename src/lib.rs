@@ -3,18 +3,39 @@
 use crate::errors::DDApiError;
 
 pub mod devices;
+pub mod adapter_info;
+pub mod topology;
+pub mod display_id;
+pub mod edid;
+pub mod geometry;
+pub mod desktop;
+pub mod watcher;
+pub mod modes;
+pub mod mode_change;
+pub mod display_layout;
 pub mod outputs;
 pub mod duplication;
 mod utils;
 pub mod errors;
 pub mod texture;
 pub mod tex_reader;
+pub mod stream;
+pub mod channel;
+pub mod worker;
+pub mod pool;
+pub mod broadcast;
+pub mod vsync;
+pub mod session;
+pub mod supervisor;
+pub mod placeholder;
 
 
 
 
 pub use duplication::*;
-pub use utils::{co_init,set_process_dpi_awareness};
+pub use utils::{co_init,try_co_init,set_process_dpi_awareness};
+#[cfg(feature = "tokio")]
+pub use utils::spawn_graphics_thread;
 
 pub type Result<T> = core::result::Result<T, DDApiError>;
 
@@ -0,0 +1,329 @@
+//! Display mode changes that are undone unless confirmed.
+//!
+//! [ModeChangeGuard::apply] checks a mode with `CDS_TEST`, sets it and remembers the mode the
+//! display had before. the original mode is restored when the guard is dropped, or when the
+//! change isn't [confirmed][ModeChangeGuard::confirm] within
+//! [confirm_timeout][ModeChangeOptions::confirm_timeout], like the "keep these display settings?"
+//! dialog of windows does. a host switching resolution for a client can't leave the display in a
+//! mode nobody can see.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::mode_change::{ModeChangeGuard, ModeChangeOptions};
+//!
+//! let guard = ModeChangeGuard::apply(&display, &mode, ModeChangeOptions {
+//!     confirm_timeout: Some(Duration::from_secs(15)),
+//!     ..Default::default()
+//! })?;
+//! if client_sees_picture().await {
+//!     guard.confirm()?;
+//! } // otherwise dropping the guard restores the previous mode.
+//! ```
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{Builder, JoinHandle};
+use std::time::Duration;
+
+use log::{error, warn};
+use windows::Win32::Graphics::Gdi::{CDS_TEST, CDS_TYPE, CDS_UPDATEREGISTRY};
+
+use crate::errors::{DDApiError, ErrorKind};
+use crate::outputs::{change_display_settings, Display, DisplayMode};
+use crate::Result;
+
+#[cfg(test)]
+mod test {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use std::sync::{Arc, Mutex};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use crate::errors::{DDApiError, ErrorKind};
+    use crate::mode_change::{ChangeKind, GuardState, ModeChangeGuard, ModeChangeOptions};
+    use crate::outputs::DisplayMode;
+
+    fn mode(width: u32, height: u32) -> DisplayMode {
+        DisplayMode { width, height, refresh_num: 60, refresh_den: 1, ..Default::default() }
+    }
+
+    type Calls = Arc<Mutex<Vec<(u32, ChangeKind)>>>;
+
+    // guard changing 1080p to 720p, recording the width of every mode written.
+    fn guard(options: ModeChangeOptions) -> (crate::Result<ModeChangeGuard>, Calls) {
+        let calls = Calls::default();
+        let recorded = calls.clone();
+        let guard = ModeChangeGuard::from_setter(mode(1920, 1080), mode(1280, 720), options, move |mode, kind| {
+            recorded.lock().unwrap().push((mode.width, kind));
+            Ok(())
+        });
+        (guard, calls)
+    }
+
+    #[test]
+    fn test_restore_on_drop() {
+        let (guard, calls) = guard(Default::default());
+        let guard = guard.unwrap();
+        assert_eq!(guard.state(), GuardState::Pending);
+        assert_eq!(guard.original().width, 1920);
+        assert_eq!(*calls.lock().unwrap(), vec![(1280, ChangeKind::Test), (1280, ChangeKind::Apply)]);
+
+        drop(guard);
+        assert_eq!(calls.lock().unwrap().last(), Some(&(1920, ChangeKind::Apply)));
+    }
+
+    #[test]
+    fn test_confirm() {
+        let (guard, calls) = guard(ModeChangeOptions { persist: true, confirm_timeout: Some(Duration::from_secs(10)) });
+        let guard = guard.unwrap();
+        // nothing reaches the registry before the change is confirmed.
+        assert_eq!(*calls.lock().unwrap(), vec![(1280, ChangeKind::Test), (1280, ChangeKind::Apply)]);
+
+        guard.confirm().unwrap();
+        // the confirmed mode is saved and kept, and the timer thread is gone.
+        assert_eq!(*calls.lock().unwrap(), vec![
+            (1280, ChangeKind::Test),
+            (1280, ChangeKind::Apply),
+            (1280, ChangeKind::Persist),
+        ]);
+    }
+
+    #[test]
+    fn test_confirm_timeout() {
+        let (guard, calls) = guard(ModeChangeOptions { persist: true, confirm_timeout: Some(Duration::from_millis(20)) });
+        let guard = guard.unwrap();
+        sleep(Duration::from_millis(500));
+        assert_eq!(guard.state(), GuardState::Restored);
+        // the registry was never written, applying the original mode is enough.
+        assert_eq!(calls.lock().unwrap().last(), Some(&(1920, ChangeKind::Apply)));
+        assert!(!calls.lock().unwrap().iter().any(|(_, kind)| *kind == ChangeKind::Persist));
+
+        assert_eq!(guard.confirm().unwrap_err().kind(), ErrorKind::Unexpected);
+        assert_eq!(calls.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_panicking_setter() {
+        let calls = Calls::default();
+        let recorded = calls.clone();
+        let guard = ModeChangeGuard::from_setter(mode(1920, 1080), mode(1280, 720), ModeChangeOptions {
+            persist: true,
+            ..Default::default()
+        }, move |mode, kind| {
+            recorded.lock().unwrap().push((mode.width, kind));
+            if kind == ChangeKind::Persist {
+                panic!("registry is gone");
+            }
+            Ok(())
+        }).unwrap();
+        // the panic poisons the guard's lock, restoring on drop still works.
+        assert!(catch_unwind(AssertUnwindSafe(|| guard.confirm())).is_err());
+        assert_eq!(calls.lock().unwrap().last(), Some(&(1920, ChangeKind::Apply)));
+    }
+
+    #[test]
+    fn test_rejected_mode() {
+        let calls = Calls::default();
+        let recorded = calls.clone();
+        let guard = ModeChangeGuard::from_setter(mode(1920, 1080), mode(7680, 4320), Default::default(), move |mode, kind| {
+            recorded.lock().unwrap().push((mode.width, kind));
+            match kind {
+                ChangeKind::Test => Err(DDApiError::bad_param("unsupported mode")),
+                _ => Ok(()),
+            }
+        });
+        assert_eq!(guard.err().map(|e| e.kind()), Some(ErrorKind::BadParam));
+        // nothing was applied, so nothing is restored.
+        assert_eq!(*calls.lock().unwrap(), vec![(7680, ChangeKind::Test)]);
+    }
+}
+
+/// how [ModeChangeGuard] writes a mode.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ChangeKind {
+    /// only check that the mode can be set (`CDS_TEST`).
+    Test,
+    /// set the mode until the next restart or mode change.
+    Apply,
+    /// set the mode and save it to the registry (`CDS_UPDATEREGISTRY`).
+    Persist,
+}
+
+impl ChangeKind {
+    fn flags(self) -> CDS_TYPE {
+        match self {
+            ChangeKind::Test => CDS_TEST,
+            ChangeKind::Apply => CDS_TYPE(0),
+            ChangeKind::Persist => CDS_UPDATEREGISTRY,
+        }
+    }
+}
+
+/// options of [ModeChangeGuard].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ModeChangeOptions {
+    /// save the new mode to the registry once it is [confirmed][ModeChangeGuard::confirm], so it
+    /// survives a restart. unconfirmed modes never reach the registry.
+    pub persist: bool,
+    /// restore the original mode if the change isn't confirmed within this time. `None` waits
+    /// until the guard is dropped.
+    pub confirm_timeout: Option<Duration>,
+}
+
+/// where a [ModeChangeGuard] is at.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum GuardState {
+    /// the new mode is set and will be restored unless confirmed.
+    Pending,
+    /// the new mode is kept.
+    Confirmed,
+    /// the original mode was restored.
+    Restored,
+}
+
+type Setter = Box<dyn FnMut(&DisplayMode, ChangeKind) -> Result<()> + Send>;
+
+struct Inner {
+    state: GuardState,
+    setter: Setter,
+}
+
+struct Shared {
+    inner: Mutex<Inner>,
+    changed: Condvar,
+}
+
+impl Shared {
+    // a panicking setter must not keep the original mode from being restored.
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // restores `original` if the change is still pending. the registry was never written, so
+    // applying is enough.
+    fn restore(&self, original: &DisplayMode) -> Result<()> {
+        let mut inner = self.lock();
+        let result = match inner.state {
+            GuardState::Pending => {
+                inner.state = GuardState::Restored;
+                (inner.setter)(original, ChangeKind::Apply)
+            }
+            GuardState::Confirmed | GuardState::Restored => Ok(()),
+        };
+        self.changed.notify_all();
+        result
+    }
+}
+
+/// A display mode change that is undone unless confirmed. check the [module docs][self].
+pub struct ModeChangeGuard {
+    original: DisplayMode,
+    mode: DisplayMode,
+    persist: bool,
+    shared: Arc<Shared>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl ModeChangeGuard {
+    /// check and set `mode` on `display`. fails without changing anything if the display doesn't
+    /// accept the mode.
+    pub fn apply(display: &Display, mode: &DisplayMode, options: ModeChangeOptions) -> Result<Self> {
+        let name = display.c_name()?;
+        let original = display.get_current_display_mode()?;
+        Self::from_setter(original, mode.clone(), options, move |mode, kind| {
+            change_display_settings(&name, mode, kind.flags())
+        })
+    }
+
+    /// change from `original` to `mode` by calling `setter`, which writes a mode the way
+    /// [ChangeKind] says. `setter` is called on the timer thread when the confirmation times out.
+    pub fn from_setter<F>(original: DisplayMode, mode: DisplayMode, options: ModeChangeOptions, mut setter: F) -> Result<Self>
+        where F: FnMut(&DisplayMode, ChangeKind) -> Result<()> + Send + 'static {
+        setter(&mode, ChangeKind::Test)?;
+        setter(&mode, ChangeKind::Apply)?;
+
+        let shared = Arc::new(Shared {
+            inner: Mutex::new(Inner { state: GuardState::Pending, setter: Box::new(setter) }),
+            changed: Condvar::new(),
+        });
+        let mut guard = Self { original, mode, persist: options.persist, shared, timer: None };
+        if let Some(timeout) = options.confirm_timeout {
+            let shared = guard.shared.clone();
+            let original = guard.original.clone();
+            let timer = Builder::new().name("mode_change_timer".to_owned()).spawn(move || {
+                {
+                    let inner = shared.lock();
+                    let (inner, _) = shared.changed
+                        .wait_timeout_while(inner, timeout, |inner| inner.state == GuardState::Pending)
+                        .unwrap_or_else(|e| e.into_inner());
+                    if inner.state != GuardState::Pending {
+                        return;
+                    }
+                }
+                warn!("display mode change wasn't confirmed within {:?}, restoring {:?}", timeout, original);
+                if let Err(e) = shared.restore(&original) {
+                    error!("failed to restore the original display mode. {}", e);
+                }
+            });
+            match timer {
+                Ok(timer) => guard.timer = Some(timer),
+                Err(e) => {
+                    // dropping the guard restores the original mode.
+                    return Err(DDApiError::unexpected(format!("failed to spawn mode change timer thread. {:?}", e)));
+                }
+            }
+        }
+        Ok(guard)
+    }
+
+    /// mode of the display before the change.
+    pub fn original(&self) -> &DisplayMode {
+        &self.original
+    }
+
+    /// the mode that was set.
+    pub fn mode(&self) -> &DisplayMode {
+        &self.mode
+    }
+
+    pub fn state(&self) -> GuardState {
+        self.shared.lock().state
+    }
+
+    /// keep the new mode, saving it to the registry if [persist][ModeChangeOptions::persist] is
+    /// set. fails if the original mode was already restored because the confirmation timed out.
+    /// if saving fails, the original mode is restored.
+    pub fn confirm(self) -> Result<()> {
+        let mut inner = self.shared.lock();
+        let result = match inner.state {
+            GuardState::Pending => {
+                let saved = if self.persist { (inner.setter)(&self.mode, ChangeKind::Persist) } else { Ok(()) };
+                if saved.is_ok() {
+                    inner.state = GuardState::Confirmed;
+                }
+                saved
+            }
+            GuardState::Confirmed => Ok(()),
+            GuardState::Restored => Err(DDApiError::new(ErrorKind::Unexpected)
+                .with_message("the confirmation timed out and the original display mode was restored")),
+        };
+        self.shared.changed.notify_all();
+        result
+    }
+
+    /// restore the original mode now. unlike dropping the guard, this reports whether restoring
+    /// worked.
+    pub fn restore(self) -> Result<()> {
+        self.shared.restore(&self.original)
+    }
+}
+
+impl Drop for ModeChangeGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.shared.restore(&self.original) {
+            error!("failed to restore the original display mode. {}", e);
+        }
+        if let Some(timer) = self.timer.take() {
+            let _ = timer.join();
+        }
+    }
+}
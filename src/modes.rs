@@ -0,0 +1,559 @@
+//! Refresh rates as fractions and picking the display mode closest to a request.
+//!
+//! DXGI reports refresh rates as fractions, e.g. `60000/1001` for 59.94 Hz, and a "60 Hz" mode
+//! may well be `59997/1000`. [RefreshRate] keeps the fraction, compares rates exactly or within a
+//! tolerance and converts them to the whole numbers `ChangeDisplaySettings` expects.
+//!
+//! [best_mode] picks the supported mode closest to a [ModeRequest], so callers don't need to know
+//! the exact modes of a monitor. what "closest" means is set by the request's
+//! [priorities][ModeRequest::priorities].
+//!
+//! DXGI lists every mode once per scaling variant and pixel format. [ModeInfo] keeps those details
+//! next to the [DisplayMode], [clean_modes] drops the duplicates and sorts the list, [group_by_resolution] turns it into resolutions with their
+//! refresh rates, and [native_aspect_modes] and [progressive_modes] filter it.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::modes::{ModeRequest, RefreshRate};
+//!
+//! let mode = display.best_display_mode(&ModeRequest {
+//!     refresh_rate: Some(RefreshRate::from_hz(144)),
+//!     ..ModeRequest::resolution(2560, 1440)
+//! })?;
+//! display.set_display_mode(&mode)?;
+//! ```
+
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+
+use windows::Win32::Graphics::Dxgi::Common::{DXGI_MODE_SCALING, DXGI_MODE_SCALING_CENTERED, DXGI_MODE_SCALING_STRETCHED, DXGI_MODE_SCANLINE_ORDER, DXGI_MODE_SCANLINE_ORDER_LOWER_FIELD_FIRST, DXGI_MODE_SCANLINE_ORDER_PROGRESSIVE, DXGI_MODE_SCANLINE_ORDER_UPPER_FIELD_FIRST};
+
+use crate::outputs::DisplayMode;
+use crate::texture::ColorFormat;
+
+#[cfg(test)]
+mod test {
+    use crate::modes::{best_mode, clean_modes, group_by_resolution, ModeCriterion, ModeInfo, ModeRequest, ModeScaling, native_aspect_modes, native_resolution, progressive_modes, RefreshRate, ScanlineOrdering};
+    use crate::outputs::DisplayMode;
+    use crate::texture::ColorFormat;
+
+    fn mode(width: u32, height: u32, refresh_num: u32, refresh_den: u32, hdr: bool) -> DisplayMode {
+        DisplayMode { width, height, refresh_num, refresh_den, hdr, ..Default::default() }
+    }
+
+    // modes as reported by a 4k 144Hz HDR monitor. only 4k supports HDR.
+    fn monitor() -> Vec<DisplayMode> {
+        vec![
+            mode(1920, 1080, 60000, 1001, false),
+            mode(1920, 1080, 60000, 1000, false),
+            mode(1920, 1080, 119998, 1000, false),
+            mode(2560, 1440, 59951, 1000, false),
+            mode(2560, 1440, 143973, 1000, false),
+            mode(3840, 2160, 30000, 1000, false),
+            mode(3840, 2160, 59997, 1000, false),
+            mode(3840, 2160, 143998, 1000, false),
+            mode(3840, 2160, 59997, 1000, true),
+            mode(3840, 2160, 119999, 1000, true),
+        ]
+    }
+
+    // what DXGI reports for a 1080p monitor: every mode per scaling variant and format.
+    fn raw_modes() -> Vec<ModeInfo> {
+        let mut out = Vec::new();
+        for (hdr, format) in [(false, ColorFormat::ARGB8UNorm), (true, ColorFormat::ARGB16Float)] {
+            for (width, height, refresh_num, refresh_den, scanline_ordering) in [
+                (1280, 720, 60000, 1000, ScanlineOrdering::Progressive),
+                (1920, 1080, 60000, 1000, ScanlineOrdering::Progressive),
+                (1920, 1080, 60000, 1001, ScanlineOrdering::Progressive),
+                (1920, 1080, 60000, 1000, ScanlineOrdering::UpperFieldFirst),
+                (1920, 1080, 119998, 1000, ScanlineOrdering::Progressive),
+                (1280, 1024, 60000, 1000, ScanlineOrdering::Progressive),
+                (1366, 768, 60000, 1000, ScanlineOrdering::Progressive),
+            ] {
+                for scaling in [ModeScaling::Centered, ModeScaling::Unspecified, ModeScaling::Stretched] {
+                    out.push(ModeInfo {
+                        mode: mode(width, height, refresh_num, refresh_den, hdr),
+                        scaling,
+                        scanline_ordering,
+                        stereo: false,
+                        format,
+                    });
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_clean_modes() {
+        let modes = clean_modes(raw_modes());
+        // one mode per scaling variant is left.
+        assert_eq!(modes.len(), 14);
+        assert!(modes.iter().all(|m| m.scaling == ModeScaling::Unspecified));
+
+        let sdr: Vec<_> = modes.iter().filter(|m| !m.mode.hdr)
+            .map(|m| (m.mode.width, m.mode.height, m.mode.refresh_rate().to_devmode_hz(), m.is_interlaced()))
+            .collect();
+        assert_eq!(sdr, vec![
+            (1920, 1080, 120, false),
+            (1920, 1080, 60, false),
+            (1920, 1080, 60, true),
+            (1920, 1080, 59, false),
+            (1280, 1024, 60, false),
+            (1366, 768, 60, false),
+            (1280, 720, 60, false),
+        ]);
+        // SDR before HDR for the same mode.
+        assert!(!modes[0].mode.hdr && modes[1].mode.hdr);
+        assert_eq!(modes[1].format, ColorFormat::ARGB16Float);
+        assert_eq!(clean_modes(modes.clone()), modes);
+    }
+
+    #[test]
+    fn test_group_by_resolution() {
+        let groups = group_by_resolution(&clean_modes(raw_modes()));
+        let resolutions: Vec<_> = groups.iter().map(|g| (g.width, g.height)).collect();
+        assert_eq!(resolutions, vec![(1920, 1080), (1280, 1024), (1366, 768), (1280, 720)]);
+        assert_eq!(groups[0].refresh_rates, vec![
+            RefreshRate::new(119998, 1000),
+            RefreshRate::from_hz(60),
+            RefreshRate::new(60000, 1001),
+        ]);
+        assert_eq!(groups[3].refresh_rates, vec![RefreshRate::from_hz(60)]);
+        assert!(group_by_resolution::<DisplayMode>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_mode_filters() {
+        let modes = clean_modes(raw_modes());
+        assert_eq!(native_resolution(&modes), Some((1920, 1080)));
+        assert_eq!(native_resolution::<ModeInfo>(&[]), None);
+
+        // 1366x768 is close enough to 16:9, 1280x1024 is not.
+        let native: Vec<_> = native_aspect_modes(&modes, 1920, 1080).map(|m| (m.mode.width, m.mode.height)).collect();
+        assert!(native.contains(&(1366, 768)) && native.contains(&(1280, 720)));
+        assert!(!native.contains(&(1280, 1024)));
+
+        assert_eq!(progressive_modes(&modes).count(), 12);
+        assert!(progressive_modes(&modes).all(|m| !m.is_interlaced()));
+
+        assert_eq!(mode(1920, 1080, 60, 1, false).aspect_ratio(), (16, 9));
+        assert_eq!(mode(2560, 1080, 60, 1, false).aspect_ratio(), (64, 27));
+        assert_eq!(DisplayMode::default().aspect_ratio(), (0, 0));
+    }
+
+    #[test]
+    fn test_refresh_rate() {
+        let ntsc = RefreshRate::new(60000, 1001);
+        assert!((ntsc.as_hz() - 59.94).abs() < 0.001);
+        assert_eq!(RefreshRate::new(120, 2), RefreshRate::from_hz(60));
+        assert!(ntsc < RefreshRate::from_hz(60));
+        assert!(ntsc.approx_eq(RefreshRate::from_hz(60), 0.1));
+        assert!(!ntsc.approx_eq(RefreshRate::from_hz(60), 0.05));
+        assert_eq!(RefreshRate::new(60, 0).as_hz(), 0.0);
+        assert_eq!(RefreshRate::new(60, 0), RefreshRate::default());
+        assert_eq!(ntsc.to_string(), "59.94 Hz");
+    }
+
+    #[test]
+    fn test_devmode_hz() {
+        // windows lists x/1.001 rates one below.
+        assert_eq!(RefreshRate::new(60000, 1001).to_devmode_hz(), 59);
+        assert_eq!(RefreshRate::new(24000, 1001).to_devmode_hz(), 23);
+        assert_eq!(RefreshRate::new(59997, 1000).to_devmode_hz(), 60);
+        assert_eq!(RefreshRate::new(143973, 1000).to_devmode_hz(), 144);
+        assert_eq!(RefreshRate::new(119998, 1000).to_devmode_hz(), 120);
+        assert_eq!(RefreshRate::from_hz(75).to_devmode_hz(), 75);
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let modes = monitor();
+        let best = best_mode(&modes, &ModeRequest {
+            refresh_rate: Some(RefreshRate::from_hz(60)),
+            ..ModeRequest::resolution(1920, 1080)
+        }).unwrap();
+        assert_eq!(best.refresh_rate(), RefreshRate::new(60000, 1000));
+
+        let best = best_mode(&modes, &ModeRequest {
+            refresh_rate: Some(RefreshRate::new(60000, 1001)),
+            ..ModeRequest::resolution(1920, 1080)
+        }).unwrap();
+        assert_eq!(best.refresh_rate(), RefreshRate::new(60000, 1001));
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let modes = monitor();
+        // no refresh rate requested, the highest one wins.
+        let best = best_mode(&modes, &ModeRequest::resolution(2560, 1440)).unwrap();
+        assert_eq!(best.refresh_rate().to_devmode_hz(), 144);
+
+        // 144Hz is matched by 143.998Hz.
+        let best = best_mode(&modes, &ModeRequest {
+            refresh_rate: Some(RefreshRate::from_hz(144)),
+            ..ModeRequest::resolution(3840, 2160)
+        }).unwrap();
+        assert_eq!((best.width, best.refresh_rate().to_devmode_hz()), (3840, 144));
+
+        // unsupported resolution, the closest one is picked.
+        let best = best_mode(&modes, &ModeRequest::resolution(2560, 1600)).unwrap();
+        assert_eq!((best.width, best.height), (2560, 1440));
+
+        assert!(best_mode(&[], &ModeRequest::resolution(1920, 1080)).is_none());
+    }
+
+    #[test]
+    fn test_priorities() {
+        let modes = monitor();
+        let request = ModeRequest {
+            refresh_rate: Some(RefreshRate::from_hz(120)),
+            hdr: Some(true),
+            ..ModeRequest::resolution(1920, 1080)
+        };
+        // resolution first: 1080p at 120Hz, without HDR.
+        let best = best_mode(&modes, &request).unwrap();
+        assert_eq!((best.width, best.refresh_rate().to_devmode_hz(), best.hdr), (1920, 120, false));
+
+        // HDR first: the only HDR resolution, at the requested rate.
+        let best = best_mode(&modes, &ModeRequest {
+            priorities: vec![ModeCriterion::Hdr, ModeCriterion::RefreshRate, ModeCriterion::Resolution],
+            ..request.clone()
+        }).unwrap();
+        assert_eq!((best.width, best.refresh_rate().to_devmode_hz(), best.hdr), (3840, 120, true));
+
+        // refresh rate only: the resolution doesn't matter, 4k has the closest rate to 144Hz.
+        let best = best_mode(&modes, &ModeRequest {
+            priorities: vec![ModeCriterion::RefreshRate],
+            refresh_rate: Some(RefreshRate::from_hz(144)),
+            ..ModeRequest::resolution(1920, 1080)
+        }).unwrap();
+        assert_eq!((best.width, best.refresh_rate().to_devmode_hz()), (3840, 144));
+    }
+}
+
+/// A refresh rate as a fraction in Hz.
+///
+/// rates are equal if their fractions are, `120/2 == 60/1`. a zero denominator means the rate is
+/// unknown and equals `0/1`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RefreshRate {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl RefreshRate {
+    pub fn new(num: u32, den: u32) -> Self {
+        Self { num, den }
+    }
+
+    /// a whole number rate.
+    pub fn from_hz(hz: u32) -> Self {
+        Self::new(hz, 1)
+    }
+
+    /// the rate in Hz. zero if the rate is unknown.
+    pub fn as_hz(&self) -> f64 {
+        if self.den == 0 {
+            return 0.0;
+        }
+        self.num as f64 / self.den as f64
+    }
+
+    /// returns true if both rates differ by at most `tolerance_hz`.
+    pub fn approx_eq(&self, other: RefreshRate, tolerance_hz: f64) -> bool {
+        (self.as_hz() - other.as_hz()).abs() <= tolerance_hz
+    }
+
+    /// the whole number of Hz windows uses for this rate in `DEVMODE::dmDisplayFrequency`.
+    ///
+    /// rates are rounded, except for the `n/1.001` rates of TV standards (59.94, 29.97, 23.976 Hz)
+    /// which windows lists as `n - 1`.
+    pub fn to_devmode_hz(&self) -> u32 {
+        let hz = self.as_hz();
+        let rounded = hz.round();
+        if rounded >= 1.0 && (hz - rounded * 1000.0 / 1001.0).abs() < 0.005 {
+            return rounded as u32 - 1;
+        }
+        rounded as u32
+    }
+
+    // fraction with a non-zero denominator.
+    fn normalized(&self) -> (u64, u64) {
+        if self.den == 0 { (0, 1) } else { (self.num as u64, self.den as u64) }
+    }
+}
+
+impl PartialEq for RefreshRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RefreshRate {}
+
+impl PartialOrd for RefreshRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RefreshRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b) = (self.normalized(), other.normalized());
+        (a.0 * b.1).cmp(&(b.0 * a.1))
+    }
+}
+
+impl Display for RefreshRate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2} Hz", self.as_hz())
+    }
+}
+
+/// something [best_mode] compares modes by.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ModeCriterion {
+    /// the resolution closest to the requested one.
+    Resolution,
+    /// the refresh rate closest to the requested one, or the highest one if none was requested.
+    RefreshRate,
+    /// the requested HDR setting.
+    Hdr,
+}
+
+/// what [best_mode] looks for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModeRequest {
+    pub width: u32,
+    pub height: u32,
+    /// requested refresh rate. the highest one is picked if `None`.
+    pub refresh_rate: Option<RefreshRate>,
+    /// requested HDR setting. either is fine if `None`.
+    pub hdr: Option<bool>,
+    /// criteria in order of importance. a criterion only decides between modes that are equally
+    /// good in all criteria before it. criteria not listed are ignored.
+    pub priorities: Vec<ModeCriterion>,
+    /// refresh rates within this many Hz of the requested one count as exact matches.
+    pub refresh_tolerance_hz: f64,
+}
+
+impl ModeRequest {
+    /// request a resolution, with the highest refresh rate and any HDR setting.
+    pub fn resolution(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    // distance of `mode` from this request, one entry per priority. smaller is better.
+    fn distance(&self, mode: &DisplayMode) -> Vec<u64> {
+        self.priorities.iter().map(|criterion| match criterion {
+            ModeCriterion::Resolution => {
+                mode.width.abs_diff(self.width) as u64 + mode.height.abs_diff(self.height) as u64
+            }
+            ModeCriterion::RefreshRate => {
+                let rate = mode.refresh_rate();
+                match self.refresh_rate {
+                    Some(req) if rate.approx_eq(req, self.refresh_tolerance_hz) => 0,
+                    // millihertz, so fractional rates still compare.
+                    Some(req) => ((rate.as_hz() - req.as_hz()).abs() * 1000.0) as u64,
+                    None => u64::MAX - (rate.as_hz() * 1000.0) as u64,
+                }
+            }
+            ModeCriterion::Hdr => match self.hdr {
+                Some(hdr) if hdr != mode.hdr => 1,
+                _ => 0,
+            },
+        }).collect()
+    }
+}
+
+impl Default for ModeRequest {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            refresh_rate: None,
+            hdr: None,
+            priorities: vec![ModeCriterion::Resolution, ModeCriterion::RefreshRate, ModeCriterion::Hdr],
+            refresh_tolerance_hz: 0.01,
+        }
+    }
+}
+
+/// the mode of `modes` closest to `request`. of equally good modes the first one wins. `None` if
+/// `modes` is empty.
+pub fn best_mode<'a>(modes: &'a [DisplayMode], request: &ModeRequest) -> Option<&'a DisplayMode> {
+    modes.iter()
+        .map(|mode| (request.distance(mode), mode))
+        .reduce(|best, next| if next.0 < best.0 { next } else { best })
+        .map(|(_, mode)| mode)
+}
+
+/// how a mode is scaled to the panel.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum ModeScaling {
+    /// left to the driver.
+    #[default]
+    Unspecified,
+    /// the image is centered without scaling.
+    Centered,
+    /// the image is stretched to the panel.
+    Stretched,
+}
+
+impl From<DXGI_MODE_SCALING> for ModeScaling {
+    fn from(scaling: DXGI_MODE_SCALING) -> Self {
+        match scaling {
+            DXGI_MODE_SCALING_CENTERED => Self::Centered,
+            DXGI_MODE_SCALING_STRETCHED => Self::Stretched,
+            _ => Self::Unspecified,
+        }
+    }
+}
+
+/// order in which lines are scanned out.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum ScanlineOrdering {
+    #[default]
+    Unspecified,
+    Progressive,
+    /// interlaced, upper field first.
+    UpperFieldFirst,
+    /// interlaced, lower field first.
+    LowerFieldFirst,
+}
+
+impl ScanlineOrdering {
+    pub fn is_interlaced(&self) -> bool {
+        matches!(self, Self::UpperFieldFirst | Self::LowerFieldFirst)
+    }
+}
+
+impl From<DXGI_MODE_SCANLINE_ORDER> for ScanlineOrdering {
+    fn from(order: DXGI_MODE_SCANLINE_ORDER) -> Self {
+        match order {
+            DXGI_MODE_SCANLINE_ORDER_PROGRESSIVE => Self::Progressive,
+            DXGI_MODE_SCANLINE_ORDER_UPPER_FIELD_FIRST => Self::UpperFieldFirst,
+            DXGI_MODE_SCANLINE_ORDER_LOWER_FIELD_FIRST => Self::LowerFieldFirst,
+            _ => Self::Unspecified,
+        }
+    }
+}
+
+/// A [DisplayMode] with the details DXGI reports for it. returned by
+/// [Display::get_detailed_display_modes][crate::outputs::Display::get_detailed_display_modes].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ModeInfo {
+    pub mode: DisplayMode,
+    /// how the image is scaled to the panel.
+    pub scaling: ModeScaling,
+    /// progressive or interlaced scan out.
+    pub scanline_ordering: ScanlineOrdering,
+    /// the mode is a stereo (3D) mode.
+    pub stereo: bool,
+    /// pixel format of the desktop in this mode.
+    pub format: ColorFormat,
+}
+
+impl ModeInfo {
+    /// returns true for interlaced modes.
+    pub fn is_interlaced(&self) -> bool {
+        self.scanline_ordering.is_interlaced()
+    }
+}
+
+impl AsRef<DisplayMode> for ModeInfo {
+    fn as_ref(&self) -> &DisplayMode {
+        &self.mode
+    }
+}
+
+impl AsRef<DisplayMode> for DisplayMode {
+    fn as_ref(&self) -> &DisplayMode {
+        self
+    }
+}
+
+/// sort modes of one display and drop duplicates.
+///
+/// modes are sorted by resolution, largest first, then by refresh rate, highest first. for equal
+/// rates progressive modes come before interlaced ones and SDR before HDR. modes differing only in
+/// [scaling][ModeInfo::scaling] are duplicates, the one with the lowest [ModeScaling] is kept.
+pub fn clean_modes(mut modes: Vec<ModeInfo>) -> Vec<ModeInfo> {
+    modes.sort_by(|a, b| compare_modes(a, b).then(a.scaling.cmp(&b.scaling)));
+    modes.dedup_by(|next, kept| compare_modes(next, kept) == Ordering::Equal);
+    modes
+}
+
+// order of clean_modes, without scaling.
+fn compare_modes(a: &ModeInfo, b: &ModeInfo) -> Ordering {
+    let pixels = |m: &ModeInfo| m.mode.width as u64 * m.mode.height as u64;
+    pixels(b).cmp(&pixels(a))
+        .then(b.mode.width.cmp(&a.mode.width))
+        .then(b.mode.refresh_rate().cmp(&a.mode.refresh_rate()))
+        .then(a.is_interlaced().cmp(&b.is_interlaced()))
+        .then(a.scanline_ordering.cmp(&b.scanline_ordering))
+        .then(a.mode.hdr.cmp(&b.mode.hdr))
+        .then((a.format as u32).cmp(&(b.format as u32)))
+        .then(a.stereo.cmp(&b.stereo))
+}
+
+/// a resolution and the refresh rates it supports.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ResolutionModes {
+    pub width: u32,
+    pub height: u32,
+    /// distinct refresh rates, highest first.
+    pub refresh_rates: Vec<RefreshRate>,
+}
+
+/// group `modes` by resolution, in the order the resolutions first appear.
+pub fn group_by_resolution<M: AsRef<DisplayMode>>(modes: &[M]) -> Vec<ResolutionModes> {
+    let mut groups: Vec<ResolutionModes> = Vec::new();
+    for mode in modes.iter().map(AsRef::as_ref) {
+        let idx = match groups.iter().position(|g| (g.width, g.height) == (mode.width, mode.height)) {
+            Some(idx) => idx,
+            None => {
+                groups.push(ResolutionModes { width: mode.width, height: mode.height, refresh_rates: Vec::new() });
+                groups.len() - 1
+            }
+        };
+        let rates = &mut groups[idx].refresh_rates;
+        if !rates.contains(&mode.refresh_rate()) {
+            rates.push(mode.refresh_rate());
+        }
+    }
+    for group in groups.iter_mut() {
+        group.refresh_rates.sort_by(|a, b| b.cmp(a));
+    }
+    groups
+}
+
+/// the largest resolution of `modes`, usually the native one of the panel. drivers with super
+/// resolution enabled list larger ones, the native timing of the [Edid][crate::edid::Edid] is
+/// more reliable then.
+pub fn native_resolution<M: AsRef<DisplayMode>>(modes: &[M]) -> Option<(u32, u32)> {
+    modes.iter()
+        .map(AsRef::as_ref)
+        .max_by_key(|m| (m.width as u64 * m.height as u64, m.width))
+        .map(|m| (m.width, m.height))
+}
+
+/// modes with the aspect ratio of the native resolution `width` x `height`, see
+/// [DisplayMode::has_aspect_of].
+pub fn native_aspect_modes<M: AsRef<DisplayMode>>(modes: &[M], width: u32, height: u32) -> impl Iterator<Item=&M> {
+    modes.iter().filter(move |m| m.as_ref().has_aspect_of(width, height))
+}
+
+/// modes that are not interlaced.
+pub fn progressive_modes(modes: &[ModeInfo]) -> impl Iterator<Item=&ModeInfo> {
+    modes.iter().filter(|m| !m.is_interlaced())
+}
+
+pub(crate) fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
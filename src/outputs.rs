@@ -3,26 +3,33 @@
 //! * [Display] - basic wrapper for output with options to change resolution and refresh-rates
 //! * [DisplayVSyncStream] - provides async [Stream][futures::Stream] that ticks at every
 //!                          display vsync event.
-use std::cmp::max;
-use std::ffi::{c_void, CString};
+use std::ffi::{c_void, CStr, CString};
 use std::mem::{size_of, swap, transmute};
 use std::pin::Pin;
 use std::ptr::{null, null_mut};
 use std::sync::mpsc::{channel, Receiver, TryRecvError};
 use std::task::{Context, Poll, Waker};
-use std::thread::{JoinHandle, sleep, spawn};
+use std::sync::Arc;
 use std::time::Duration;
 
-use futures::Stream;
-use log::{error, trace};
+use futures::{Stream, StreamExt};
+use futures::channel::mpsc;
+use log::error;
 use windows::core::{PCSTR, Result as WinResult};
-use windows::Win32::Graphics::Dxgi::{DXGI_MODE_DESC1, DXGI_OUTPUT_DESC1, DXGIDisableVBlankVirtualization, IDXGIOutput6};
-use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM};
-use windows::Win32::Graphics::Gdi::{CDS_TYPE, ChangeDisplaySettingsExA, DEVMODE_DISPLAY_ORIENTATION, DEVMODEA, DISP_CHANGE_SUCCESSFUL, DISPLAY_DEVICEA, DISPLAY_DEVICEW, DM_BITSPERPEL, DM_DISPLAYFREQUENCY, DM_DISPLAYORIENTATION, DM_PELSHEIGHT, DM_PELSWIDTH, ENUM_CURRENT_SETTINGS, ENUM_DISPLAY_SETTINGS_FLAGS, EnumDisplayDevicesW, EnumDisplaySettingsExA, GetMonitorInfoW, MONITORINFO, MONITORINFOEXW};
-use windows::Win32::UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME;
-
-use crate::errors::DDApiError;
+use windows::Win32::Graphics::Dxgi::{DXGI_MODE_DESC1, DXGI_OUTPUT_DESC1, IDXGIOutput6};
+use windows::Win32::Graphics::Dxgi::Common::{DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020, DXGI_FORMAT, DXGI_MODE_ROTATION, DXGI_MODE_ROTATION_ROTATE180, DXGI_MODE_ROTATION_ROTATE270, DXGI_MODE_ROTATION_ROTATE90, DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM};
+use windows::Win32::Graphics::Gdi::{CDS_TEST, CDS_TYPE, ChangeDisplaySettingsExA, DEVMODE_DISPLAY_ORIENTATION, DEVMODEA, DISP_CHANGE, DISP_CHANGE_RESTART, DISP_CHANGE_SUCCESSFUL, DISPLAY_DEVICEA, DISPLAY_DEVICEW, DM_BITSPERPEL, DM_DISPLAYFREQUENCY, DM_DISPLAYORIENTATION, DM_PELSHEIGHT, DM_PELSWIDTH, ENUM_CURRENT_SETTINGS, ENUM_DISPLAY_SETTINGS_FLAGS, EnumDisplayDevicesW, EnumDisplaySettingsExA, GetMonitorInfoW, MONITORINFO, MONITORINFOEXW};
+use windows::Win32::UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+use windows::Win32::UI::WindowsAndMessaging::{EDD_GET_DEVICE_INTERFACE_NAME, MONITORINFOF_PRIMARY};
+
+use crate::display_id::DisplayId;
+use crate::edid::{Edid, read_edid};
+use crate::errors::{DDApiError, ErrorKind};
+use crate::geometry::{DisplayGeometry, Rect};
+use crate::modes::{best_mode, clean_modes, gcd, ModeInfo, ModeRequest, RefreshRate};
 use crate::utils::convert_u16_to_string;
+use crate::vsync;
+use crate::vsync::{VSyncBroadcaster, VsyncSource};
 
 #[cfg(test)]
 mod test {
@@ -34,10 +41,9 @@ mod test {
     use futures::StreamExt;
     use tokio::runtime::Builder;
     use tokio::time;
-    use windows::Win32::Graphics::Dxgi::DXGI_OUTPUT_DESC1;
-    use windows::Win32::Graphics::Gdi::{GetMonitorInfoA, MONITORINFO};
 
     use crate::devices::AdapterFactory;
+    use crate::mode_change::ModeChangeGuard;
     use crate::outputs::{DisplayMode, DisplayOrientation};
 
     #[test]
@@ -61,8 +67,6 @@ mod test {
     #[test]
     fn test_display_setting_change() {
         let disp = AdapterFactory::new().get_adapter_by_idx(0).unwrap().get_display_by_idx(0).unwrap();
-        let curr_settings = disp.get_current_display_mode().unwrap();
-
         let mode = DisplayMode {
             width: 1920,
             height: 1080,
@@ -72,10 +76,10 @@ mod test {
             hdr: false,
         };
 
-        disp.set_display_mode(&mode).unwrap();
+        let guard = ModeChangeGuard::apply(&disp, &mode, Default::default()).unwrap();
         sleep(Duration::from_secs(5));
-        disp.set_display_mode(&curr_settings).unwrap();
-        println!("{:?}", curr_settings);
+        println!("{:?}", guard.original());
+        guard.restore().unwrap();
     }
 
     #[test]
@@ -87,15 +91,10 @@ mod test {
 
 
     #[test]
-    fn test_display_rect() {
-        let disp = AdapterFactory::new().get_adapter_by_idx(0).unwrap().get_display_by_idx(1).unwrap();
-        let mut desc: DXGI_OUTPUT_DESC1 = Default::default();
-        unsafe { disp.0.GetDesc1(&mut desc).unwrap() };
-
-        let mut info: MONITORINFO = Default::default();
-        info.cbSize = size_of::<MONITORINFO>() as u32;
-        unsafe { GetMonitorInfoA(desc.Monitor, &mut info); }
-        println!("{:?}", info.rcMonitor);
+    fn test_display_geometry() {
+        for display in AdapterFactory::new().get_adapter_by_idx(0).unwrap().iter_displays() {
+            println!("{:?}", display.geometry().unwrap());
+        }
     }
 
     #[test]
@@ -146,73 +145,172 @@ impl Display {
     }
 
     /// returns name of this monitor
+    ///
+    /// panics if the driver fails to describe the output. use [try_name][Self::try_name] to
+    /// handle the error instead.
     pub fn name(&self) -> String {
-        let mut desc: DXGI_OUTPUT_DESC1 = Default::default();
-        unsafe { self.0.GetDesc1(&mut desc).unwrap() };
-        convert_u16_to_string(&desc.DeviceName)
+        self.try_name().unwrap()
+    }
+
+    /// returns name of this monitor or the error of `GetDesc1`.
+    pub fn try_name(&self) -> Result<String, DDApiError> {
+        Ok(convert_u16_to_string(&self.desc()?.DeviceName))
     }
 
+    /// returns the device string of this monitor.
+    ///
+    /// panics if the driver fails to describe the output. use
+    /// [try_display_name][Self::try_display_name] to handle the error instead.
     pub fn display_name(&self) -> String {
-        let mut desc: DXGI_OUTPUT_DESC1 = Default::default();
-        unsafe { self.0.GetDesc1(&mut desc).unwrap() };
+        self.try_display_name().unwrap()
+    }
+
+    /// returns the device string of this monitor or the error of `GetDesc1`.
+    pub fn try_display_name(&self) -> Result<String, DDApiError> {
+        let display_device = self.monitor_device()?;
+        Ok(convert_u16_to_string(&display_device.DeviceString))
+    }
+
+    /// returns the device interface path of the monitor, e.g.
+    /// `\\?\DISPLAY#DEL40F4#5&2a5d0a5e&0&UID4352#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}`.
+    pub fn device_path(&self) -> Result<String, DDApiError> {
+        let display_device = self.monitor_device()?;
+        let path = convert_u16_to_string(&display_device.DeviceID);
+        if path.is_empty() {
+            return Err(DDApiError::new(ErrorKind::Unsupported).with_operation("EnumDisplayDevicesW")
+                .with_message("no monitor device is attached to this output"));
+        }
+        Ok(path)
+    }
+
+    /// returns the [stable identity][DisplayId] of the monitor. missing parts, like the EDID of a
+    /// virtual display, are left empty.
+    pub fn id(&self) -> Result<DisplayId, DDApiError> {
+        let name = self.try_name()?;
+        let path = self.device_path().ok();
+        let edid = path.as_deref().and_then(|path| read_edid(path).ok());
+        Ok(DisplayId::from_parts(path.as_deref(), edid.as_deref(), &name))
+    }
+
+    /// raw EDID of the monitor as cached by windows, including extension blocks.
+    pub fn edid(&self) -> Result<Vec<u8>, DDApiError> {
+        read_edid(&self.device_path()?)
+    }
+
+    /// the monitor's EDID, parsed. see [Edid].
+    pub fn edid_info(&self) -> Result<Edid, DDApiError> {
+        Edid::parse(&self.edid()?)
+    }
+
+    /// returns where this display sits on the virtual desktop, its work area, rotation and
+    /// effective dpi. detached displays report their desktop rect as work area and 96 dpi.
+    pub fn geometry(&self) -> Result<DisplayGeometry, DDApiError> {
+        let desc = self.desc()?;
+        let mut geometry = DisplayGeometry {
+            name: convert_u16_to_string(&desc.DeviceName),
+            desktop: Rect::from(desc.DesktopCoordinates),
+            work_area: Rect::from(desc.DesktopCoordinates),
+            orientation: desc.Rotation.into(),
+            dpi_x: 96,
+            dpi_y: 96,
+            attached: desc.AttachedToDesktop.as_bool(),
+            primary: false,
+        };
+        if !geometry.attached {
+            return Ok(geometry);
+        }
+
+        let mut info = MONITORINFO {
+            cbSize: size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if !unsafe { GetMonitorInfoW(desc.Monitor, &mut info) }.as_bool() {
+            return Err(DDApiError::unexpected("failed to get monitor info").with_operation("GetMonitorInfoW"));
+        }
+        geometry.work_area = info.rcWork.into();
+        geometry.primary = info.dwFlags & MONITORINFOF_PRIMARY != 0;
+
+        unsafe { GetDpiForMonitor(desc.Monitor, MDT_EFFECTIVE_DPI, &mut geometry.dpi_x, &mut geometry.dpi_y) }
+            .map_err(|e| DDApiError::from_win("GetDpiForMonitor", e))?;
+        Ok(geometry)
+    }
+
+    /// returns true if windows drives this display in HDR mode, i.e. its output color space is
+    /// BT.2020 with the ST 2084 (PQ) transfer function.
+    pub fn hdr_enabled(&self) -> Result<bool, DDApiError> {
+        Ok(self.desc()?.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020)
+    }
+
+    // the monitor attached to this output.
+    fn monitor_device(&self) -> Result<DISPLAY_DEVICEW, DDApiError> {
+        let desc = self.desc()?;
 
         let mut display_device: DISPLAY_DEVICEW = Default::default();
         display_device.cb = size_of::<DISPLAY_DEVICEW>() as u32;
 
         unsafe { EnumDisplayDevicesW(windows::core::PCWSTR(&desc.DeviceName[0]), 0, &mut display_device, EDD_GET_DEVICE_INTERFACE_NAME); }
+        Ok(display_device)
+    }
 
-        convert_u16_to_string(&display_device.DeviceString)
+    fn desc(&self) -> Result<DXGI_OUTPUT_DESC1, DDApiError> {
+        let mut desc: DXGI_OUTPUT_DESC1 = Default::default();
+        unsafe { self.0.GetDesc1(&mut desc) }.map_err(|e| DDApiError::from_win("GetDesc1", e))?;
+        Ok(desc)
     }
 
-    /// get supported display modes
-    pub fn get_display_modes(&self) -> Result<Vec<DisplayMode>, DDApiError> {
-        // SDR display modes.
-        let mut out = Vec::new();
-        self.fill_modes(DXGI_FORMAT_R8G8B8A8_UNORM, false, &mut out)?;
-        self.fill_modes(DXGI_FORMAT_R16G16B16A16_FLOAT, true, &mut out)?;
-        Ok(out)
+    // device name as a C string for the ansi display settings functions.
+    pub(crate) fn c_name(&self) -> Result<CString, DDApiError> {
+        c_display_name(&self.try_name()?)
     }
 
-    /// set a specific mode to display
-    pub fn set_display_mode(&self, mode: &DisplayMode) -> Result<(), DDApiError> {
-        let name = self.name();
-        let name = CString::new(name).unwrap();
-        let mut display_mode = DEVMODEA {
-            ..Default::default()
-        };
-        display_mode.dmSize = size_of::<DEVMODEA>() as _;
-        match mode.orientation {
-            DisplayOrientation::NoRotation | DisplayOrientation::Rotate180 => {
-                display_mode.dmPelsHeight = mode.height;
-                display_mode.dmPelsWidth = mode.width;
-            }
-            DisplayOrientation::Rotate90 | DisplayOrientation::Rotate270 => {
-                display_mode.dmPelsHeight = mode.width;
-                display_mode.dmPelsWidth = mode.height;
+    /// get supported display modes, without duplicates and sorted as described in
+    /// [clean_modes][crate::modes::clean_modes]. see [get_detailed_display_modes][Self::get_detailed_display_modes]
+    /// for scaling, scanline ordering and format of each mode.
+    pub fn get_display_modes(&self) -> Result<Vec<DisplayMode>, DDApiError> {
+        let mut out: Vec<DisplayMode> = Vec::new();
+        for info in self.get_detailed_display_modes()? {
+            // interlaced and progressive variants of a mode look the same here.
+            if !out.contains(&info.mode) {
+                out.push(info.mode);
             }
         }
-        display_mode.dmBitsPerPel = if mode.hdr { 64 } else { 32 };
-        display_mode.dmDisplayFrequency = mode.refresh_num / mode.refresh_den;
-        unsafe {
-            display_mode.Anonymous1.Anonymous2.dmDisplayOrientation = mode.orientation.into();
-        }
+        Ok(out)
+    }
 
-        display_mode.dmFields |= DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY | DM_BITSPERPEL | DM_DISPLAYORIENTATION;
+    /// get supported display modes with the details DXGI reports for them, without duplicates
+    /// and sorted as described in [clean_modes][crate::modes::clean_modes].
+    pub fn get_detailed_display_modes(&self) -> Result<Vec<ModeInfo>, DDApiError> {
+        // modes are listed for the current rotation.
+        let orientation = self.get_current_display_mode()?.orientation;
+        let mut out = Vec::new();
+        self.fill_modes(DXGI_FORMAT_R8G8B8A8_UNORM, false, orientation, &mut out)?;
+        self.fill_modes(DXGI_FORMAT_R16G16B16A16_FLOAT, true, orientation, &mut out)?;
+        Ok(clean_modes(out))
+    }
 
+    /// the supported mode closest to `request`, see [best_mode]. fails with
+    /// [ErrorKind::Unsupported] if the display reports no modes.
+    pub fn best_display_mode(&self, request: &ModeRequest) -> Result<DisplayMode, DDApiError> {
+        let modes = self.get_display_modes()?;
+        best_mode(&modes, request).cloned().ok_or_else(|| {
+            DDApiError::new(ErrorKind::Unsupported).with_message("the display reports no display modes")
+        })
+    }
 
-        let resp = unsafe { ChangeDisplaySettingsExA(PCSTR(name.as_ptr() as _), Some(&display_mode), None, CDS_TYPE(0), None) };
+    /// set a specific mode to display. see [ModeChangeGuard][crate::mode_change::ModeChangeGuard]
+    /// to restore the previous mode automatically.
+    pub fn set_display_mode(&self, mode: &DisplayMode) -> Result<(), DDApiError> {
+        change_display_settings(&self.c_name()?, mode, CDS_TYPE(0))
+    }
 
-        if resp != DISP_CHANGE_SUCCESSFUL {
-            Err(DDApiError::BadParam(format!("failed to change display settings. DISP_CHANGE={}", resp.0)))
-        } else {
-            Ok(())
-        }
+    /// checks whether `mode` can be set on this display, without changing anything.
+    pub fn test_display_mode(&self, mode: &DisplayMode) -> Result<(), DDApiError> {
+        change_display_settings(&self.c_name()?, mode, CDS_TEST)
     }
 
     /// get current [display mode][DisplayMode] of this monitor.
     pub fn get_current_display_mode(&self) -> Result<DisplayMode, DDApiError> {
-        let name = self.name();
-        let name = CString::new(name).unwrap();
+        let name = self.c_name()?;
 
         let mut mode: DEVMODEA = DEVMODEA {
             dmSize: size_of::<DEVMODEA>() as _,
@@ -221,7 +319,8 @@ impl Display {
         };
         let success = unsafe { EnumDisplaySettingsExA(PCSTR(name.as_c_str().as_ptr() as _), ENUM_CURRENT_SETTINGS, &mut mode, ENUM_DISPLAY_SETTINGS_FLAGS(0)) };
         if !success.as_bool() {
-            Err(DDApiError::Unexpected("Failed to retrieve display settings for output".to_string()))
+            Err(DDApiError::unexpected("Failed to retrieve display settings for output")
+                .with_operation("EnumDisplaySettingsExA"))
         } else {
             let mut dm = DisplayMode {
                 width: mode.dmPelsWidth,
@@ -247,8 +346,9 @@ impl Display {
     /// this is not very async friendly use [get_vsync_stream][Display::get_vsync_stream]
     pub fn wait_for_vsync(&self) -> Result<(), DDApiError> {
         let err = unsafe { self.0.WaitForVBlank() };
-        if err.is_err() {
-            return Err(DDApiError::Unexpected(format!("DisplaySyncStream received a sync error. Maybe monitor disconnected? {:?}", err)));
+        if let Err(e) = err {
+            return Err(DDApiError::unexpected("DisplaySyncStream received a sync error. Maybe monitor disconnected?")
+                .with_operation("WaitForVBlank").with_code(e.code()).with_source(e));
         } else {
             Ok(())
         }
@@ -260,28 +360,33 @@ impl Display {
     }
 
     // internal function
-    fn fill_modes(&self, format: DXGI_FORMAT, hdr: bool, mode_list: &mut Vec<DisplayMode>) -> Result<(), DDApiError> {
+    fn fill_modes(&self, format: DXGI_FORMAT, hdr: bool, orientation: DisplayOrientation, mode_list: &mut Vec<ModeInfo>) -> Result<(), DDApiError> {
         let mut num_modes: u32 = 0;
         if let Err(e) = unsafe { self.0.GetDisplayModeList1(format, 0, &mut num_modes, None) } {
-            return Err(DDApiError::Unexpected(format!("{:?}", e)));
+            return Err(DDApiError::from_win("GetDisplayModeList1", e));
         }
 
         let mut modes: Vec<DXGI_MODE_DESC1> = Vec::with_capacity(num_modes as _);
         if let Err(e) = unsafe { self.0.GetDisplayModeList1(format, 0, &mut num_modes, Some(modes.as_mut_ptr())) } {
-            return Err(DDApiError::Unexpected(format!("{:?}", e)));
+            return Err(DDApiError::from_win("GetDisplayModeList1", e));
         }
 
         unsafe { modes.set_len(num_modes as _) };
-        let reserve = max(0, num_modes as usize - mode_list.capacity() + mode_list.len());
-        mode_list.reserve(reserve);
+        mode_list.reserve(modes.len());
         for mode in modes.iter() {
-            mode_list.push(DisplayMode {
-                width: mode.Width,
-                height: mode.Height,
-                refresh_num: mode.RefreshRate.Numerator,
-                refresh_den: mode.RefreshRate.Denominator,
-                hdr,
-                ..Default::default()
+            mode_list.push(ModeInfo {
+                mode: DisplayMode {
+                    width: mode.Width,
+                    height: mode.Height,
+                    orientation,
+                    refresh_num: mode.RefreshRate.Numerator,
+                    refresh_den: mode.RefreshRate.Denominator,
+                    hdr,
+                },
+                scaling: mode.Scaling.into(),
+                scanline_ordering: mode.ScanlineOrdering.into(),
+                stereo: mode.Stereo.as_bool(),
+                format: mode.Format.into(),
             })
         }
         Ok(())
@@ -295,7 +400,7 @@ unsafe impl Sync for Display {}
 
 /// Enum for display orientation
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
 pub enum DisplayOrientation {
     /// Landscape mode
     #[default]
@@ -322,6 +427,17 @@ impl From<DEVMODE_DISPLAY_ORIENTATION> for DisplayOrientation {
     }
 }
 
+impl From<DXGI_MODE_ROTATION> for DisplayOrientation {
+    fn from(i: DXGI_MODE_ROTATION) -> Self {
+        match i {
+            DXGI_MODE_ROTATION_ROTATE90 => Self::Rotate90,
+            DXGI_MODE_ROTATION_ROTATE180 => Self::Rotate180,
+            DXGI_MODE_ROTATION_ROTATE270 => Self::Rotate270,
+            _ => Self::NoRotation,
+        }
+    }
+}
+
 impl From<DisplayOrientation> for DEVMODE_DISPLAY_ORIENTATION {
     fn from(i: DisplayOrientation) -> Self {
         DEVMODE_DISPLAY_ORIENTATION(match i {
@@ -334,8 +450,59 @@ impl From<DisplayOrientation> for DEVMODE_DISPLAY_ORIENTATION {
 }
 
 
+// display name as the C string GDI functions take.
+pub(crate) fn c_display_name(name: &str) -> Result<CString, DDApiError> {
+    CString::new(name).map_err(|e| DDApiError::bad_param(format!("display name contains a nul byte. {}", e)))
+}
+
+// applies or tests `mode` on the display named `name` with ChangeDisplaySettingsEx.
+pub(crate) fn change_display_settings(name: &CStr, mode: &DisplayMode, flags: CDS_TYPE) -> Result<(), DDApiError> {
+    let display_mode = devmode(mode);
+    let resp = unsafe { ChangeDisplaySettingsExA(PCSTR(name.as_ptr() as _), Some(&display_mode), None, flags, None) };
+    disp_change_result(resp)
+}
+
+// DEVMODE setting resolution, refresh rate, bit depth and orientation of `mode`.
+pub(crate) fn devmode(mode: &DisplayMode) -> DEVMODEA {
+    let mut display_mode = DEVMODEA {
+        ..Default::default()
+    };
+    display_mode.dmSize = size_of::<DEVMODEA>() as _;
+    match mode.orientation {
+        DisplayOrientation::NoRotation | DisplayOrientation::Rotate180 => {
+            display_mode.dmPelsHeight = mode.height;
+            display_mode.dmPelsWidth = mode.width;
+        }
+        DisplayOrientation::Rotate90 | DisplayOrientation::Rotate270 => {
+            display_mode.dmPelsHeight = mode.width;
+            display_mode.dmPelsWidth = mode.height;
+        }
+    }
+    display_mode.dmBitsPerPel = if mode.hdr { 64 } else { 32 };
+    display_mode.dmDisplayFrequency = mode.refresh_rate().to_devmode_hz();
+    unsafe {
+        display_mode.Anonymous1.Anonymous2.dmDisplayOrientation = mode.orientation.into();
+    }
+
+    display_mode.dmFields |= DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY | DM_BITSPERPEL | DM_DISPLAYORIENTATION;
+    display_mode
+}
+
+// maps the result of ChangeDisplaySettingsEx to an error.
+pub(crate) fn disp_change_result(resp: DISP_CHANGE) -> Result<(), DDApiError> {
+    if resp == DISP_CHANGE_SUCCESSFUL {
+        Ok(())
+    } else if resp == DISP_CHANGE_RESTART {
+        Err(DDApiError::new(ErrorKind::Unsupported).with_message("the display mode can only be set after a restart")
+            .with_operation("ChangeDisplaySettingsExA"))
+    } else {
+        Err(DDApiError::bad_param(format!("failed to change display settings. DISP_CHANGE={}", resp.0))
+            .with_operation("ChangeDisplaySettingsExA"))
+    }
+}
+
 #[repr(C)]
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, Eq, PartialEq)]
 /**
 DisplayMode represents one display mode of monitor. It contains resolution, refresh-rate and orientation.
 The resolution contains width and height of display for their default orientation.
@@ -362,14 +529,54 @@ pub struct DisplayMode {
     pub hdr: bool,
 }
 
+impl DisplayMode {
+    /// refresh rate of this mode.
+    pub fn refresh_rate(&self) -> RefreshRate {
+        RefreshRate::new(self.refresh_num, self.refresh_den)
+    }
+
+    /// width and height of this mode reduced to the smallest whole numbers, e.g. `(16, 9)`.
+    pub fn aspect_ratio(&self) -> (u32, u32) {
+        let divisor = gcd(self.width, self.height).max(1);
+        (self.width / divisor, self.height / divisor)
+    }
+
+    /// returns true if this mode has the aspect ratio of `width` x `height`, within 1%. the
+    /// tolerance lets modes like 1366 x 768 count as 16:9.
+    pub fn has_aspect_of(&self, width: u32, height: u32) -> bool {
+        if self.height == 0 || height == 0 {
+            return false;
+        }
+        let ours = self.width as f64 / self.height as f64;
+        let theirs = width as f64 / height as f64;
+        (ours / theirs - 1.0).abs() <= 0.01
+    }
+
+    /// time between two refreshes. returns `None` if the refresh rate is unknown.
+    pub fn refresh_period(&self) -> Option<Duration> {
+        if self.refresh_num == 0 {
+            return None;
+        }
+        let den = self.refresh_den.max(1) as u64;
+        Some(Duration::from_nanos(den * 1_000_000_000 / self.refresh_num as u64))
+    }
+}
+
 
 /// used to receive sync signal with vsync. this is a async stream.
 /// it receives signal after every frame.
 ///
-/// it implements stream api to use in async. The function creates a separate thread to wait
-/// for sync events because they are not implemented in async way in the windows os.
+/// it implements stream api to use in async. Waiting for vsync is not implemented in async way in
+/// the windows os, so a separate thread waits for sync events. all streams of the same display
+/// share that thread. it doesn't depend on any particular async runtime.
 ///
-/// the new thread auto cleans up item goes out of scope.
+/// the thread is signalled and joined when the last stream of the display is dropped or
+/// [closed][DisplayVSyncStream::close]. if the thread is stuck waiting for vblank (for example
+/// when the monitor is off), it's detached after [VSYNC_JOIN_TIMEOUT][crate::vsync::VSYNC_JOIN_TIMEOUT].
+///
+/// when `WaitForVBlank` fails, the stream keeps ticking from a timer at the display's refresh rate
+/// until vblank is available again. see [FallbackVsync][crate::vsync::FallbackVsync]. streams
+/// driven by any other [VsyncSource] can be created with [from_source][DisplayVSyncStream::from_source].
 ///
 /// # Example:
 /// ```
@@ -379,8 +586,8 @@ pub struct DisplayMode {
 /// }
 /// ```
 pub struct DisplayVSyncStream {
-    sync_rx: tokio::sync::mpsc::Receiver<Result<(), DDApiError>>,
-    thread_handle: Option<Box<JoinHandle<()>>>,
+    sync_rx: Option<mpsc::Receiver<Result<(), DDApiError>>>,
+    broadcaster: Option<Arc<VSyncBroadcaster>>,
 }
 
 unsafe impl Send for DisplayVSyncStream {}
@@ -390,33 +597,45 @@ unsafe impl Sync for DisplayVSyncStream {}
 impl DisplayVSyncStream {
     /// generates a new sync stream for a given display.
     pub fn new(output: Display) -> Self {
-        let (sync_tx, sync_rx) = tokio::sync::mpsc::channel::<Result<(), DDApiError>>(1);
-        // the thread auto stops when this object goes out of scope.
-        let thread_handle = spawn(move || {
-            let output = output;
-            unsafe { let _ = DXGIDisableVBlankVirtualization(); }
-            loop {
-                let mut out = Ok(());
-                let res = unsafe { output.0.WaitForVBlank() };
-
-                // extra sleep to ensure the image is processed for desktop duplication.
-                // removing this sleep here. will add this back in duplication function instead
-                sleep(Duration::from_millis(4));
-
-                if let Err(e) = res {
-                    out = Err(DDApiError::Unexpected(format!("{:?}", e)));
-                }
-                let err = sync_tx.blocking_send(out);
-                if err.is_err() {
-                    trace!("exiting display sync wait thread");
-                    return;
-                }
-            }
-        });
+        match vsync::for_display(&output) {
+            Ok(broadcaster) => Self::subscribed(broadcaster),
+            Err(e) => Self::failed(e),
+        }
+    }
+
+    /// generates a new sync stream driven by `source` on its own thread. the stream ends when the
+    /// source ends.
+    pub fn from_source<S>(source: S) -> Self
+        where S: VsyncSource + 'static {
+        match VSyncBroadcaster::spawn(source) {
+            Ok(broadcaster) => Self::subscribed(Arc::new(broadcaster)),
+            Err(e) => Self::failed(e),
+        }
+    }
+
+    /// stop receiving vsync signals. the stream ends after this call.
+    ///
+    /// if this was the last stream of the display, its vsync thread is signalled and joined.
+    pub fn close(&mut self) {
+        self.sync_rx = None;
+        self.broadcaster = None;
+    }
 
+    fn subscribed(broadcaster: Arc<VSyncBroadcaster>) -> Self {
         Self {
-            sync_rx,
-            thread_handle: Some(Box::new(thread_handle)),
+            sync_rx: Some(broadcaster.subscribe()),
+            broadcaster: Some(broadcaster),
+        }
+    }
+
+    fn failed(e: DDApiError) -> Self {
+        error!("failed to start vsync thread. {:?}", e);
+        // the stream yields the error once and ends.
+        let (mut tx, rx) = mpsc::channel(1);
+        let _ = tx.try_send(Err(e));
+        Self {
+            sync_rx: Some(rx),
+            broadcaster: None,
         }
     }
 }
@@ -425,6 +644,9 @@ impl Stream for DisplayVSyncStream {
     type Item = Result<(), DDApiError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.sync_rx.poll_recv(cx)
+        match self.sync_rx.as_mut() {
+            Some(rx) => rx.poll_next_unpin(cx),
+            None => Poll::Ready(None),
+        }
     }
-}
\ No newline at end of file
+}
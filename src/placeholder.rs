@@ -0,0 +1,500 @@
+//! Generates placeholder frames shown while the desktop can't be captured.
+//!
+//! During UAC prompts or on the lock screen, windows denies access to the desktop and
+//! [DesktopDuplicationApi][crate::DesktopDuplicationApi] can't acquire frames. When
+//! [DuplicationApiOptions::placeholder][crate::DuplicationApiOptions::placeholder] is set, the api
+//! returns a generated frame of the current display size instead of an error and marks it as
+//! [synthetic][crate::FrameInfo::synthetic].
+//!
+//! Placeholders are rendered on the cpu by [render_placeholder] into an
+//! [ABGR8UNorm][ColorFormat::ABGR8UNorm] [CpuFrame] and converted by [convert_placeholder] to the
+//! format of the duplicated frames, so the stream doesn't switch formats on HDR or 10 bit
+//! desktops. The optional message is drawn with a tiny built in bitmap font that covers ascii
+//! letters, digits and basic punctuation.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::DuplicationApiOptions;
+//! use win_desktop_duplication::placeholder::{PlaceholderOptions, PlaceholderStyle};
+//!
+//! dupl.configure(DuplicationApiOptions {
+//!     placeholder: Some(PlaceholderOptions {
+//!         style: PlaceholderStyle::BlurredLastFrame { radius: 12 },
+//!         message: Some("secure desktop is active".to_owned()),
+//!         ..Default::default()
+//!     }),
+//!     ..Default::default()
+//! });
+//! ```
+
+use std::sync::Arc;
+
+use crate::errors::{DDApiError, ErrorKind};
+use crate::Result;
+use crate::tex_reader::CpuFrame;
+use crate::texture::ColorFormat;
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::errors::ErrorKind;
+    use crate::placeholder::{convert_placeholder, FALLBACK_COLOR, PlaceholderOptions, PlaceholderStyle, render_placeholder};
+    use crate::tex_reader::CpuFrame;
+    use crate::texture::ColorFormat;
+
+    fn pixel(frame: &CpuFrame, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * frame.width + x) * 4) as usize;
+        frame.data[i..i + 4].try_into().unwrap()
+    }
+
+    fn solid(color: [u8; 4]) -> PlaceholderOptions {
+        PlaceholderOptions {
+            style: PlaceholderStyle::SolidColor(color),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_solid_color() {
+        let frame = render_placeholder(&solid([10, 20, 30, 255]), 8, 4, None);
+        assert_eq!((frame.width, frame.height, frame.format), (8, 4, ColorFormat::ABGR8UNorm));
+        assert_eq!(frame.data.len(), 8 * 4 * 4);
+        // colors are given as rgba and stored as bgra.
+        assert!(frame.data.chunks(4).all(|p| p == [30, 20, 10, 255]));
+    }
+
+    #[test]
+    fn test_convert_placeholder() {
+        let frame = render_placeholder(&solid([255, 0, 188, 255]), 2, 2, None);
+        assert_eq!(convert_placeholder(frame.clone(), ColorFormat::ABGR8UNorm).unwrap().data, frame.data);
+
+        let rgba = convert_placeholder(frame.clone(), ColorFormat::ARGB8UNorm).unwrap();
+        assert_eq!(&rgba.data[..4], &[255, 0, 188, 255]);
+
+        let packed = convert_placeholder(frame.clone(), ColorFormat::ARGB10UNorm).unwrap();
+        let px = u32::from_le_bytes(packed.data[..4].try_into().unwrap());
+        assert_eq!((px & 0x3FF, px >> 10 & 0x3FF, px >> 20 & 0x3FF, px >> 30), (1023, 0, 754, 3));
+
+        // linear half floats: 1.0, 0.0, ~0.5.
+        let float = convert_placeholder(frame.clone(), ColorFormat::ARGB16Float).unwrap();
+        assert_eq!((float.format, float.data.len()), (ColorFormat::ARGB16Float, 2 * 2 * 8));
+        let half: Vec<_> = float.data[..8].chunks(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        assert_eq!((half[0], half[1], half[3]), (0x3C00, 0, 0x3C00));
+        assert!((0x37F0..0x3810).contains(&half[2]));
+
+        let err = convert_placeholder(frame, ColorFormat::NV12).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_blurred_last_frame() {
+        // left half black, right half white, at half the output size.
+        let mut last = CpuFrame { width: 8, height: 4, format: ColorFormat::ABGR8UNorm, data: [0, 0, 0, 255].repeat(8 * 4) };
+        for y in 0..4 {
+            for x in 4..8 {
+                let i = ((y * 8 + x) * 4) as usize;
+                last.data[i..i + 4].copy_from_slice(&[255, 255, 255, 255]);
+            }
+        }
+        let options = PlaceholderOptions {
+            style: PlaceholderStyle::BlurredLastFrame { radius: 2 },
+            dim: 0.0,
+            ..Default::default()
+        };
+        let frame = render_placeholder(&options, 16, 8, Some(&last));
+        assert_eq!(frame.data.len(), 16 * 8 * 4);
+        assert_eq!(pixel(&frame, 0, 4), [0, 0, 0, 255]);
+        assert_eq!(pixel(&frame, 15, 4), [255, 255, 255, 255]);
+        // the hard edge in the middle is smoothed out.
+        let edge = pixel(&frame, 7, 4)[0];
+        assert!(edge > 0 && edge < 255, "{}", edge);
+        assert!(pixel(&frame, 6, 4)[0] < edge);
+    }
+
+    #[test]
+    fn test_unsupported_last_frame_falls_back() {
+        let last = CpuFrame { width: 2, height: 2, format: ColorFormat::NV12, data: vec![0; 6] };
+        let options = PlaceholderOptions {
+            style: PlaceholderStyle::BlurredLastFrame { radius: 2 },
+            ..Default::default()
+        };
+        for last in [None, Some(&last)] {
+            let frame = render_placeholder(&options, 4, 4, last);
+            let [r, g, b, a] = FALLBACK_COLOR;
+            assert!(frame.data.chunks(4).all(|p| p == [b, g, r, a]));
+        }
+    }
+
+    #[test]
+    fn test_image_is_scaled_and_swizzled() {
+        // 1x2 rgba image, red on top of blue.
+        let image = CpuFrame { width: 1, height: 2, format: ColorFormat::ARGB8UNorm, data: vec![255, 0, 0, 255, 0, 0, 255, 255] };
+        let frame = render_placeholder(&PlaceholderOptions {
+            style: PlaceholderStyle::Image(Arc::new(image)),
+            dim: 0.0,
+            ..Default::default()
+        }, 4, 4, None);
+        assert_eq!(pixel(&frame, 3, 1), [0, 0, 255, 255]);
+        assert_eq!(pixel(&frame, 0, 2), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_message_is_drawn() {
+        let options = PlaceholderOptions {
+            message: Some("Locked".to_owned()),
+            text_color: [255, 255, 255, 255],
+            ..solid([0, 0, 0, 255])
+        };
+        let frame = render_placeholder(&options, 120, 40, None);
+        let lit: Vec<_> = (0..40).flat_map(|y| (0..120).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(&frame, x, y) == [255, 255, 255, 255]).collect();
+        assert!(!lit.is_empty());
+        // the text is centered.
+        let (min_x, max_x) = (lit.iter().map(|p| p.0).min().unwrap(), lit.iter().map(|p| p.0).max().unwrap());
+        assert!((min_x as i32 - (119 - max_x) as i32).abs() <= 2, "{} {}", min_x, max_x);
+        // the corners are untouched.
+        assert_eq!(pixel(&frame, 0, 0), [0, 0, 0, 255]);
+
+        // text that doesn't fit is clipped instead of panicking.
+        let long = PlaceholderOptions { message: Some("x".repeat(100) + "\n?"), ..options };
+        render_placeholder(&long, 10, 5, None);
+    }
+}
+
+/// color used when the configured style can't be rendered, e.g. there is no last frame to blur.
+/// given as rgba.
+pub const FALLBACK_COLOR: [u8; 4] = [24, 24, 24, 255];
+
+/// background of a placeholder frame.
+#[derive(Clone, Debug)]
+pub enum PlaceholderStyle {
+    /// fill the frame with a single rgba color.
+    SolidColor([u8; 4]),
+
+    /// a blurred copy of the last captured frame. falls back to [FALLBACK_COLOR] if there is no
+    /// last frame or it isn't an 8 bit rgb format.
+    BlurredLastFrame {
+        /// blur radius in pixels of the output frame.
+        radius: u32,
+    },
+
+    /// a user supplied image stretched to the frame size. it must be an
+    /// [ARGB8UNorm][ColorFormat::ARGB8UNorm] or [ABGR8UNorm][ColorFormat::ABGR8UNorm] frame.
+    Image(Arc<CpuFrame>),
+}
+
+/// Settings for placeholder frames.
+#[derive(Clone, Debug)]
+pub struct PlaceholderOptions {
+    /// background of the frame. defaults to a blurred last frame.
+    pub style: PlaceholderStyle,
+
+    /// message drawn at the center of the frame. `\n` starts a new line.
+    pub message: Option<String>,
+
+    /// rgba color of the message. defaults to white.
+    pub text_color: [u8; 4],
+
+    /// how much blurred and image backgrounds are darkened, from 0 (not at all) to 1 (black).
+    /// this keeps the message readable on bright backgrounds. defaults to 0.4.
+    pub dim: f32,
+}
+
+impl Default for PlaceholderOptions {
+    fn default() -> Self {
+        Self {
+            style: PlaceholderStyle::BlurredLastFrame { radius: 16 },
+            message: None,
+            text_color: [255, 255, 255, 255],
+            dim: 0.4,
+        }
+    }
+}
+
+/// render a placeholder frame of given size. `last_frame` is used by
+/// [PlaceholderStyle::BlurredLastFrame].
+///
+/// the result is always an [ABGR8UNorm][ColorFormat::ABGR8UNorm] frame, which matches the
+/// `DXGI_FORMAT_B8G8R8A8_UNORM` textures of desktop duplication on SDR desktops. see
+/// [convert_placeholder] for other formats.
+pub fn render_placeholder(options: &PlaceholderOptions, width: u32, height: u32, last_frame: Option<&CpuFrame>) -> CpuFrame {
+    let mut frame = CpuFrame {
+        width,
+        height,
+        format: ColorFormat::ABGR8UNorm,
+        data: vec![0; width as usize * height as usize * 4],
+    };
+    let drawn = match &options.style {
+        PlaceholderStyle::SolidColor(color) => {
+            fill(&mut frame, *color);
+            true
+        }
+        PlaceholderStyle::BlurredLastFrame { radius } => match last_frame.filter(|f| is_rgba8(f)) {
+            Some(last) => {
+                stretch(last, &mut frame);
+                box_blur(&mut frame, *radius);
+                darken(&mut frame, options.dim);
+                true
+            }
+            None => false,
+        },
+        PlaceholderStyle::Image(image) if is_rgba8(image) => {
+            stretch(image, &mut frame);
+            darken(&mut frame, options.dim);
+            true
+        }
+        PlaceholderStyle::Image(_) => false,
+    };
+    if !drawn {
+        fill(&mut frame, FALLBACK_COLOR);
+    }
+    if let Some(message) = &options.message {
+        draw_text(&mut frame, message, options.text_color);
+    }
+    frame
+}
+
+/// convert a frame from [render_placeholder] to `format`, the format of the duplicated frames.
+///
+/// [ARGB16Float][ColorFormat::ARGB16Float] frames hold linear scRGB, as duplication returns on
+/// HDR desktops. other formats than the three duplication uses fail with [ErrorKind::Unsupported].
+pub fn convert_placeholder(frame: CpuFrame, format: ColorFormat) -> Result<CpuFrame> {
+    let bytes_per_pixel = match format {
+        ColorFormat::ABGR8UNorm => return Ok(frame),
+        ColorFormat::ARGB8UNorm | ColorFormat::ARGB10UNorm => 4,
+        ColorFormat::ARGB16Float => 8,
+        _ => return Err(DDApiError::new(ErrorKind::Unsupported)
+            .with_message(format!("can't generate placeholder frames of format {:?}", format))),
+    };
+    let mut data = Vec::with_capacity(frame.data.len() / 4 * bytes_per_pixel);
+    for px in frame.data.chunks_exact(4) {
+        let (b, g, r, a) = (px[0], px[1], px[2], px[3]);
+        match format {
+            ColorFormat::ARGB8UNorm => data.extend_from_slice(&[r, g, b, a]),
+            ColorFormat::ARGB10UNorm => {
+                let ten = |c: u8| (c as u32) << 2 | (c as u32) >> 6;
+                let packed = ten(r) | ten(g) << 10 | ten(b) << 20 | (a as u32 >> 6) << 30;
+                data.extend_from_slice(&packed.to_le_bytes());
+            }
+            _ => {
+                for value in [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a as f32 / 255.0] {
+                    data.extend_from_slice(&half_bits(value).to_le_bytes());
+                }
+            }
+        }
+    }
+    Ok(CpuFrame { width: frame.width, height: frame.height, format, data })
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+// IEEE half float bits of `value` in `0.0..=1.0`, rounded.
+fn half_bits(value: f32) -> u16 {
+    let value = value.clamp(0.0, 1.0);
+    // below the smallest normal half float.
+    if value < 6.103_515_6e-5 {
+        return (value * 16_777_216.0).round() as u16;
+    }
+    let bits = value.to_bits() + 0x1000;
+    ((((bits >> 23) & 0xFF) - 112) << 10 | (bits >> 13) & 0x3FF) as u16
+}
+
+fn is_rgba8(frame: &CpuFrame) -> bool {
+    matches!(frame.format, ColorFormat::ABGR8UNorm | ColorFormat::ARGB8UNorm)
+        && frame.width > 0 && frame.height > 0
+        && frame.data.len() >= frame.width as usize * frame.height as usize * 4
+}
+
+// rgba color to the byte order of ABGR8UNorm.
+fn to_bgra([r, g, b, a]: [u8; 4]) -> [u8; 4] {
+    [b, g, r, a]
+}
+
+fn fill(frame: &mut CpuFrame, color: [u8; 4]) {
+    let color = to_bgra(color);
+    for px in frame.data.chunks_exact_mut(4) {
+        px.copy_from_slice(&color);
+    }
+}
+
+// nearest neighbour scale of `src` into `dst`, converting to ABGR8UNorm.
+fn stretch(src: &CpuFrame, dst: &mut CpuFrame) {
+    let swap = src.format == ColorFormat::ARGB8UNorm;
+    let (sw, sh) = (src.width as usize, src.height as usize);
+    let (dw, dh) = (dst.width as usize, dst.height as usize);
+    for y in 0..dh {
+        let sy = y * sh / dh;
+        for x in 0..dw {
+            let sx = x * sw / dw;
+            let s = (sy * sw + sx) * 4;
+            let d = (y * dw + x) * 4;
+            let px = &src.data[s..s + 4];
+            dst.data[d..d + 4].copy_from_slice(&if swap { [px[2], px[1], px[0], px[3]] } else { [px[0], px[1], px[2], px[3]] });
+        }
+    }
+}
+
+// two passes of a separable box blur, which is close enough to a gaussian for a placeholder.
+fn box_blur(frame: &mut CpuFrame, radius: u32) {
+    if radius == 0 {
+        return;
+    }
+    let (w, h) = (frame.width as usize, frame.height as usize);
+    let mut line = Vec::new();
+    for _ in 0..2 {
+        for y in 0..h {
+            blur_line(&mut frame.data, y * w * 4, 4, w, radius as usize, &mut line);
+        }
+        for x in 0..w {
+            blur_line(&mut frame.data, x * 4, w * 4, h, radius as usize, &mut line);
+        }
+    }
+}
+
+// blur `len` pixels starting at byte `start`, `stride` bytes apart, with a running sum.
+fn blur_line(data: &mut [u8], start: usize, stride: usize, len: usize, radius: usize, line: &mut Vec<[u8; 4]>) {
+    line.clear();
+    line.extend((0..len).map(|i| {
+        let p = start + i * stride;
+        [data[p], data[p + 1], data[p + 2], data[p + 3]]
+    }));
+    let at = |i: isize| line[i.clamp(0, len as isize - 1) as usize];
+    let window = (2 * radius + 1) as u32;
+    let mut sum = [0u32; 4];
+    for i in -(radius as isize)..=(radius as isize) {
+        let px = at(i);
+        (0..4).for_each(|c| sum[c] += px[c] as u32);
+    }
+    for i in 0..len {
+        let p = start + i * stride;
+        (0..4).for_each(|c| data[p + c] = (sum[c] / window) as u8);
+        let (add, sub) = (at((i + radius + 1) as isize), at(i as isize - radius as isize));
+        (0..4).for_each(|c| sum[c] = sum[c] + add[c] as u32 - sub[c] as u32);
+    }
+}
+
+fn darken(frame: &mut CpuFrame, dim: f32) {
+    let keep = ((1.0 - dim.clamp(0.0, 1.0)) * 256.0) as u32;
+    if keep >= 256 {
+        return;
+    }
+    for px in frame.data.chunks_exact_mut(4) {
+        (0..3).for_each(|c| px[c] = (px[c] as u32 * keep / 256) as u8);
+    }
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+// horizontal and vertical space taken by a glyph including spacing.
+const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: u32 = GLYPH_HEIGHT + 3;
+
+// draw `text` centered with a drop shadow, scaled to the frame size.
+fn draw_text(frame: &mut CpuFrame, text: &str, color: [u8; 4]) {
+    let lines: Vec<&str> = text.lines().collect();
+    let columns = lines.iter().map(|l| l.chars().count() as u32).max().unwrap_or(0);
+    if columns == 0 {
+        return;
+    }
+    let rows = lines.len() as u32;
+    // about 1/30th of the frame height per line, but never wider than the frame.
+    let mut scale = (frame.height / (CELL_HEIGHT * 30).max(1)).clamp(1, 8);
+    while scale > 1 && columns * CELL_WIDTH * scale > frame.width * 9 / 10 {
+        scale -= 1;
+    }
+    let block_height = (rows * CELL_HEIGHT - 3) * scale;
+    let top = (frame.height as i64 - block_height as i64) / 2;
+    let shadow = to_bgra([0, 0, 0, color[3]]);
+    let color = to_bgra(color);
+
+    for (row, line) in lines.iter().enumerate() {
+        let width = (line.chars().count() as u32 * CELL_WIDTH).saturating_sub(1) * scale;
+        let left = (frame.width as i64 - width as i64) / 2;
+        let y = top + (row as u32 * CELL_HEIGHT * scale) as i64;
+        for (col, ch) in line.chars().enumerate() {
+            let x = left + (col as u32 * CELL_WIDTH * scale) as i64;
+            let offset = scale.div_ceil(2) as i64;
+            draw_glyph(frame, glyph(ch), x + offset, y + offset, scale, shadow);
+            draw_glyph(frame, glyph(ch), x, y, scale, color);
+        }
+    }
+}
+
+fn draw_glyph(frame: &mut CpuFrame, rows: [u8; 7], x: i64, y: i64, scale: u32, color: [u8; 4]) {
+    for (gy, bits) in rows.iter().enumerate() {
+        for gx in 0..GLYPH_WIDTH {
+            if bits & (1 << (GLYPH_WIDTH - 1 - gx)) == 0 {
+                continue;
+            }
+            for dy in 0..scale as i64 {
+                for dx in 0..scale as i64 {
+                    let px = x + (gx * scale) as i64 + dx;
+                    let py = y + gy as i64 * scale as i64 + dy;
+                    if px < 0 || py < 0 || px >= frame.width as i64 || py >= frame.height as i64 {
+                        continue;
+                    }
+                    let i = ((py as usize * frame.width as usize) + px as usize) * 4;
+                    frame.data[i..i + 4].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+}
+
+// 5x7 bitmap of a character, one byte per row with the leftmost pixel in bit 4.
+fn glyph(ch: char) -> [u8; 7] {
+    match ch.to_ascii_uppercase() {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '\'' => [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        // '?' and anything the font doesn't know.
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
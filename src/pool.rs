@@ -0,0 +1,276 @@
+//! Provides [FramePool], a fixed size pool of reusable frame buffers with lease/return semantics.
+//!
+//! A [PooledFrame] is handed out by the pool and returns its slot when dropped, so a consumer can
+//! keep working on a frame while the next one is written to a different buffer. The pool is
+//! generic, it backs GPU [textures][crate::texture::Texture] in
+//! [DesktopDuplicationApi][crate::DesktopDuplicationApi] as well as
+//! [CPU frames][crate::tex_reader::CpuFrame].
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::pool::FramePool;
+//! use win_desktop_duplication::tex_reader::CpuFrame;
+//!
+//! let pool = FramePool::new(2);
+//! let mut frame = pool.lease_with(|| Ok(CpuFrame::default()))?;
+//! reader.read_frame(&tex, &mut frame)?;
+//! // frame returns to the pool when dropped
+//! ```
+
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::Result;
+use crate::errors::ErrorKind;
+use crate::texture::Texture;
+
+#[cfg(test)]
+mod test {
+    use crate::errors::ErrorKind;
+    use crate::pool::FramePool;
+    use crate::tex_reader::CpuFrame;
+
+    #[test]
+    fn test_lease_and_return() {
+        let pool = FramePool::new(2);
+        let mut created = 0;
+        let mut create = || {
+            created += 1;
+            Ok(vec![0u8; 4])
+        };
+
+        let mut a = pool.lease_with(&mut create).unwrap();
+        a[0] = 1;
+        let b = pool.lease_with(&mut create).unwrap();
+        assert_eq!(pool.leased(), 2);
+        assert!(matches!(pool.lease_with(&mut create), Err(e) if e.kind() == ErrorKind::PoolExhausted));
+        assert!(pool.try_lease().is_none());
+
+        drop(a);
+        assert_eq!(pool.leased(), 1);
+        // the returned buffer is reused instead of creating a new one.
+        let a = pool.lease_with(&mut create).unwrap();
+        assert_eq!(a[0], 1);
+        drop((a, b));
+        assert_eq!(created, 2);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn test_clear_discards_leased() {
+        let pool = FramePool::new(2);
+        let a = pool.lease_with(|| Ok(CpuFrame { width: 1, ..Default::default() })).unwrap();
+        let b = pool.lease_with(|| Ok(CpuFrame { width: 1, ..Default::default() })).unwrap();
+        drop(b);
+        pool.clear();
+        // a was leased before clear so it's not returned.
+        drop(a);
+        assert_eq!(pool.leased(), 0);
+        assert!(pool.try_lease().is_none());
+        let c = pool.lease_with(|| Ok(CpuFrame { width: 2, ..Default::default() })).unwrap();
+        assert_eq!(c.width, 2);
+    }
+
+    #[test]
+    fn test_resize_and_detach() {
+        let pool = FramePool::new(1);
+        let a = pool.lease_with(|| Ok(1u32)).unwrap();
+        pool.resize(2);
+        let b = pool.lease_with(|| Ok(2u32)).unwrap();
+        assert_eq!(b.detach(), 2);
+        assert_eq!(pool.leased(), 1);
+        pool.resize(0);
+        // capacity is at least 1
+        assert_eq!(pool.capacity(), 1);
+        drop(a);
+        assert_eq!(*pool.try_lease().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_shrink_drops_returned() {
+        let pool = FramePool::new(3);
+        let frames: Vec<_> = (0..3).map(|i| pool.lease_with(|| Ok(i)).unwrap()).collect();
+        pool.resize(1);
+        drop(frames);
+        assert_eq!(pool.available(), 1);
+        assert!(pool.try_lease().is_some());
+    }
+}
+
+/// texture leased from the frame pool of [DesktopDuplicationApi][crate::DesktopDuplicationApi].
+pub type PooledTexture = PooledFrame<Texture>;
+
+#[derive(Debug)]
+struct PoolState<T> {
+    free: Vec<T>,
+    leased: usize,
+    capacity: usize,
+    // bumped on clear. frames leased from an older generation are dropped instead of returned.
+    generation: u64,
+}
+
+/// Fixed size pool of reusable frame buffers. cloning the pool returns another handle to the same
+/// pool.
+pub struct FramePool<T> {
+    state: Arc<Mutex<PoolState<T>>>,
+}
+
+impl<T> Clone for FramePool<T> {
+    fn clone(&self) -> Self {
+        Self { state: self.state.clone() }
+    }
+}
+
+impl<T> FramePool<T> {
+    /// create a new pool holding at most `capacity` buffers (at least 1). buffers are created
+    /// lazily on [lease_with][FramePool::lease_with].
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(PoolState {
+                free: Vec::with_capacity(capacity),
+                leased: 0,
+                capacity: capacity.max(1),
+                generation: 0,
+            })),
+        }
+    }
+
+    /// lease a free buffer, or create one with `create` if the pool is not full yet.
+    ///
+    /// fails with [ErrorKind::PoolExhausted] if all buffers are leased.
+    pub fn lease_with<F>(&self, create: F) -> Result<PooledFrame<T>>
+        where F: FnOnce() -> Result<T> {
+        let generation = {
+            let mut state = self.lock();
+            if let Some(item) = state.free.pop() {
+                state.leased += 1;
+                return Ok(self.wrap(item, state.generation));
+            }
+            if state.leased >= state.capacity {
+                return Err(ErrorKind::PoolExhausted.into());
+            }
+            // reserve the slot so the lock isn't held while creating the buffer.
+            state.leased += 1;
+            state.generation
+        };
+
+        match create() {
+            Ok(item) => Ok(self.wrap(item, generation)),
+            Err(e) => {
+                self.lock().leased -= 1;
+                Err(e)
+            }
+        }
+    }
+
+    /// lease a free buffer if there is one. this never creates new buffers.
+    pub fn try_lease(&self) -> Option<PooledFrame<T>> {
+        let mut state = self.lock();
+        let item = state.free.pop()?;
+        state.leased += 1;
+        Some(self.wrap(item, state.generation))
+    }
+
+    /// drop all buffers. buffers that are currently leased are dropped when they are returned.
+    /// use this when the shape of the frames changes.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.free.clear();
+        state.leased = 0;
+        state.generation += 1;
+    }
+
+    /// change the maximum number of buffers (at least 1). extra free buffers are dropped right
+    /// away, extra leased buffers are dropped when they are returned.
+    pub fn resize(&self, capacity: usize) {
+        let mut state = self.lock();
+        state.capacity = capacity.max(1);
+        let keep = state.capacity.saturating_sub(state.leased);
+        state.free.truncate(keep);
+    }
+
+    /// maximum number of buffers in this pool.
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity
+    }
+
+    /// number of buffers that are currently leased.
+    pub fn leased(&self) -> usize {
+        self.lock().leased
+    }
+
+    /// number of buffers that can be leased right now, including ones that are not created yet.
+    pub fn available(&self) -> usize {
+        let state = self.lock();
+        state.capacity.saturating_sub(state.leased)
+    }
+
+    fn wrap(&self, item: T, generation: u64) -> PooledFrame<T> {
+        PooledFrame {
+            item: Some(item),
+            generation,
+            pool: self.state.clone(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Frame buffer leased from a [FramePool]. it dereferences to the buffer and returns it to the
+/// pool when dropped.
+pub struct PooledFrame<T> {
+    item: Option<T>,
+    generation: u64,
+    pool: Arc<Mutex<PoolState<T>>>,
+}
+
+impl<T> PooledFrame<T> {
+    /// take the buffer out of the pool. its slot becomes available for a new buffer.
+    pub fn detach(mut self) -> T {
+        let mut state = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        if state.generation == self.generation {
+            state.leased -= 1;
+        }
+        drop(state);
+        self.item.take().unwrap()
+    }
+}
+
+impl<T> Deref for PooledFrame<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for PooledFrame<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().unwrap()
+    }
+}
+
+impl<T> Drop for PooledFrame<T> {
+    fn drop(&mut self) {
+        let Some(item) = self.item.take() else {
+            return;
+        };
+        let mut state = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        if state.generation != self.generation {
+            return;
+        }
+        state.leased -= 1;
+        if state.free.len() + state.leased < state.capacity {
+            state.free.push(item);
+        }
+    }
+}
+
+impl<T: Debug> Debug for PooledFrame<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PooledFrame").field(&self.item).finish()
+    }
+}
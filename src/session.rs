@@ -0,0 +1,333 @@
+//! Explicit state machine of a desktop duplication session.
+//!
+//! [DesktopDuplicationApi][crate::DesktopDuplicationApi] recovers from desktop switches and mode
+//! changes on its own, but callers used to only see sporadic [AccessLost][ErrorKind::AccessLost]
+//! / [AccessDenied][ErrorKind::AccessDenied] errors. The api now tracks a [SessionState] and
+//! reports every change as a [SessionTransition] to [subscribers][SessionEvents].
+//!
+//! | from \ event      | FrameAcquired | AccessLost | AccessDenied  | Disconnected | Failed |
+//! |-------------------|---------------|------------|---------------|--------------|--------|
+//! | any but `Failed`  | Active        | Lost       | SecureDesktop | Disconnected | Failed |
+//! | `Failed`          | Failed        | Failed     | Failed        | Failed       | Failed |
+//!
+//! The transition logic doesn't touch DXGI at all. The api only feeds [SessionEvent]s into a
+//! [SessionStateMachine].
+//!
+//! # Example
+//! ```
+//! use futures::StreamExt;
+//!
+//! let mut events = dupl.subscribe_session_events();
+//! while let Some(transition) = events.next().await {
+//!     println!("{:?} -> {:?} because of {:?}", transition.from, transition.to, transition.cause);
+//! }
+//! ```
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::{Stream, StreamExt};
+use futures::channel::mpsc;
+use log::{debug, warn};
+
+use crate::errors::{DDApiError, ErrorKind};
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    use crate::errors::{DDApiError, ErrorKind};
+    use crate::session::{SessionEvent, SessionState, SessionStateMachine};
+
+    const STATES: [SessionState; 5] = [SessionState::Active, SessionState::Lost,
+        SessionState::SecureDesktop, SessionState::Disconnected, SessionState::Failed];
+
+    fn all_events() -> Vec<SessionEvent> {
+        vec![SessionEvent::FrameAcquired, SessionEvent::AccessLost, SessionEvent::AccessDenied,
+             SessionEvent::Disconnected, SessionEvent::Failed(DDApiError::unexpected("boom"))]
+    }
+
+    #[test]
+    fn test_transition_table() {
+        for state in STATES {
+            for event in all_events() {
+                let expected = match (state, &event) {
+                    (SessionState::Failed, _) => SessionState::Failed,
+                    (_, SessionEvent::FrameAcquired) => SessionState::Active,
+                    (_, SessionEvent::AccessLost) => SessionState::Lost,
+                    (_, SessionEvent::AccessDenied) => SessionState::SecureDesktop,
+                    (_, SessionEvent::Disconnected) => SessionState::Disconnected,
+                    (_, SessionEvent::Failed(_)) => SessionState::Failed,
+                };
+                assert_eq!(state.next(&event), expected, "{:?} on {:?}", state, event);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_error() {
+        assert!(matches!(SessionEvent::from_error(&ErrorKind::AccessLost.into()), Some(SessionEvent::AccessLost)));
+        assert!(matches!(SessionEvent::from_error(&ErrorKind::AccessDenied.into()), Some(SessionEvent::AccessDenied)));
+        assert!(matches!(SessionEvent::from_error(&ErrorKind::Disconnected.into()), Some(SessionEvent::Disconnected)));
+        assert!(matches!(SessionEvent::from_error(&ErrorKind::Unsupported.into()), Some(SessionEvent::Failed(_))));
+        // these say nothing about the session.
+        assert!(SessionEvent::from_error(&ErrorKind::PoolExhausted.into()).is_none());
+        assert!(SessionEvent::from_error(&ErrorKind::CursorNotAvailable.into()).is_none());
+    }
+
+    #[test]
+    fn test_only_changes_are_reported() {
+        let mut machine = SessionStateMachine::new();
+        let mut events = machine.subscribe();
+        assert!(machine.handle(SessionEvent::FrameAcquired).is_none());
+        assert!(machine.handle(SessionEvent::AccessLost).is_some());
+        assert!(machine.handle(SessionEvent::AccessLost).is_none());
+        machine.handle(SessionEvent::AccessDenied);
+        machine.handle(SessionEvent::FrameAcquired);
+
+        let seen: Vec<_> = std::iter::from_fn(|| events.try_recv()).map(|t| (t.from, t.to)).collect();
+        assert_eq!(seen, vec![
+            (SessionState::Active, SessionState::Lost),
+            (SessionState::Lost, SessionState::SecureDesktop),
+            (SessionState::SecureDesktop, SessionState::Active),
+        ]);
+        assert_eq!(machine.state(), SessionState::Active);
+    }
+
+    #[test]
+    fn test_failed_is_terminal() {
+        let mut machine = SessionStateMachine::new();
+        let mut events = machine.subscribe();
+        let transition = machine.handle_error(&DDApiError::unexpected("device removed")).unwrap();
+        assert!(matches!(&transition.cause, SessionEvent::Failed(e) if e.kind() == ErrorKind::Unexpected));
+        assert_eq!(transition.to, SessionState::Failed);
+        assert_eq!(machine.entered_at(), transition.at);
+        for event in all_events() {
+            assert!(machine.handle(event).is_none());
+        }
+        assert_eq!(machine.state(), SessionState::Failed);
+        // subscribers are disconnected once the session failed.
+        assert!(block_on(events.next()).is_some());
+        assert!(block_on(events.next()).is_none());
+    }
+
+    #[test]
+    fn test_dropped_subscribers_are_pruned() {
+        let mut machine = SessionStateMachine::new();
+        let a = machine.subscribe();
+        let mut b = machine.subscribe();
+        drop(a);
+        machine.handle(SessionEvent::AccessLost);
+        assert_eq!(machine.subscriber_count(), 1);
+        assert_eq!(b.try_recv().unwrap().to, SessionState::Lost);
+    }
+}
+
+/// maximum number of transitions queued for a subscriber. new ones are dropped while a
+/// subscriber is this far behind.
+pub const SESSION_EVENT_QUEUE: usize = 16;
+
+/// state of a duplication session.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum SessionState {
+    /// frames are being captured.
+    #[default]
+    Active,
+
+    /// access to the desktop was lost (mode switch, desktop switch etc). the api recreates the
+    /// duplication instance and becomes active with the next frame.
+    Lost,
+
+    /// windows switched to a secure desktop (UAC prompt, lock screen) and access is denied. the
+    /// api becomes active once the desktop is accessible again.
+    SecureDesktop,
+
+    /// the remote session was disconnected.
+    Disconnected,
+
+    /// an error the api can't recover from. the api has to be re-created. this state is terminal.
+    Failed,
+}
+
+impl SessionState {
+    /// returns the state after `event` happens in this state.
+    pub fn next(self, event: &SessionEvent) -> SessionState {
+        if self == SessionState::Failed {
+            return SessionState::Failed;
+        }
+        match event {
+            SessionEvent::FrameAcquired => SessionState::Active,
+            SessionEvent::AccessLost => SessionState::Lost,
+            SessionEvent::AccessDenied => SessionState::SecureDesktop,
+            SessionEvent::Disconnected => SessionState::Disconnected,
+            SessionEvent::Failed(_) => SessionState::Failed,
+        }
+    }
+
+    /// returns true if no other state can be reached from this one.
+    pub fn is_terminal(self) -> bool {
+        self == SessionState::Failed
+    }
+}
+
+/// something that happened to the session. this is the cause of a [SessionTransition].
+#[derive(Clone, Debug)]
+pub enum SessionEvent {
+    /// a new frame was acquired.
+    FrameAcquired,
+    /// duplication reported lost access.
+    AccessLost,
+    /// duplication reported denied access.
+    AccessDenied,
+    /// the session was disconnected.
+    Disconnected,
+    /// an unrecoverable error.
+    Failed(DDApiError),
+}
+
+impl SessionEvent {
+    /// map an error of the api to a session event. returns `None` for errors that don't say
+    /// anything about the session, such as [ErrorKind::PoolExhausted].
+    pub fn from_error(err: &DDApiError) -> Option<Self> {
+        match err.kind() {
+            ErrorKind::AccessLost => Some(Self::AccessLost),
+            ErrorKind::AccessDenied => Some(Self::AccessDenied),
+            ErrorKind::Disconnected => Some(Self::Disconnected),
+            ErrorKind::CursorNotAvailable | ErrorKind::PoolExhausted => None,
+            ErrorKind::Unsupported | ErrorKind::BadParam | ErrorKind::Unexpected => {
+                Some(Self::Failed(err.clone()))
+            }
+        }
+    }
+}
+
+/// a change of [SessionState].
+#[derive(Clone, Debug)]
+pub struct SessionTransition {
+    /// state before the transition.
+    pub from: SessionState,
+    /// state after the transition.
+    pub to: SessionState,
+    /// event that caused the transition.
+    pub cause: SessionEvent,
+    /// when the transition happened.
+    pub at: Instant,
+    /// wall clock time of the transition, useful for logs.
+    pub time: SystemTime,
+    /// how long the session was in `from` state.
+    pub previous_duration: Duration,
+}
+
+/// Tracks the [SessionState] of a duplication session and notifies subscribers of every change.
+///
+/// it's only a state machine, the owner feeds it [SessionEvent]s with [handle][Self::handle].
+#[derive(Debug)]
+pub struct SessionStateMachine {
+    state: SessionState,
+    entered_at: Instant,
+    subscribers: Vec<mpsc::Sender<SessionTransition>>,
+}
+
+impl Default for SessionStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionStateMachine {
+    /// create a new machine in [SessionState::Active] state.
+    pub fn new() -> Self {
+        Self {
+            state: SessionState::Active,
+            entered_at: Instant::now(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// current state.
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// when the current state was entered.
+    pub fn entered_at(&self) -> Instant {
+        self.entered_at
+    }
+
+    /// receive all future transitions.
+    pub fn subscribe(&mut self) -> SessionEvents {
+        let (tx, rx) = mpsc::channel(SESSION_EVENT_QUEUE);
+        self.subscribers.push(tx);
+        SessionEvents { rx }
+    }
+
+    /// number of subscribers that are still listening.
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /// apply `event`. returns the transition if the state changed.
+    pub fn handle(&mut self, event: SessionEvent) -> Option<SessionTransition> {
+        let next = self.state.next(&event);
+        if next == self.state {
+            return None;
+        }
+        let at = Instant::now();
+        let transition = SessionTransition {
+            from: self.state,
+            to: next,
+            cause: event,
+            at,
+            time: SystemTime::now(),
+            previous_duration: at.duration_since(self.entered_at),
+        };
+        debug!("duplication session {:?} -> {:?} because of {:?}", transition.from, transition.to, transition.cause);
+        self.state = next;
+        self.entered_at = at;
+
+        self.subscribers.retain_mut(|tx| match tx.try_send(transition.clone()) {
+            Ok(()) => true,
+            Err(e) if e.is_full() => {
+                warn!("session event subscriber is not keeping up, dropping transition");
+                true
+            }
+            Err(_) => false,
+        });
+        if next.is_terminal() {
+            // nothing can happen after this, end the subscribers' streams.
+            self.subscribers.clear();
+        }
+        Some(transition)
+    }
+
+    /// apply the event matching `err`, if any. check [SessionEvent::from_error].
+    pub fn handle_error(&mut self, err: &DDApiError) -> Option<SessionTransition> {
+        self.handle(SessionEvent::from_error(err)?)
+    }
+}
+
+/// Stream of [SessionTransition]s. returned by
+/// [subscribe_session_events][crate::DesktopDuplicationApi::subscribe_session_events].
+///
+/// the stream ends when the session fails or the api is dropped.
+#[derive(Debug)]
+pub struct SessionEvents {
+    rx: mpsc::Receiver<SessionTransition>,
+}
+
+impl SessionEvents {
+    /// return the next queued transition without waiting.
+    pub fn try_recv(&mut self) -> Option<SessionTransition> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl Stream for SessionEvents {
+    type Item = SessionTransition;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_next_unpin(cx)
+    }
+}
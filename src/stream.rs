@@ -0,0 +1,350 @@
+//! Provides [FrameStream], an async [Stream][futures::Stream] of frames acquired from any
+//! [FrameSource] such as [DesktopDuplicationApi][crate::DesktopDuplicationApi].
+//!
+//! This removes the need to write `select!` loops around
+//! [acquire_next_vsync_frame][crate::DesktopDuplicationApi::acquire_next_vsync_frame]. The stream
+//! can be paced with display vsync, a fixed interval or not at all, and can either yield or swallow
+//! recoverable errors.
+//!
+//! The stream is runtime agnostic, it can be polled from tokio, async-std, smol or
+//! [block_on][futures::executor::block_on].
+//!
+//! # Example
+//! ```
+//! use futures::StreamExt;
+//! use win_desktop_duplication::stream::{FrameSource, FrameStreamOptions};
+//!
+//! let mut frames = dupl.into_frame_stream(FrameStreamOptions::default());
+//! while let Some(frame) = frames.next().await {
+//!     // use the frame here
+//! }
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::{Stream, StreamExt};
+use futures_timer::Delay;
+use log::trace;
+
+use crate::Result;
+use crate::errors::ErrorKind;
+use crate::duplication::DesktopDuplicationApi;
+use crate::pool::PooledTexture;
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use crate::{DDApiError, Result};
+    use crate::errors::ErrorKind;
+    use crate::stream::{ErrorPolicy, FramePacing, FrameSource, FrameStreamOptions};
+
+    // frame source that replays a fixed script of results and vsync ticks.
+    struct ScriptedSource {
+        frames: VecDeque<Result<u32>>,
+        vsyncs: VecDeque<Option<Result<()>>>,
+    }
+
+    impl ScriptedSource {
+        fn new(frames: Vec<Result<u32>>) -> Self {
+            Self {
+                frames: frames.into(),
+                vsyncs: Default::default(),
+            }
+        }
+    }
+
+    impl FrameSource for ScriptedSource {
+        type Frame = u32;
+
+        fn acquire_next_frame(&mut self, _timeout: Duration) -> Result<u32> {
+            self.frames.pop_front().unwrap_or(Err(DDApiError::unexpected("script ended")))
+        }
+
+        fn poll_vsync(&mut self, _cx: &mut Context<'_>) -> Poll<Option<Result<()>>> {
+            Poll::Ready(self.vsyncs.pop_front().flatten())
+        }
+    }
+
+    fn run<T>(fut: impl std::future::Future<Output=T>) -> T {
+        tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap().block_on(fut)
+    }
+
+    #[test]
+    fn test_unpaced_yields_errors() {
+        let source = ScriptedSource::new(vec![Ok(1), Err(ErrorKind::AccessLost.into()), Ok(2),
+                                              Err(DDApiError::unexpected("boom")), Ok(3)]);
+        let items: Vec<_> = run(source.into_frame_stream(FrameStreamOptions {
+            pacing: FramePacing::Unpaced,
+            error_policy: ErrorPolicy::Yield,
+        }).collect());
+
+        assert_eq!(items.len(), 4);
+        assert!(matches!(items[0], Ok(1)));
+        assert!(matches!(&items[1], Err(e) if e.kind() == ErrorKind::AccessLost));
+        assert!(matches!(items[2], Ok(2)));
+        // non-recoverable errors are yielded and end the stream.
+        assert!(matches!(&items[3], Err(e) if e.kind() == ErrorKind::Unexpected));
+    }
+
+    #[test]
+    fn test_skip_recoverable_errors() {
+        let source = ScriptedSource::new(vec![Err(ErrorKind::AccessDenied.into()), Ok(1),
+                                              Err(ErrorKind::AccessLost.into()), Ok(2)]);
+        let items: Vec<_> = run(source.into_frame_stream(FrameStreamOptions {
+            pacing: FramePacing::Unpaced,
+            error_policy: ErrorPolicy::SkipRecoverable,
+        }).take(2).collect());
+
+        assert!(matches!(items[..], [Ok(1), Ok(2)]));
+    }
+
+    #[test]
+    fn test_vsync_stream_end_terminates() {
+        let mut source = ScriptedSource::new(vec![Ok(1), Ok(2), Ok(3)]);
+        source.vsyncs = vec![Some(Ok(())), Some(Ok(()))].into();
+        let items: Vec<_> = run(source.into_frame_stream(Default::default()).collect());
+
+        assert!(matches!(items[..], [Ok(1), Ok(2)]));
+    }
+
+    #[test]
+    fn test_vsync_error_terminates() {
+        let mut source = ScriptedSource::new(vec![Ok(1), Ok(2)]);
+        source.vsyncs = vec![Some(Ok(())), Some(Err(DDApiError::unexpected("vblank")))].into();
+        let items: Vec<_> = run(source.into_frame_stream(Default::default()).collect());
+
+        assert_eq!(items.len(), 2);
+        assert!(matches!(items[0], Ok(1)));
+        assert!(matches!(&items[1], Err(e) if e.kind() == ErrorKind::Unexpected));
+    }
+
+    #[test]
+    fn test_interval_pacing() {
+        let source = ScriptedSource::new(vec![Ok(1), Ok(2), Ok(3)]);
+        let start = std::time::Instant::now();
+        let items: Vec<_> = run(source.into_frame_stream(FrameStreamOptions {
+            pacing: FramePacing::Interval(Duration::from_millis(20)),
+            error_policy: ErrorPolicy::Yield,
+        }).take(3).collect());
+
+        assert_eq!(items.len(), 3);
+        // first tick completes immediately, the remaining two are paced.
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
+
+/// A source of captured frames. [DesktopDuplicationApi] implements this trait, and you can
+/// implement it for your own capture sources or for synthetic sources in tests.
+///
+/// Any source can be converted into a [FrameStream] with [into_frame_stream][FrameSource::into_frame_stream].
+pub trait FrameSource {
+    /// type of frame returned by this source.
+    type Frame;
+
+    /// acquire the next frame waiting at most `timeout` for a new one.
+    ///
+    /// this uses the same error semantics as
+    /// [DesktopDuplicationApi::acquire_next_frame][crate::DesktopDuplicationApi::acquire_next_frame].
+    fn acquire_next_frame(&mut self, timeout: Duration) -> Result<Self::Frame>;
+
+    /// poll for the next vsync event of the display backing this source. `Ready(None)` means the
+    /// vsync signal ended and no more frames should be acquired.
+    ///
+    /// sources that are not backed by a display fail with [ErrorKind::Unsupported]. use
+    /// [FramePacing::Interval] or [FramePacing::Unpaced] with those.
+    fn poll_vsync(&mut self, _cx: &mut Context<'_>) -> Poll<Option<Result<()>>> {
+        Poll::Ready(Some(Err(ErrorKind::Unsupported.into())))
+    }
+
+    /// convert this source into an async [Stream] of frames.
+    fn into_frame_stream(self, options: FrameStreamOptions) -> FrameStream<Self>
+        where Self: Sized {
+        FrameStream::new(self, options)
+    }
+}
+
+/// frames are leased from the [frame pool][crate::pool] of the api so they stay valid while newer
+/// frames are acquired. see [acquire_next_pooled_frame][DesktopDuplicationApi::acquire_next_pooled_frame].
+impl FrameSource for DesktopDuplicationApi {
+    type Frame = PooledTexture;
+
+    fn acquire_next_frame(&mut self, timeout: Duration) -> Result<PooledTexture> {
+        self.acquire_next_pooled_frame(timeout)
+    }
+
+    fn poll_vsync(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<()>>> {
+        self.vsync_stream.poll_next_unpin(cx)
+    }
+}
+
+/// decides when a [FrameStream] acquires the next frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum FramePacing {
+    /// acquire a frame after every vsync of the source's display. the stream ends when the
+    /// vsync signal ends.
+    #[default]
+    VSync,
+
+    /// acquire a frame at a fixed interval. missed ticks are skipped. the interval must be non-zero.
+    Interval(Duration),
+
+    /// acquire a frame every time the stream is polled. only useful with sources that pace
+    /// themselves.
+    Unpaced,
+}
+
+/// decides how a [FrameStream] handles [recoverable][crate::errors::DDApiError::is_recoverable] errors such as
+/// [ErrorKind::AccessLost] or [ErrorKind::PoolExhausted].
+///
+/// non-recoverable errors are always yielded and end the stream.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ErrorPolicy {
+    /// yield recoverable errors to the consumer and keep going.
+    #[default]
+    Yield,
+
+    /// swallow recoverable errors and retry on the next pacing tick.
+    SkipRecoverable,
+}
+
+/// Settings for [FrameStream].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStreamOptions {
+    pub pacing: FramePacing,
+    pub error_policy: ErrorPolicy,
+}
+
+/// Async [Stream] of frames acquired from a [FrameSource]. created with
+/// [into_frame_stream][FrameSource::into_frame_stream].
+///
+/// The stream ends after yielding a non-recoverable error, or when the vsync signal of the source
+/// ends.
+pub struct FrameStream<S: FrameSource> {
+    source: S,
+    options: FrameStreamOptions,
+    ticker: Option<Ticker>,
+    done: bool,
+}
+
+impl<S: FrameSource> FrameStream<S> {
+    /// create a new frame stream from given source
+    pub fn new(source: S, options: FrameStreamOptions) -> Self {
+        Self {
+            source,
+            options,
+            ticker: None,
+            done: false,
+        }
+    }
+
+    /// returns reference to underlying source.
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// returns mutable reference to underlying source. this can be used to configure the source
+    /// while streaming.
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// consume the stream and return the underlying source.
+    pub fn into_source(self) -> S {
+        self.source
+    }
+
+    fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<()>>> {
+        match self.options.pacing {
+            FramePacing::VSync => self.source.poll_vsync(cx),
+            FramePacing::Interval(period) => {
+                let ticker = self.ticker.get_or_insert_with(|| Ticker::new(period));
+                ticker.poll_tick(cx).map(|_| Some(Ok(())))
+            }
+            FramePacing::Unpaced => Poll::Ready(Some(Ok(()))),
+        }
+    }
+}
+
+impl<S: FrameSource + Unpin> Stream for FrameStream<S> {
+    type Item = Result<S::Frame>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while !this.done {
+            match this.poll_tick(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => {
+                    trace!("frame stream pacing signal ended");
+                    this.done = true;
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(Some(Ok(()))) => {
+                    match this.source.acquire_next_frame(Duration::from_millis(0)) {
+                        Ok(frame) => return Poll::Ready(Some(Ok(frame))),
+                        Err(e) if e.is_recoverable() => {
+                            if this.options.error_policy == ErrorPolicy::Yield {
+                                return Poll::Ready(Some(Err(e)));
+                            }
+                            trace!("skipping recoverable error in frame stream: {:?}", e);
+                            if matches!(this.options.pacing, FramePacing::Unpaced) {
+                                // give other tasks a chance to run instead of spinning here.
+                                cx.waker().wake_by_ref();
+                                return Poll::Pending;
+                            }
+                        }
+                        Err(e) => {
+                            this.done = true;
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                    }
+                }
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+// runtime agnostic interval timer. the first tick completes immediately and missed ticks are
+// skipped.
+struct Ticker {
+    period: Duration,
+    next: Instant,
+    delay: Option<Delay>,
+}
+
+impl Ticker {
+    fn new(period: Duration) -> Self {
+        Self {
+            period: period.max(Duration::from_micros(1)),
+            next: Instant::now(),
+            delay: None,
+        }
+    }
+
+    fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now < self.next {
+            let delay = self.delay.get_or_insert_with(|| Delay::new(self.next - now));
+            if Pin::new(delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+        self.delay = None;
+        let now = now.max(self.next);
+        while self.next <= now {
+            self.next += self.period;
+        }
+        Poll::Ready(())
+    }
+}
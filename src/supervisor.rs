@@ -0,0 +1,498 @@
+//! Provides [Supervisor], a [FrameSource] that re-creates its inner source after
+//! non-recoverable errors.
+//!
+//! Errors like [ErrorKind::Unexpected] mean the duplication instance is gone for good and
+//! the application has to drop it and create a new one. The supervisor does that: it drops the
+//! failed source and rebuilds it through a factory with exponential backoff and jitter, up to an
+//! optional maximum number of attempts. While it's rebuilding, acquiring a frame fails with the
+//! recoverable [ErrorKind::AccessLost], so [streams][crate::stream::FrameStream] and
+//! [workers][crate::worker::CaptureWorker] keep running.
+//!
+//! [Supervisor::for_display] rebuilds device, context and duplication for a display, re-finding the
+//! adapter by LUID and the display by its [DisplayId][crate::display_id::DisplayId] since the old handles
+//! may be dead after a driver reset and the display may have been replugged.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::supervisor::{Supervisor, SupervisorOptions};
+//!
+//! let supervisor = Supervisor::for_display(&adapter, &display, Default::default(), SupervisorOptions::default())?
+//!     .on_retry(|attempt, delay, err| println!("rebuild #{} in {:?} after {:?}", attempt, delay, err));
+//! let mut frames = supervisor.into_frame_stream(Default::default());
+//! ```
+
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures_timer::Delay;
+use log::{error, info, warn};
+
+use crate::{DDApiError, DesktopDuplicationApi, DuplicationApiOptions, Result};
+use crate::errors::ErrorKind;
+use crate::devices::{Adapter, AdapterFactory};
+use crate::display_id::DisplayId;
+use crate::outputs::Display;
+use crate::stream::FrameSource;
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use crate::{DDApiError, Result};
+    use crate::errors::ErrorKind;
+    use crate::stream::FrameSource;
+    use crate::supervisor::{Supervisor, SupervisorOptions};
+
+    // source that replays scripted results and then keeps returning frames.
+    struct FlakySource {
+        id: u32,
+        script: VecDeque<Result<u32>>,
+    }
+
+    impl FrameSource for FlakySource {
+        type Frame = (u32, u32);
+
+        fn acquire_next_frame(&mut self, _timeout: Duration) -> Result<(u32, u32)> {
+            self.script.pop_front().unwrap_or(Ok(0)).map(|f| (self.id, f))
+        }
+    }
+
+    fn fast_options() -> SupervisorOptions {
+        SupervisorOptions {
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(20),
+            jitter: 0.0,
+            ..Default::default()
+        }
+    }
+
+    // factory that fails `fail_builds` times, then builds sources with given scripts.
+    fn factory(fail_builds: u32, scripts: Vec<Vec<Result<u32>>>) -> impl FnMut() -> Result<FlakySource> + Send {
+        let mut builds = 0;
+        let mut scripts: VecDeque<_> = scripts.into();
+        move || {
+            builds += 1;
+            if builds <= fail_builds {
+                return Err(DDApiError::unexpected("adapter is gone"));
+            }
+            Ok(FlakySource { id: builds, script: scripts.pop_front().unwrap_or_default().into() })
+        }
+    }
+
+    #[test]
+    fn test_rebuild_after_fatal_error() {
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let seen = failures.clone();
+        let mut sup = Supervisor::new(fast_options(), factory(0, vec![
+            vec![Ok(1), Err(ErrorKind::AccessLost.into()), Err(DDApiError::unexpected("device removed"))],
+            vec![Ok(2)],
+        ])).on_failure(move |e| seen.lock().unwrap().push(format!("{:?}", e)));
+
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Ok((1, 1))));
+        // recoverable errors are passed through without rebuilding.
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Err(e) if e.kind() == ErrorKind::AccessLost));
+        assert!(sup.source().is_some());
+        // fatal errors are reported as recoverable while the source is rebuilt.
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Err(e) if e.kind() == ErrorKind::AccessLost));
+        assert!(sup.source().is_none());
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Ok((2, 2))));
+        assert_eq!(sup.restarts(), 1);
+        assert_eq!(failures.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_backoff_between_attempts() {
+        let retries = Arc::new(Mutex::new(Vec::new()));
+        let recovered = Arc::new(Mutex::new(None));
+        let (seen_retries, seen_recovered) = (retries.clone(), recovered.clone());
+        let mut sup = Supervisor::new(fast_options(), factory(2, vec![]))
+            .on_retry(move |attempt, delay, _| seen_retries.lock().unwrap().push((attempt, delay)))
+            .on_recovered(move |attempts| *seen_recovered.lock().unwrap() = Some(attempts));
+
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Err(e) if e.kind() == ErrorKind::AccessLost));
+        // the next attempt isn't due yet, so the factory isn't called.
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Err(e) if e.kind() == ErrorKind::AccessLost));
+        assert_eq!(sup.attempts(), 1);
+        sleep(Duration::from_millis(6));
+        assert!(sup.acquire_next_frame(Duration::ZERO).is_err());
+        sleep(Duration::from_millis(11));
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Ok((3, 0))));
+
+        assert_eq!(*retries.lock().unwrap(), vec![(1, Duration::from_millis(5)), (2, Duration::from_millis(10))]);
+        assert_eq!(*recovered.lock().unwrap(), Some(3));
+        assert_eq!(sup.attempts(), 0);
+    }
+
+    #[test]
+    fn test_give_up_after_max_attempts() {
+        let gave_up = Arc::new(Mutex::new(false));
+        let flag = gave_up.clone();
+        let mut sup = Supervisor::new(SupervisorOptions {
+            initial_backoff: Duration::ZERO,
+            max_attempts: Some(2),
+            ..fast_options()
+        }, factory(u32::MAX, vec![])).on_give_up(move |_| *flag.lock().unwrap() = true);
+
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Err(e) if e.kind() == ErrorKind::AccessLost));
+        // the last error is returned as is once the supervisor gives up.
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Err(e) if e.kind() == ErrorKind::Unexpected));
+        assert!(matches!(sup.acquire_next_frame(Duration::ZERO), Err(e) if e.kind() == ErrorKind::Unexpected));
+        assert!(sup.has_given_up());
+        assert!(*gave_up.lock().unwrap());
+        assert_eq!(sup.attempts(), 2);
+
+        sup.reset();
+        assert!(!sup.has_given_up());
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let options = SupervisorOptions {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(options.backoff_delay(1, 0.5), Duration::from_millis(100));
+        assert_eq!(options.backoff_delay(3, 0.5), Duration::from_millis(400));
+        // capped at max backoff before jitter is applied.
+        assert_eq!(options.backoff_delay(10, 0.5), Duration::from_secs(1));
+        // jitter spreads the delay by +-50%.
+        assert_eq!(options.backoff_delay(1, 0.0), Duration::from_millis(50));
+        assert_eq!(options.backoff_delay(1, 1.0), Duration::from_millis(150));
+    }
+}
+
+/// Settings for [Supervisor].
+#[derive(Clone, Debug)]
+pub struct SupervisorOptions {
+    /// delay before the first rebuild attempt after a failure. defaults to 100ms.
+    pub initial_backoff: Duration,
+
+    /// upper bound of the delay between attempts. defaults to 10 seconds.
+    pub max_backoff: Duration,
+
+    /// factor the delay grows by with every failed attempt. defaults to 2.
+    pub multiplier: f64,
+
+    /// fraction of the delay that is randomized to spread out retries, 0 disables jitter.
+    /// defaults to 0.2 (+-20%).
+    pub jitter: f64,
+
+    /// number of failed rebuild attempts in a row after which the supervisor gives up. `None`
+    /// retries forever, which is the default.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for SupervisorOptions {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl SupervisorOptions {
+    /// delay before attempt number `attempt + 1`. `unit` is a random number in `0.0..=1.0` that
+    /// decides the jitter, 0.5 means none.
+    pub fn backoff_delay(&self, attempt: u32, unit: f64) -> Duration {
+        let exp = attempt.saturating_sub(1).min(63) as i32;
+        let base = self.initial_backoff.as_secs_f64() * self.multiplier.max(1.0).powi(exp);
+        let base = base.min(self.max_backoff.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0) * (unit.clamp(0.0, 1.0) * 2.0 - 1.0);
+        Duration::from_secs_f64((base * (1.0 + jitter)).max(0.0))
+    }
+}
+
+type Factory<S> = Box<dyn FnMut() -> Result<S> + Send>;
+type ErrorCallback = Box<dyn FnMut(&DDApiError) + Send>;
+type RetryCallback = Box<dyn FnMut(u32, Duration, &DDApiError) + Send>;
+type RecoveredCallback = Box<dyn FnMut(u32) + Send>;
+
+/// A [FrameSource] that re-creates its inner source with a factory after non-recoverable errors.
+///
+/// * recoverable errors of the inner source are passed through.
+/// * a non-recoverable error (or a failed vsync signal) drops the inner source and schedules a
+///   rebuild. acquiring frames fails with [ErrorKind::AccessLost] until a rebuild succeeds.
+/// * failed rebuilds are retried after [backoff][SupervisorOptions::backoff_delay]. after
+///   [max_attempts][SupervisorOptions::max_attempts] failures in a row, the supervisor gives up
+///   and returns the last error from then on, which ends frame streams.
+///
+/// the inner source is built lazily on the first acquire.
+pub struct Supervisor<S> {
+    factory: Factory<S>,
+    options: SupervisorOptions,
+    source: Option<S>,
+    attempts: u32,
+    restarts: u32,
+    // the source failed and wasn't rebuilt yet.
+    rebuilding: bool,
+    next_attempt: Instant,
+    delay: Option<Delay>,
+    given_up: Option<DDApiError>,
+    rng: u64,
+
+    on_failure: Option<ErrorCallback>,
+    on_retry: Option<RetryCallback>,
+    on_recovered: Option<RecoveredCallback>,
+    on_give_up: Option<ErrorCallback>,
+}
+
+impl<S: FrameSource> Supervisor<S> {
+    /// create a new supervisor that builds its source with `factory`.
+    pub fn new<F>(options: SupervisorOptions, factory: F) -> Self
+        where F: FnMut() -> Result<S> + Send + 'static {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        Self {
+            factory: Box::new(factory),
+            options,
+            source: None,
+            attempts: 0,
+            restarts: 0,
+            rebuilding: false,
+            next_attempt: Instant::now(),
+            delay: None,
+            given_up: None,
+            rng: seed | 1,
+            on_failure: None,
+            on_retry: None,
+            on_recovered: None,
+            on_give_up: None,
+        }
+    }
+
+    /// called with the error that made the supervisor drop its source.
+    pub fn on_failure<F>(mut self, f: F) -> Self
+        where F: FnMut(&DDApiError) + Send + 'static {
+        self.on_failure = Some(Box::new(f));
+        self
+    }
+
+    /// called after a failed rebuild with the attempt number, the delay until the next attempt
+    /// and the error.
+    pub fn on_retry<F>(mut self, f: F) -> Self
+        where F: FnMut(u32, Duration, &DDApiError) + Send + 'static {
+        self.on_retry = Some(Box::new(f));
+        self
+    }
+
+    /// called after a successful rebuild with the number of attempts it took.
+    pub fn on_recovered<F>(mut self, f: F) -> Self
+        where F: FnMut(u32) + Send + 'static {
+        self.on_recovered = Some(Box::new(f));
+        self
+    }
+
+    /// called once with the last error when the supervisor gives up.
+    pub fn on_give_up<F>(mut self, f: F) -> Self
+        where F: FnMut(&DDApiError) + Send + 'static {
+        self.on_give_up = Some(Box::new(f));
+        self
+    }
+
+    /// returns the inner source, if it's currently alive.
+    pub fn source(&self) -> Option<&S> {
+        self.source.as_ref()
+    }
+
+    /// returns the inner source mutably, if it's currently alive. configuration done here is lost
+    /// when the source is rebuilt, configure it in the factory instead.
+    pub fn source_mut(&mut self) -> Option<&mut S> {
+        self.source.as_mut()
+    }
+
+    /// number of failed rebuild attempts in a row.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// number of times the source was rebuilt after a failure.
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// returns true if the supervisor stopped trying to rebuild the source.
+    pub fn has_given_up(&self) -> bool {
+        self.given_up.is_some()
+    }
+
+    /// forget failed attempts and try to rebuild right away, even after giving up.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+        self.given_up = None;
+        self.next_attempt = Instant::now();
+        self.delay = None;
+    }
+
+    // drop the source after a fatal error and rebuild it right away.
+    fn fail(&mut self, err: &DDApiError) {
+        error!("supervised source failed, rebuilding it. {:?}", err);
+        if let Some(cb) = self.on_failure.as_mut() {
+            cb(err);
+        }
+        self.source = None;
+        self.rebuilding = true;
+        self.next_attempt = Instant::now();
+        self.delay = None;
+    }
+
+    // make sure the source exists, building it if an attempt is due.
+    fn ensure_source(&mut self) -> Result<&mut S> {
+        if let Some(err) = &self.given_up {
+            return Err(err.clone());
+        }
+        if self.source.is_none() {
+            if Instant::now() < self.next_attempt {
+                return Err(ErrorKind::AccessLost.into());
+            }
+            match (self.factory)() {
+                Ok(source) => {
+                    if self.rebuilding {
+                        self.restarts += 1;
+                        self.rebuilding = false;
+                    }
+                    if self.restarts > 0 || self.attempts > 0 {
+                        info!("supervised source rebuilt after {} attempts", self.attempts + 1);
+                        if let Some(cb) = self.on_recovered.as_mut() {
+                            cb(self.attempts + 1);
+                        }
+                    }
+                    self.source = Some(source);
+                    self.attempts = 0;
+                    self.delay = None;
+                }
+                Err(e) => return Err(self.schedule_retry(e)),
+            }
+        }
+        Ok(self.source.as_mut().unwrap())
+    }
+
+    // record a failed attempt and return the error to report to the caller.
+    fn schedule_retry(&mut self, err: DDApiError) -> DDApiError {
+        self.attempts += 1;
+        if self.options.max_attempts.is_some_and(|max| self.attempts >= max) {
+            error!("giving up on supervised source after {} attempts. {:?}", self.attempts, err);
+            if let Some(cb) = self.on_give_up.as_mut() {
+                cb(&err);
+            }
+            self.given_up = Some(err.clone());
+            return err;
+        }
+        let unit = self.next_unit();
+        let delay = self.options.backoff_delay(self.attempts, unit);
+        warn!("failed to build supervised source (attempt {}), retrying in {:?}. {:?}", self.attempts, delay, err);
+        if let Some(cb) = self.on_retry.as_mut() {
+            cb(self.attempts, delay, &err);
+        }
+        self.next_attempt = Instant::now() + delay;
+        self.delay = Some(Delay::new(delay));
+        ErrorKind::AccessLost.into()
+    }
+
+    // xorshift64, good enough to spread out retries.
+    fn next_unit(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Supervisor<DesktopDuplicationApi> {
+    /// supervise duplication of `display`. rebuilds look for the monitor by its [DisplayId] on all
+    /// adapters, starting with the one with the LUID of `adapter`. the display with the same name
+    /// is taken right away if it is still the same monitor, as names change when monitors are
+    /// replugged.
+    ///
+    /// fails if the LUID of `adapter` or the name of `display` can't be read.
+    pub fn for_display(adapter: &Adapter, display: &Display, dupl_options: DuplicationApiOptions,
+                       options: SupervisorOptions) -> Result<Self> {
+        let luid = adapter.try_luid()?;
+        let name = display.try_name()?;
+        let id = display.id()?;
+        Ok(Self::new(options, move || {
+            let mut adapters: Vec<Adapter> = AdapterFactory::try_new()?.collect();
+            adapters.sort_by_key(|a| a.try_luid().ok() != Some(luid));
+            let mut displays: Vec<(Adapter, Display)> = adapters.iter()
+                .flat_map(|a| a.iter_displays().map(move |d| (a.clone(), d)))
+                .collect();
+
+            let same_name = displays.iter().position(|(_, d)| {
+                d.try_name().is_ok_and(|n| n == name) && d.id().is_ok_and(|other| id.match_quality(&other).is_some())
+            });
+            let idx = match same_name {
+                Some(idx) => idx,
+                None => {
+                    let ids: Vec<DisplayId> = displays.iter().map(|(_, d)| d.id().unwrap_or_default()).collect();
+                    id.find_in(&ids).map(|(idx, _)| idx)
+                        .ok_or_else(|| DDApiError::unexpected(format!("display {} ({}) not found", name, id)))?
+                }
+            };
+            let (adapter, display) = displays.swap_remove(idx);
+            let mut dupl = DesktopDuplicationApi::new(adapter, display)?;
+            dupl.configure(dupl_options.clone());
+            Ok(dupl)
+        }))
+    }
+}
+
+impl<S: FrameSource> FrameSource for Supervisor<S> {
+    type Frame = S::Frame;
+
+    fn acquire_next_frame(&mut self, timeout: Duration) -> Result<S::Frame> {
+        match self.ensure_source()?.acquire_next_frame(timeout) {
+            Err(e) if !e.is_recoverable() => {
+                self.fail(&e);
+                Err(ErrorKind::AccessLost.into())
+            }
+            res => res,
+        }
+    }
+
+    fn poll_vsync(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<()>>> {
+        if let Some(source) = self.source.as_mut() {
+            match source.poll_vsync(cx) {
+                Poll::Ready(Some(Err(e))) if e.kind() == ErrorKind::Unsupported => {
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(Some(Err(e))) => self.fail(&e),
+                Poll::Ready(None) => self.fail(&DDApiError::unexpected("vsync signal ended")),
+                pending_or_tick => return pending_or_tick,
+            }
+        }
+        if let Some(err) = &self.given_up {
+            return Poll::Ready(Some(Err(err.clone())));
+        }
+        // wake up when the next rebuild attempt is due.
+        if let Some(delay) = self.delay.as_mut() {
+            if Pin::new(delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.delay = None;
+        }
+        Poll::Ready(Some(Ok(())))
+    }
+}
+
+impl<S> Debug for Supervisor<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Supervisor")
+            .field("options", &self.options)
+            .field("alive", &self.source.is_some())
+            .field("attempts", &self.attempts)
+            .field("restarts", &self.restarts)
+            .field("given_up", &self.given_up)
+            .finish()
+    }
+}
@@ -134,7 +134,7 @@ impl TextureReader {
         let raw_tex = self.tex.as_mut().unwrap().as_raw_ref();
         let mut sub_res = D3D11_MAPPED_SUBRESOURCE::default();
         if let Err(e) = unsafe { self.ctx.Map(raw_tex, 0, D3D11_MAP_READ, 0, Some(&mut sub_res)) } {
-            return Err(DDApiError::Unexpected(format!("failed to map to cpu {:?}", e)));
+            return Err(DDApiError::from_win("Map", e).with_message("failed to map to cpu"));
         }
         let desc = tex.desc();
 
@@ -168,6 +168,18 @@ impl TextureReader {
         Ok(())
     }
 
+    /// retrieve data from texture and store it in `frame` along with its dimensions and format.
+    /// the frame's buffer is reused, so this works well with frames leased from a
+    /// [FramePool][crate::pool::FramePool].
+    pub fn read_frame(&mut self, tex: &Texture, frame: &mut CpuFrame) -> Result<()> {
+        self.get_data(&mut frame.data, tex)?;
+        let desc = tex.desc();
+        frame.width = desc.width;
+        frame.height = desc.height;
+        frame.format = desc.format;
+        Ok(())
+    }
+
     fn ensure_shape(&mut self, tex: &Texture) -> Result<()> {
         if self.tex.is_none() || self.tex.as_mut().unwrap().desc() != tex.desc() {
             self.tex = None;
@@ -181,11 +193,21 @@ impl TextureReader {
             let mut new_tex = None;
 
             if let Err(e) = unsafe { self.device.CreateTexture2D(&desc, None, Some(&mut new_tex)) } {
-                return Err(DDApiError::Unexpected(format!("failed to create texture. {:?}", e)));
+                return Err(DDApiError::from_win("CreateTexture2D", e));
             }
             self.tex = Some(Texture::new(new_tex.unwrap()))
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Frame whose pixel data lives in system memory. rows are tightly packed, see
+/// [TextureReader::get_data] for the layout of each format.
+#[derive(Clone, Debug, Default)]
+pub struct CpuFrame {
+    pub width: u32,
+    pub height: u32,
+    pub format: ColorFormat,
+    pub data: Vec<u8>,
+}
@@ -0,0 +1,191 @@
+//! Which adapter owns which display.
+//!
+//! On hybrid-GPU laptops (Optimus and similar) the displays are usually attached to the
+//! integrated gpu while [AdapterFactory] returns the discrete one first. That adapter has no
+//! outputs and `DuplicateOutput1` only works with the adapter that owns the display. A [Topology]
+//! is a snapshot of every display across all adapters with its owning adapter, so the right
+//! adapter can be picked and callers learn whether frames have to be copied to another adapter.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::topology::Topology;
+//!
+//! let topology = Topology::capture()?;
+//! let (adapter, display) = topology.locate("\\\\.\\DISPLAY1").unwrap();
+//! let dupl = DesktopDuplicationApi::new(adapter, display)?;
+//!
+//! // the encoder runs on the discrete gpu.
+//! if topology.needs_cross_adapter_copy("\\\\.\\DISPLAY1", encoder_luid) == Some(true) {
+//!     // share or copy frames between the adapters.
+//! }
+//! ```
+
+use windows::Win32::Foundation::LUID;
+
+use crate::devices::{Adapter, AdapterFactory, AdapterOptions, GpuPreference};
+use crate::outputs::Display;
+use crate::Result;
+
+#[cfg(test)]
+mod test {
+    use windows::Win32::Foundation::LUID;
+
+    use crate::topology::{DisplayLocation, Topology};
+
+    const IGPU: LUID = LUID { LowPart: 1, HighPart: 0 };
+    const DGPU: LUID = LUID { LowPart: 2, HighPart: 0 };
+
+    fn location(display_name: &str, adapter_luid: LUID, adapter_index: usize, display_index: u32) -> DisplayLocation {
+        DisplayLocation {
+            display_name: display_name.to_owned(),
+            adapter_name: format!("adapter {}", adapter_index),
+            adapter_luid,
+            adapter_index,
+            display_index,
+        }
+    }
+
+    // discrete gpu without outputs enumerated first, both displays on the integrated gpu.
+    fn hybrid() -> Topology {
+        Topology {
+            adapters: Vec::new(),
+            adapter_luids: vec![DGPU, IGPU],
+            locations: vec![location("\\\\.\\DISPLAY1", IGPU, 1, 0), location("\\\\.\\DISPLAY2", IGPU, 1, 1)],
+        }
+    }
+
+    #[test]
+    fn test_find_display() {
+        let topology = hybrid();
+        assert_eq!(topology.displays().len(), 2);
+        let loc = topology.find_display("\\\\.\\DISPLAY2").unwrap();
+        assert_eq!((loc.adapter_index, loc.display_index), (1, 1));
+        assert_eq!(topology.adapter_luid_for_display("\\\\.\\DISPLAY1"), Some(IGPU));
+        assert!(topology.find_display("\\\\.\\DISPLAY3").is_none());
+    }
+
+    #[test]
+    fn test_headless_adapters() {
+        let topology = hybrid();
+        assert_eq!(topology.headless_adapter_luids(), vec![DGPU]);
+        assert_eq!(topology.displays_of(IGPU).count(), 2);
+        assert_eq!(topology.displays_of(DGPU).count(), 0);
+    }
+
+    #[test]
+    fn test_cross_adapter_copy() {
+        let topology = hybrid();
+        assert_eq!(topology.needs_cross_adapter_copy("\\\\.\\DISPLAY1", DGPU), Some(true));
+        assert_eq!(topology.needs_cross_adapter_copy("\\\\.\\DISPLAY1", IGPU), Some(false));
+        assert_eq!(topology.needs_cross_adapter_copy("\\\\.\\DISPLAY9", IGPU), None);
+    }
+}
+
+/// where a display is attached.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DisplayLocation {
+    /// name of the display, as returned by [Display::name].
+    pub display_name: String,
+    /// name of the owning adapter.
+    pub adapter_name: String,
+    /// LUID of the owning adapter.
+    pub adapter_luid: LUID,
+    /// index of the owning adapter in [Topology::adapters].
+    pub adapter_index: usize,
+    /// index of the display on its adapter, for [Adapter::get_display_by_idx].
+    pub display_index: u32,
+}
+
+/// Snapshot of all adapters and the displays attached to them.
+///
+/// the snapshot doesn't update itself. capture a new one after displays are plugged in or out.
+#[derive(Clone)]
+pub struct Topology {
+    adapters: Vec<Adapter>,
+    adapter_luids: Vec<LUID>,
+    locations: Vec<DisplayLocation>,
+}
+
+impl Topology {
+    /// enumerate every adapter, including software and headless ones, and their displays.
+    pub fn capture() -> Result<Self> {
+        let mut fac = AdapterFactory::try_new()?;
+        fac.configure(AdapterOptions {
+            preference: GpuPreference::Unspecified,
+            ..Default::default()
+        });
+        let mut topology = Self {
+            adapters: Vec::new(),
+            adapter_luids: Vec::new(),
+            locations: Vec::new(),
+        };
+        for (adapter_index, adapter) in fac.iter_adapters().enumerate() {
+            let adapter_luid = adapter.try_luid()?;
+            let adapter_name = adapter.try_name()?;
+            for (display_index, display) in adapter.iter_displays().enumerate() {
+                topology.locations.push(DisplayLocation {
+                    display_name: display.try_name()?,
+                    adapter_name: adapter_name.clone(),
+                    adapter_luid,
+                    adapter_index,
+                    display_index: display_index as _,
+                });
+            }
+            topology.adapters.push(adapter);
+            topology.adapter_luids.push(adapter_luid);
+        }
+        Ok(topology)
+    }
+
+    /// all adapters, in the order DXGI enumerates them.
+    pub fn adapters(&self) -> &[Adapter] {
+        &self.adapters
+    }
+
+    /// all displays across all adapters.
+    pub fn displays(&self) -> &[DisplayLocation] {
+        &self.locations
+    }
+
+    /// displays attached to the adapter with given LUID.
+    pub fn displays_of(&self, adapter_luid: LUID) -> impl Iterator<Item=&DisplayLocation> + '_ {
+        self.locations.iter().filter(move |loc| loc.adapter_luid == adapter_luid)
+    }
+
+    /// find a display by its name.
+    pub fn find_display(&self, display_name: &str) -> Option<&DisplayLocation> {
+        self.locations.iter().find(|loc| loc.display_name == display_name)
+    }
+
+    /// LUID of the adapter owning given display.
+    pub fn adapter_luid_for_display(&self, display_name: &str) -> Option<LUID> {
+        self.find_display(display_name).map(|loc| loc.adapter_luid)
+    }
+
+    /// the adapter owning given display. this is the adapter to create
+    /// [DesktopDuplicationApi][crate::DesktopDuplicationApi] with.
+    pub fn adapter_for_display(&self, display_name: &str) -> Option<&Adapter> {
+        self.adapters.get(self.find_display(display_name)?.adapter_index)
+    }
+
+    /// the owning adapter and the display with given name.
+    pub fn locate(&self, display_name: &str) -> Option<(Adapter, Display)> {
+        let loc = self.find_display(display_name)?;
+        let adapter = self.adapters.get(loc.adapter_index)?;
+        let display = adapter.get_display_by_idx(loc.display_index)?;
+        Some((adapter.clone(), display))
+    }
+
+    /// LUIDs of adapters without any display, e.g. the discrete gpu of a hybrid laptop.
+    pub fn headless_adapter_luids(&self) -> Vec<LUID> {
+        self.adapter_luids.iter().copied()
+            .filter(|luid| self.displays_of(*luid).next().is_none())
+            .collect()
+    }
+
+    /// returns true if frames of given display have to be copied to the adapter with
+    /// `target_luid`, e.g. because an encoder runs there. `None` if the display doesn't exist.
+    pub fn needs_cross_adapter_copy(&self, display_name: &str, target_luid: LUID) -> Option<bool> {
+        Some(self.adapter_luid_for_display(display_name)? != target_luid)
+    }
+}
@@ -1,7 +1,9 @@
-use windows::core::HSTRING;
 use windows::Win32::System::Com::{COINIT_MULTITHREADED, COINIT_SPEED_OVER_MEMORY, CoInitializeEx};
 use windows::Win32::UI::HiDpi::{DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, SetProcessDpiAwarenessContext};
 
+use crate::errors::DDApiError;
+use crate::Result;
+
 fn find_terminal_idx(content: &[u16]) -> usize {
     for (i, val) in content.iter().enumerate() {
         if *val == 0 {
@@ -13,7 +15,7 @@ fn find_terminal_idx(content: &[u16]) -> usize {
 
 pub fn convert_u16_to_string(data: &[u16]) -> String {
     let terminal_idx = find_terminal_idx(data);
-    HSTRING::from_wide(&data[0..terminal_idx]).unwrap().to_string_lossy()
+    String::from_utf16_lossy(&data[0..terminal_idx])
 }
 
 pub fn set_process_dpi_awareness() {
@@ -24,8 +26,45 @@ pub fn set_process_dpi_awareness() {
     }
 }
 
+/// initialize COM for the current thread.
+///
+/// panics if the thread was already initialized with a different concurrency model. use
+/// [try_co_init] to handle the error instead.
 pub fn co_init() {
-    unsafe {
-        CoInitializeEx(None, COINIT_SPEED_OVER_MEMORY).unwrap();
-    }
-}
\ No newline at end of file
+    try_co_init().unwrap()
+}
+
+/// initialize COM for the current thread. fails if the thread was already initialized with a
+/// different concurrency model.
+pub fn try_co_init() -> Result<()> {
+    unsafe { CoInitializeEx(None, COINIT_SPEED_OVER_MEMORY) }.ok()
+        .map_err(|e| DDApiError::from_win("CoInitializeEx", e))
+}
+
+/// Spawn a dedicated "graphics thread" running a single threaded tokio runtime and run `f` on it.
+/// the thread is prepared for desktop duplication with [set_process_dpi_awareness] and [try_co_init].
+///
+/// this is what [DesktopDuplicationApi][crate::DesktopDuplicationApi] expects its caller to look
+/// like. only available with the `tokio` feature.
+///
+/// # Example
+/// ```
+/// let handle = spawn_graphics_thread(|| async {
+///     let mut dupl = DesktopDuplicationApi::new(adapter, output)?;
+///     loop {
+///         let tex = dupl.acquire_next_vsync_frame().await?;
+///     }
+/// });
+/// ```
+#[cfg(feature = "tokio")]
+pub fn spawn_graphics_thread<F, Fut>(f: F) -> std::io::Result<std::thread::JoinHandle<std::io::Result<Fut::Output>>>
+    where F: FnOnce() -> Fut + Send + 'static,
+          Fut: std::future::Future,
+          Fut::Output: Send + 'static {
+    std::thread::Builder::new().name("graphics_thread".to_owned()).spawn(move || {
+        set_process_dpi_awareness();
+        try_co_init().map_err(std::io::Error::other)?;
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build()?;
+        Ok(rt.block_on(f()))
+    })
+}
@@ -0,0 +1,587 @@
+//! Vsync sources and shared vsync waiter threads.
+//!
+//! A [VsyncSource] blocks until the next vertical blank. there are a few implementations:
+//!
+//! * [DxgiVsync] - waits on `IDXGIOutput::WaitForVBlank` of a display.
+//! * [TimerVsync] - ticks at a fixed period with a high resolution timer.
+//! * [FallbackVsync] - uses a primary source and switches to a [TimerVsync] at the display's
+//!   refresh rate while the primary one fails (monitor off, remote session etc.)
+//! * [SimulatedVsync] - stepped manually through a [SimulatedVsyncHandle]. useful for tests.
+//!
+//! Waiting for vblank is a blocking call, so every vsync stream needs a thread to wait on. Each
+//! display has at most one such thread which forwards every tick to any number of subscribers,
+//! so N [DisplayVSyncStream][crate::outputs::DisplayVSyncStream]s on the same [Display] share a single OS thread.
+//!
+//! The thread is signalled and joined when the last stream is dropped or
+//! [closed][crate::outputs::DisplayVSyncStream::close]. A waiter that hangs (e.g. `WaitForVBlank` while the
+//! monitor is off) is detached after [VSYNC_JOIN_TIMEOUT] instead of blocking the caller.
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::outputs::DisplayVSyncStream;
+//! use win_desktop_duplication::vsync::SimulatedVsync;
+//!
+//! let (source, handle) = SimulatedVsync::new();
+//! let mut stream = DisplayVSyncStream::from_source(source);
+//! handle.step();
+//! stream.next().await;
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread::{Builder, JoinHandle, sleep, yield_now};
+use std::time::{Duration, Instant};
+
+use futures::channel::mpsc;
+use log::{info, trace, warn};
+use windows::Win32::Graphics::Dxgi::DXGIDisableVBlankVirtualization;
+
+use crate::errors::DDApiError;
+use crate::outputs::Display;
+use crate::Result;
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread::sleep;
+    use std::time::{Duration, Instant};
+
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    use crate::errors::{DDApiError, ErrorKind};
+    use crate::vsync::{FallbackVsync, SimulatedVsync, TimerVsync, VSyncBroadcaster, VsyncSource};
+
+    #[test]
+    fn test_ticks_reach_all_subscribers() {
+        let broadcaster = VSyncBroadcaster::spawn(|| {
+            sleep(Duration::from_millis(2));
+            Ok(())
+        }).unwrap();
+        let mut a = broadcaster.subscribe();
+        let mut b = broadcaster.subscribe();
+        block_on(async {
+            for _ in 0..3 {
+                assert!(matches!(a.next().await, Some(Ok(()))));
+                assert!(matches!(b.next().await, Some(Ok(()))));
+            }
+        });
+    }
+
+    #[test]
+    fn test_errors_are_forwarded() {
+        let broadcaster = VSyncBroadcaster::spawn(|| {
+            sleep(Duration::from_millis(2));
+            Err(DDApiError::unexpected("vblank failed"))
+        }).unwrap();
+        let mut a = broadcaster.subscribe();
+        assert!(matches!(block_on(a.next()), Some(Err(e)) if e.kind() == ErrorKind::Unexpected));
+    }
+
+    #[test]
+    fn test_drop_joins_thread() {
+        let exited = Arc::new(AtomicBool::new(false));
+        let flag = exited.clone();
+        struct SetOnDrop(Arc<AtomicBool>);
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::Release);
+            }
+        }
+        let guard = SetOnDrop(flag);
+        let broadcaster = VSyncBroadcaster::spawn(move || {
+            let _ = &guard;
+            sleep(Duration::from_millis(2));
+            Ok(())
+        }).unwrap();
+        let _rx = broadcaster.subscribe();
+        drop(broadcaster);
+        // the waiter closure is dropped when the thread exits, which happened before drop returned.
+        assert!(exited.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_hung_waiter_is_detached() {
+        let broadcaster = VSyncBroadcaster::spawn(|| {
+            sleep(Duration::from_secs(2));
+            Ok(())
+        }).unwrap();
+        // let the thread enter the waiter first.
+        sleep(Duration::from_millis(20));
+        let start = Instant::now();
+        assert!(!broadcaster.shutdown(Duration::from_millis(20)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_simulated_source() {
+        let (source, handle) = SimulatedVsync::new();
+        let broadcaster = VSyncBroadcaster::spawn(source).unwrap();
+        let mut rx = broadcaster.subscribe();
+        handle.step();
+        assert!(matches!(block_on(rx.next()), Some(Ok(()))));
+        handle.fail(ErrorKind::AccessLost.into());
+        assert!(matches!(block_on(rx.next()), Some(Err(e)) if e.kind() == ErrorKind::AccessLost));
+        // closing the source ends every subscriber's stream.
+        drop(handle);
+        assert!(block_on(rx.next()).is_none());
+    }
+
+    #[test]
+    fn test_timer_source() {
+        let mut timer = TimerVsync::new(Duration::from_millis(5));
+        let start = Instant::now();
+        for _ in 0..4 {
+            assert!(matches!(timer.wait(), Some(Ok(()))));
+        }
+        // the first tick is one period after creation.
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(timer.refresh_period(), Some(Duration::from_millis(5)));
+
+        // missed ticks are skipped instead of firing back to back.
+        sleep(Duration::from_millis(30));
+        timer.wait();
+        let start = Instant::now();
+        timer.wait();
+        assert!(start.elapsed() >= Duration::from_millis(4));
+    }
+
+    #[test]
+    fn test_fallback_and_recovery() {
+        let (primary, handle) = SimulatedVsync::new();
+        let mut source = FallbackVsync::new(primary, Duration::from_millis(2))
+            .with_retry_interval(Duration::from_millis(100));
+
+        handle.step();
+        assert!(matches!(source.wait(), Some(Ok(()))));
+        assert!(!source.is_fallback_active());
+
+        // the failure is hidden behind a timer tick.
+        handle.fail(DDApiError::unexpected("monitor off"));
+        assert!(matches!(source.wait(), Some(Ok(()))));
+        assert!(source.is_fallback_active());
+
+        // the primary source isn't touched until the retry interval has passed.
+        handle.step();
+        for _ in 0..3 {
+            assert!(matches!(source.wait(), Some(Ok(()))));
+        }
+        assert!(source.is_fallback_active());
+        assert_eq!(handle.pending(), 1);
+
+        sleep(Duration::from_millis(100));
+        assert!(matches!(source.wait(), Some(Ok(()))));
+        assert!(!source.is_fallback_active());
+
+        drop(handle);
+        assert!(source.wait().is_none());
+    }
+}
+
+/// maximum time to wait for a vsync waiter thread to exit when it's no longer used.
+pub const VSYNC_JOIN_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// refresh period used by [FallbackVsync] when the display's refresh rate is unknown.
+pub const DEFAULT_REFRESH_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// ticks produced by the waiter thread.
+type Tick = Result<()>;
+
+/// Blocking source of vsync ticks.
+///
+/// implement this to drive [DisplayVSyncStream][crate::outputs::DisplayVSyncStream]s with
+/// [from_source][crate::outputs::DisplayVSyncStream::from_source].
+/// any `FnMut() -> Result<()>` closure is also a source that never ends.
+pub trait VsyncSource: Send {
+    /// block until the next vertical blank. returns `None` when the source has ended, which
+    /// ends every stream using it.
+    fn wait(&mut self) -> Option<Result<()>>;
+
+    /// nominal time between two ticks, if known.
+    fn refresh_period(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl<F> VsyncSource for F where F: FnMut() -> Result<()> + Send {
+    fn wait(&mut self) -> Option<Result<()>> {
+        Some(self())
+    }
+}
+
+/// vsync source that waits on `WaitForVBlank` of a [Display].
+pub struct DxgiVsync {
+    output: Display,
+    virtualization_disabled: bool,
+}
+
+impl DxgiVsync {
+    /// create a source for the given display.
+    pub fn new(output: Display) -> Self {
+        Self {
+            output,
+            virtualization_disabled: false,
+        }
+    }
+}
+
+impl VsyncSource for DxgiVsync {
+    fn wait(&mut self) -> Option<Result<()>> {
+        if !self.virtualization_disabled {
+            unsafe { let _ = DXGIDisableVBlankVirtualization(); }
+            self.virtualization_disabled = true;
+        }
+        let res = self.output.wait_for_vsync();
+        // extra sleep to ensure the image is processed for desktop duplication.
+        sleep(Duration::from_millis(4));
+        Some(res)
+    }
+
+    fn refresh_period(&self) -> Option<Duration> {
+        self.output.get_current_display_mode().ok()?.refresh_period()
+    }
+}
+
+/// vsync source that ticks every `period`.
+///
+/// it sleeps until shortly before the deadline and yields for the rest, so ticks are accurate
+/// to well below a millisecond. ticks that were missed because the caller was late are skipped.
+#[derive(Clone, Debug)]
+pub struct TimerVsync {
+    period: Duration,
+    next: Instant,
+}
+
+// the remaining time to the deadline that is spent yielding instead of sleeping.
+const TIMER_SPIN_THRESHOLD: Duration = Duration::from_millis(1);
+
+impl TimerVsync {
+    /// create a timer ticking every `period`. the first tick is one period from now.
+    pub fn new(period: Duration) -> Self {
+        let period = period.max(Duration::from_micros(100));
+        Self {
+            period,
+            next: Instant::now() + period,
+        }
+    }
+
+    /// create a timer ticking at the current refresh rate of `display`, or at 60hz if it can't
+    /// be queried.
+    pub fn for_display(display: &Display) -> Self {
+        let period = display.get_current_display_mode().ok()
+            .and_then(|mode| mode.refresh_period())
+            .unwrap_or(DEFAULT_REFRESH_PERIOD);
+        Self::new(period)
+    }
+}
+
+impl VsyncSource for TimerVsync {
+    fn wait(&mut self) -> Option<Result<()>> {
+        loop {
+            let now = Instant::now();
+            if now >= self.next {
+                break;
+            }
+            let remaining = self.next - now;
+            if remaining > TIMER_SPIN_THRESHOLD {
+                sleep(remaining - TIMER_SPIN_THRESHOLD);
+            } else {
+                yield_now();
+            }
+        }
+        self.next += self.period;
+        let now = Instant::now();
+        if self.next <= now {
+            self.next = now + self.period;
+        }
+        Some(Ok(()))
+    }
+
+    fn refresh_period(&self) -> Option<Duration> {
+        Some(self.period)
+    }
+}
+
+/// vsync source that uses `primary` and falls back to a [TimerVsync] while `primary` fails.
+///
+/// errors of the primary source are logged and replaced by timer ticks at the primary source's
+/// [refresh period][VsyncSource::refresh_period]. the primary source is retried every
+/// [retry interval][FallbackVsync::with_retry_interval] and used again once it succeeds.
+pub struct FallbackVsync<P> {
+    primary: P,
+    default_period: Duration,
+    retry_interval: Duration,
+    fallback: Option<TimerVsync>,
+    retry_at: Instant,
+}
+
+impl<P: VsyncSource> FallbackVsync<P> {
+    /// create a new fallback source. `default_period` is used for the timer when the primary
+    /// source doesn't know its refresh period.
+    pub fn new(primary: P, default_period: Duration) -> Self {
+        Self {
+            primary,
+            default_period,
+            retry_interval: Duration::from_secs(1),
+            fallback: None,
+            retry_at: Instant::now(),
+        }
+    }
+
+    /// how often the primary source is retried while the timer is used. default is 1 second.
+    pub fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// returns true if ticks currently come from the fallback timer.
+    pub fn is_fallback_active(&self) -> bool {
+        self.fallback.is_some()
+    }
+}
+
+impl FallbackVsync<DxgiVsync> {
+    /// default vsync source of a display: `WaitForVBlank` with a timer fallback at the display's
+    /// refresh rate.
+    pub fn for_display(display: Display) -> Self {
+        Self::new(DxgiVsync::new(display), DEFAULT_REFRESH_PERIOD)
+    }
+}
+
+impl<P: VsyncSource> VsyncSource for FallbackVsync<P> {
+    fn wait(&mut self) -> Option<Result<()>> {
+        if self.fallback.is_none() || Instant::now() >= self.retry_at {
+            match self.primary.wait()? {
+                Ok(()) => {
+                    if self.fallback.take().is_some() {
+                        info!("vsync source recovered, leaving timer fallback");
+                    }
+                    return Some(Ok(()));
+                }
+                Err(e) => {
+                    if self.fallback.is_none() {
+                        let period = self.primary.refresh_period().unwrap_or(self.default_period);
+                        warn!("vsync source failed, falling back to a {:?} timer. {:?}", period, e);
+                        self.fallback = Some(TimerVsync::new(period));
+                    }
+                    self.retry_at = Instant::now() + self.retry_interval;
+                }
+            }
+        }
+        self.fallback.as_mut()?.wait()
+    }
+
+    fn refresh_period(&self) -> Option<Duration> {
+        match &self.fallback {
+            Some(timer) => timer.refresh_period(),
+            None => self.primary.refresh_period(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SimulatedState {
+    ticks: VecDeque<Result<()>>,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct SimulatedShared {
+    state: Mutex<SimulatedState>,
+    ready: Condvar,
+}
+
+/// vsync source that only ticks when stepped through its [SimulatedVsyncHandle].
+///
+/// [wait][VsyncSource::wait] blocks until a tick is queued and returns `None` once the handle is
+/// dropped and all queued ticks are consumed.
+pub struct SimulatedVsync {
+    shared: Arc<SimulatedShared>,
+    period: Option<Duration>,
+}
+
+/// controls a [SimulatedVsync]. dropping the handle ends the source.
+pub struct SimulatedVsyncHandle {
+    shared: Arc<SimulatedShared>,
+}
+
+impl SimulatedVsync {
+    /// create a new simulated source and its handle.
+    pub fn new() -> (Self, SimulatedVsyncHandle) {
+        let shared: Arc<SimulatedShared> = Default::default();
+        (Self { shared: shared.clone(), period: None }, SimulatedVsyncHandle { shared })
+    }
+
+    /// report `period` as the [refresh period][VsyncSource::refresh_period] of this source.
+    pub fn with_refresh_period(mut self, period: Duration) -> Self {
+        self.period = Some(period);
+        self
+    }
+}
+
+impl VsyncSource for SimulatedVsync {
+    fn wait(&mut self) -> Option<Result<()>> {
+        let mut state = lock(&self.shared.state);
+        loop {
+            if let Some(tick) = state.ticks.pop_front() {
+                return Some(tick);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.shared.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn refresh_period(&self) -> Option<Duration> {
+        self.period
+    }
+}
+
+impl SimulatedVsyncHandle {
+    /// queue one successful tick.
+    pub fn step(&self) {
+        self.push(Ok(()));
+    }
+
+    /// queue `n` successful ticks.
+    pub fn step_n(&self, n: usize) {
+        let mut state = lock(&self.shared.state);
+        state.ticks.extend((0..n).map(|_| Ok(())));
+        self.shared.ready.notify_all();
+    }
+
+    /// queue a failed tick.
+    pub fn fail(&self, err: DDApiError) {
+        self.push(Err(err));
+    }
+
+    /// number of ticks that were queued but not consumed yet.
+    pub fn pending(&self) -> usize {
+        lock(&self.shared.state).ticks.len()
+    }
+
+    fn push(&self, tick: Result<()>) {
+        lock(&self.shared.state).ticks.push_back(tick);
+        self.shared.ready.notify_all();
+    }
+}
+
+impl Drop for SimulatedVsyncHandle {
+    fn drop(&mut self) {
+        lock(&self.shared.state).closed = true;
+        self.shared.ready.notify_all();
+    }
+}
+
+/// Owns a vsync waiter thread and forwards its ticks to every subscriber.
+#[derive(Debug)]
+pub(crate) struct VSyncBroadcaster {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<Tick>>>>,
+    stop: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    exited: Mutex<Receiver<()>>,
+}
+
+impl VSyncBroadcaster {
+    /// spawn a waiter thread that waits on `source` in a loop and broadcasts each tick. the
+    /// subscribers are disconnected when the source ends.
+    pub(crate) fn spawn<S>(mut source: S) -> Result<Self>
+        where S: VsyncSource + 'static {
+        let subscribers: Arc<Mutex<Vec<mpsc::Sender<Tick>>>> = Default::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (exited_tx, exited_rx) = channel();
+
+        let thread_subscribers = subscribers.clone();
+        let thread_stop = stop.clone();
+        let handle = Builder::new().name("vsync_thread".to_owned()).spawn(move || {
+            while !thread_stop.load(Ordering::Acquire) {
+                let Some(tick) = source.wait() else {
+                    lock(&thread_subscribers).clear();
+                    break;
+                };
+                if thread_stop.load(Ordering::Acquire) {
+                    break;
+                }
+                let mut subs = lock(&thread_subscribers);
+                // a full channel means the subscriber hasn't consumed the last tick yet. it's
+                // skipped for this tick instead of blocking everyone else.
+                subs.retain_mut(|tx| match tx.try_send(tick.clone()) {
+                    Ok(()) => true,
+                    Err(e) => !e.is_disconnected(),
+                });
+            }
+            // the source is dropped before signalling so it's released once shutdown returns.
+            drop(source);
+            trace!("exiting display sync wait thread");
+            let _ = exited_tx.send(());
+        }).map_err(|e| DDApiError::unexpected(format!("failed to spawn vsync thread. {:?}", e)))?;
+
+        Ok(Self {
+            subscribers,
+            stop,
+            thread: Mutex::new(Some(handle)),
+            exited: Mutex::new(exited_rx),
+        })
+    }
+
+    /// register a new subscriber.
+    pub(crate) fn subscribe(&self) -> mpsc::Receiver<Tick> {
+        let (tx, rx) = mpsc::channel(0);
+        lock(&self.subscribers).push(tx);
+        rx
+    }
+
+    /// signal the waiter thread to stop and join it, waiting at most `timeout`. returns false if
+    /// the thread didn't exit in time, in which case it's detached.
+    pub(crate) fn shutdown(&self, timeout: Duration) -> bool {
+        self.stop.store(true, Ordering::Release);
+        lock(&self.subscribers).clear();
+        let Some(handle) = lock(&self.thread).take() else {
+            return true;
+        };
+        match lock(&self.exited).recv_timeout(timeout) {
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+                let _ = handle.join();
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                warn!("vsync thread didn't exit within {:?}, detaching it", timeout);
+                false
+            }
+        }
+    }
+}
+
+impl Drop for VSyncBroadcaster {
+    fn drop(&mut self) {
+        self.shutdown(VSYNC_JOIN_TIMEOUT);
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn registry() -> &'static Mutex<HashMap<String, Weak<VSyncBroadcaster>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, Weak<VSyncBroadcaster>>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// returns the broadcaster of given display, spawning its waiter thread if no stream is using it.
+pub(crate) fn for_display(display: &Display) -> Result<Arc<VSyncBroadcaster>> {
+    let key = display.try_name()?;
+    let mut map = lock(registry());
+    if let Some(broadcaster) = map.get(&key).and_then(Weak::upgrade) {
+        return Ok(broadcaster);
+    }
+    // forget displays whose streams are all gone.
+    map.retain(|_, b| b.strong_count() > 0);
+
+    let broadcaster = Arc::new(VSyncBroadcaster::spawn(FallbackVsync::for_display(display.clone()))?);
+    map.insert(key, Arc::downgrade(&broadcaster));
+    Ok(broadcaster)
+}
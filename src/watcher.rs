@@ -0,0 +1,406 @@
+//! Notifications about displays being plugged in or out and display settings changing.
+//!
+//! [DisplayWatcher] takes a [Snapshot] of all displays at a fixed interval on its own thread and
+//! reports the differences to the previous one as [DisplayEvent]s through an async [Stream]. This
+//! tells about topology changes before [acquire_next_frame][crate::DesktopDuplicationApi::acquire_next_frame]
+//! starts failing, e.g. to re-create a [DesktopCapture][crate::desktop::DesktopCapture] with the
+//! new layout.
+//!
+//! Displays are matched between snapshots by [name][crate::outputs::Display::name]. The diffing
+//! in [Snapshot::diff] doesn't touch any windows api, and watchers can be driven by any snapshot
+//! source with [DisplayWatcher::from_source].
+//!
+//! # Example
+//! ```
+//! use futures::StreamExt;
+//! use win_desktop_duplication::watcher::{DisplayEvent, DisplayWatcher};
+//!
+//! let mut watcher = DisplayWatcher::new(Duration::from_secs(1))?;
+//! while let Some(event) = watcher.next().await {
+//!     match event? {
+//!         DisplayEvent::Added(display) => println!("{} plugged in", display.name),
+//!         DisplayEvent::Removed(display) => println!("{} unplugged", display.name),
+//!         other => println!("{:?}", other),
+//!     }
+//! }
+//! ```
+
+use std::pin::Pin;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::task::{Context, Poll};
+use std::thread::{Builder, JoinHandle};
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use futures::channel::mpsc;
+use log::{debug, trace, warn};
+use windows::Win32::Foundation::LUID;
+
+use crate::errors::DDApiError;
+use crate::geometry::{DisplayGeometry, Rect};
+use crate::outputs::{DisplayMode, DisplayOrientation};
+use crate::Result;
+use crate::topology::Topology;
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    use crate::errors::ErrorKind;
+    use crate::geometry::{DisplayGeometry, Rect};
+    use crate::outputs::{DisplayMode, DisplayOrientation};
+    use crate::watcher::{DisplayEvent, DisplayState, DisplayWatcher, Snapshot};
+
+    fn state(name: &str, desktop: Rect, primary: bool) -> DisplayState {
+        DisplayState {
+            name: name.to_owned(),
+            adapter_luid: Default::default(),
+            geometry: DisplayGeometry {
+                name: name.to_owned(),
+                desktop,
+                work_area: desktop,
+                attached: true,
+                primary,
+                ..Default::default()
+            },
+            mode: DisplayMode {
+                width: desktop.width(),
+                height: desktop.height(),
+                refresh_num: 60,
+                refresh_den: 1,
+                ..Default::default()
+            },
+            hdr: false,
+        }
+    }
+
+    fn two_displays() -> Snapshot {
+        Snapshot {
+            displays: vec![
+                state("\\\\.\\DISPLAY1", Rect::new(0, 0, 1920, 1080), true),
+                state("\\\\.\\DISPLAY2", Rect::new(1920, 0, 3840, 1080), false),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_no_changes() {
+        assert!(two_displays().diff(&two_displays()).is_empty());
+    }
+
+    #[test]
+    fn test_added_removed() {
+        let old = two_displays();
+        let mut new = two_displays();
+        let removed = new.displays.remove(1);
+        new.displays.push(state("\\\\.\\DISPLAY3", Rect::new(-1920, 0, 0, 1080), false));
+
+        let events = old.diff(&new);
+        assert_eq!(events, vec![
+            DisplayEvent::Removed(removed),
+            DisplayEvent::Added(new.displays[1].clone()),
+        ]);
+        assert_eq!(new.find("\\\\.\\DISPLAY3").unwrap().geometry.desktop.left, -1920);
+    }
+
+    #[test]
+    fn test_settings_changed() {
+        let old = two_displays();
+        let mut new = two_displays();
+        {
+            let display = &mut new.displays[1];
+            display.mode.refresh_num = 144;
+            display.hdr = true;
+        }
+        {
+            // rotating swaps the desktop rect but not the mode's resolution.
+            let display = &mut new.displays[0];
+            display.geometry.orientation = DisplayOrientation::Rotate90;
+            display.mode.orientation = DisplayOrientation::Rotate90;
+            display.geometry.desktop = Rect::new(0, 0, 1080, 1920);
+        }
+
+        let events = old.diff(&new);
+        assert_eq!(events, vec![
+            DisplayEvent::RotationChanged {
+                name: "\\\\.\\DISPLAY1".to_owned(),
+                old: DisplayOrientation::NoRotation,
+                new: DisplayOrientation::Rotate90,
+            },
+            DisplayEvent::Moved {
+                name: "\\\\.\\DISPLAY1".to_owned(),
+                old: Rect::new(0, 0, 1920, 1080),
+                new: Rect::new(0, 0, 1080, 1920),
+            },
+            DisplayEvent::ModeChanged {
+                name: "\\\\.\\DISPLAY2".to_owned(),
+                old: old.displays[1].mode.clone(),
+                new: new.displays[1].mode.clone(),
+            },
+            DisplayEvent::HdrChanged { name: "\\\\.\\DISPLAY2".to_owned(), enabled: true },
+        ]);
+    }
+
+    #[test]
+    fn test_primary_changed() {
+        let old = two_displays();
+        let mut new = two_displays();
+        new.displays[0].geometry.primary = false;
+        new.displays[1].geometry.primary = true;
+        assert_eq!(old.diff(&new), vec![DisplayEvent::PrimaryChanged {
+            old: Some("\\\\.\\DISPLAY1".to_owned()),
+            new: Some("\\\\.\\DISPLAY2".to_owned()),
+        }]);
+
+        // the primary display is unplugged.
+        let mut new = two_displays();
+        new.displays.remove(0);
+        let events = old.diff(&new);
+        assert_eq!(events.last(), Some(&DisplayEvent::PrimaryChanged {
+            old: Some("\\\\.\\DISPLAY1".to_owned()),
+            new: None,
+        }));
+    }
+
+    #[test]
+    fn test_watcher() {
+        let mut unplugged = two_displays();
+        unplugged.displays.pop();
+        let mut snapshots = VecDeque::from([
+            Ok(two_displays()),
+            Ok(two_displays()),
+            Err(ErrorKind::AccessDenied.into()),
+            Ok(unplugged),
+        ]);
+        let mut watcher = DisplayWatcher::from_source(Duration::from_millis(1), move || {
+            snapshots.pop_front().unwrap_or_else(|| Err(ErrorKind::Unsupported.into()))
+        }).unwrap();
+
+        block_on(async {
+            // failed snapshots are reported and the watcher carries on.
+            let err = watcher.next().await.unwrap().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::AccessDenied);
+            let event = watcher.next().await.unwrap().unwrap();
+            assert!(matches!(event, DisplayEvent::Removed(d) if d.name == "\\\\.\\DISPLAY2"));
+            // a non-recoverable error ends the stream.
+            let err = watcher.next().await.unwrap().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+            assert!(watcher.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn test_watcher_stop() {
+        let mut watcher = DisplayWatcher::from_source(Duration::from_secs(60), || Ok(two_displays())).unwrap();
+        // stopping doesn't wait for the interval to pass.
+        watcher.stop();
+        assert!(block_on(watcher.next()).is_none());
+    }
+}
+
+/// State of a single display in a [Snapshot].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DisplayState {
+    /// name of the display, as returned by [Display::name][crate::outputs::Display::name].
+    pub name: String,
+    /// LUID of the adapter owning the display.
+    pub adapter_luid: LUID,
+    /// position, rotation and scaling of the display.
+    pub geometry: DisplayGeometry,
+    /// current display mode.
+    pub mode: DisplayMode,
+    /// the display is in HDR mode, see [Display::hdr_enabled][crate::outputs::Display::hdr_enabled].
+    pub hdr: bool,
+}
+
+/// a change between two [Snapshot]s.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DisplayEvent {
+    /// a display was plugged in or attached to the desktop.
+    Added(DisplayState),
+    /// a display was unplugged or detached from the desktop. holds its last known state.
+    Removed(DisplayState),
+    /// resolution, refresh rate or bit depth changed.
+    ModeChanged { name: String, old: DisplayMode, new: DisplayMode },
+    /// the display was rotated.
+    RotationChanged { name: String, old: DisplayOrientation, new: DisplayOrientation },
+    /// the display's rect on the virtual desktop changed, because it was moved, rotated or its
+    /// resolution changed.
+    Moved { name: String, old: Rect, new: Rect },
+    /// another display became the primary one. `None` if there was or is no primary display.
+    PrimaryChanged { old: Option<String>, new: Option<String> },
+    /// HDR was turned on or off.
+    HdrChanged { name: String, enabled: bool },
+}
+
+/// State of all displays at one point in time.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Snapshot {
+    pub displays: Vec<DisplayState>,
+}
+
+impl Snapshot {
+    /// take a snapshot of every display on every adapter.
+    pub fn capture() -> Result<Self> {
+        let topology = Topology::capture()?;
+        let mut displays = Vec::new();
+        for location in topology.displays() {
+            let Some((_, display)) = topology.locate(&location.display_name) else { continue };
+            displays.push(DisplayState {
+                name: location.display_name.clone(),
+                adapter_luid: location.adapter_luid,
+                geometry: display.geometry()?,
+                mode: display.get_current_display_mode()?,
+                hdr: display.hdr_enabled()?,
+            });
+        }
+        Ok(Self { displays })
+    }
+
+    /// find a display by its name.
+    pub fn find(&self, name: &str) -> Option<&DisplayState> {
+        self.displays.iter().find(|d| d.name == name)
+    }
+
+    /// name of the primary display.
+    pub fn primary(&self) -> Option<&str> {
+        self.displays.iter().find(|d| d.geometry.primary).map(|d| d.name.as_str())
+    }
+
+    /// events that turn this snapshot into `newer`. removed displays come first, then added ones,
+    /// then the changes of each remaining display and finally a change of the primary display.
+    pub fn diff(&self, newer: &Snapshot) -> Vec<DisplayEvent> {
+        let mut events = Vec::new();
+        for old in self.displays.iter().filter(|d| newer.find(&d.name).is_none()) {
+            events.push(DisplayEvent::Removed(old.clone()));
+        }
+        for new in newer.displays.iter().filter(|d| self.find(&d.name).is_none()) {
+            events.push(DisplayEvent::Added(new.clone()));
+        }
+        for new in &newer.displays {
+            let Some(old) = self.find(&new.name) else { continue };
+            let name = || new.name.clone();
+            if old.geometry.orientation != new.geometry.orientation {
+                events.push(DisplayEvent::RotationChanged {
+                    name: name(),
+                    old: old.geometry.orientation,
+                    new: new.geometry.orientation,
+                });
+            }
+            if old.geometry.desktop != new.geometry.desktop {
+                events.push(DisplayEvent::Moved { name: name(), old: old.geometry.desktop, new: new.geometry.desktop });
+            }
+            // rotation is reported on its own.
+            let old_mode = DisplayMode { orientation: new.mode.orientation, ..old.mode.clone() };
+            if old_mode != new.mode {
+                events.push(DisplayEvent::ModeChanged { name: name(), old: old.mode.clone(), new: new.mode.clone() });
+            }
+            if old.hdr != new.hdr {
+                events.push(DisplayEvent::HdrChanged { name: name(), enabled: new.hdr });
+            }
+        }
+        if self.primary() != newer.primary() {
+            events.push(DisplayEvent::PrimaryChanged {
+                old: self.primary().map(str::to_owned),
+                new: newer.primary().map(str::to_owned),
+            });
+        }
+        events
+    }
+}
+
+/// Stream of [DisplayEvent]s. check the [module docs][self].
+///
+/// failed snapshots are yielded as errors. the stream ends after a non-recoverable one, or once
+/// the watcher is [stopped][DisplayWatcher::stop] or dropped.
+pub struct DisplayWatcher {
+    events: mpsc::UnboundedReceiver<Result<DisplayEvent>>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DisplayWatcher {
+    /// watch all displays, taking a [Snapshot] every `interval`.
+    pub fn new(interval: Duration) -> Result<Self> {
+        Self::from_source(interval, Snapshot::capture)
+    }
+
+    /// watch snapshots returned by `source`, called every `interval` on the watcher thread.
+    pub fn from_source<F>(interval: Duration, mut source: F) -> Result<Self>
+        where F: FnMut() -> Result<Snapshot> + Send + 'static {
+        let (event_tx, events) = mpsc::unbounded();
+        let (stop, stop_rx) = channel::<()>();
+
+        let thread = Builder::new().name("display_watcher".to_owned()).spawn(move || {
+            let mut previous: Option<Snapshot> = None;
+            loop {
+                match source() {
+                    Ok(snapshot) => {
+                        if let Some(previous) = &previous {
+                            for event in previous.diff(&snapshot) {
+                                debug!("display change: {:?}", event);
+                                let _ = event_tx.unbounded_send(Ok(event));
+                            }
+                        }
+                        previous = Some(snapshot);
+                    }
+                    Err(e) => {
+                        warn!("failed to take a display snapshot. {}", e);
+                        let fatal = !e.is_recoverable();
+                        let _ = event_tx.unbounded_send(Err(e));
+                        if fatal {
+                            break;
+                        }
+                    }
+                }
+                if event_tx.is_closed() {
+                    break;
+                }
+                match stop_rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            trace!("exiting display watcher thread");
+        }).map_err(|e| DDApiError::unexpected(format!("failed to spawn display watcher thread. {:?}", e)))?;
+
+        Ok(Self {
+            events,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// return the next queued event without waiting.
+    pub fn try_recv(&mut self) -> Option<Result<DisplayEvent>> {
+        self.events.try_recv().ok()
+    }
+
+    /// stop watching and join the watcher thread. events that were already queued can still be
+    /// received.
+    pub fn stop(&mut self) {
+        // dropping the sender wakes the thread right away.
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for DisplayWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Stream for DisplayWatcher {
+    type Item = Result<DisplayEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
@@ -0,0 +1,440 @@
+//! Provides [CaptureWorker], a dedicated capture thread that owns a [FrameSource] and delivers
+//! frames through a bounded [frame channel][crate::channel].
+//!
+//! [DesktopDuplicationApi][crate::DesktopDuplicationApi] works best when it lives on a single
+//! "graphics thread". The worker creates the source on its own thread, paces acquisition and
+//! pushes frames to the consumer following a [QueuePolicy].
+//!
+//! # Example
+//! ```
+//! use win_desktop_duplication::DesktopDuplicationApi;
+//! use win_desktop_duplication::worker::{CaptureWorker, CaptureWorkerOptions};
+//!
+//! let worker = CaptureWorker::spawn(CaptureWorkerOptions::default(), move || {
+//!     DesktopDuplicationApi::new(adapter, output)
+//! })?;
+//!
+//! while let Some(frame) = worker.recv() {
+//!     let tex = frame?;
+//!     // use the texture here
+//! }
+//! ```
+
+use std::sync::mpsc;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::{Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+use futures::executor::block_on;
+use futures::future::poll_fn;
+use log::{debug, trace, warn};
+
+use crate::{DDApiError, Result};
+use crate::channel::{frame_channel, FrameReceiver, FrameSender, QueuePolicy, TryRecvError};
+use crate::stream::{ErrorPolicy, FramePacing, FrameSource};
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use crate::{DDApiError, Result};
+    use crate::errors::ErrorKind;
+    use crate::channel::QueuePolicy;
+    use crate::stream::{ErrorPolicy, FramePacing, FrameSource};
+    use crate::worker::{CaptureWorker, CaptureWorkerOptions};
+
+    // synthetic source producing increasing frame numbers. every `fail_every`th frame fails with
+    // AccessLost.
+    struct CountingSource {
+        next: u32,
+        fail_every: u32,
+        acquired: Arc<AtomicU32>,
+    }
+
+    impl FrameSource for CountingSource {
+        type Frame = u32;
+
+        fn acquire_next_frame(&mut self, _timeout: Duration) -> Result<u32> {
+            self.next += 1;
+            self.acquired.fetch_add(1, Ordering::AcqRel);
+            if self.fail_every != 0 && self.next.is_multiple_of(self.fail_every) {
+                Err(ErrorKind::AccessLost.into())
+            } else {
+                Ok(self.next)
+            }
+        }
+    }
+
+    fn options(policy: QueuePolicy, error_policy: ErrorPolicy) -> CaptureWorkerOptions {
+        CaptureWorkerOptions {
+            pacing: FramePacing::Interval(Duration::from_millis(2)),
+            queue_capacity: 2,
+            queue_policy: policy,
+            error_policy,
+            ..Default::default()
+        }
+    }
+
+    fn spawn(opt: CaptureWorkerOptions, fail_every: u32) -> (CaptureWorker<CountingSource>, Arc<AtomicU32>) {
+        let acquired = Arc::new(AtomicU32::new(0));
+        let counter = acquired.clone();
+        let worker = CaptureWorker::spawn(opt, move || Ok(CountingSource {
+            next: 0,
+            fail_every,
+            acquired: counter,
+        })).unwrap();
+        (worker, acquired)
+    }
+
+    #[test]
+    fn test_frames_in_order() {
+        let (worker, _) = spawn(options(QueuePolicy::Block, ErrorPolicy::Yield), 0);
+        for i in 1..10 {
+            assert_eq!(worker.recv().unwrap().unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn test_skip_recoverable() {
+        let (worker, _) = spawn(options(QueuePolicy::Block, ErrorPolicy::SkipRecoverable), 3);
+        let frames: Vec<u32> = (0..4).map(|_| worker.recv().unwrap().unwrap()).collect();
+        assert_eq!(frames, vec![1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn test_latest_only() {
+        let (worker, acquired) = spawn(options(QueuePolicy::LatestOnly, ErrorPolicy::Yield), 0);
+        std::thread::sleep(Duration::from_millis(50));
+        let frame = worker.recv().unwrap().unwrap();
+        // older frames were replaced while nobody was receiving.
+        assert!(frame > 1);
+        assert!(worker.dropped() > 0);
+        assert!(acquired.load(Ordering::Acquire) >= frame);
+    }
+
+    #[test]
+    fn test_reconfigure_and_stop() {
+        let (mut worker, _) = spawn(options(QueuePolicy::Block, ErrorPolicy::Yield), 0);
+        assert_eq!(worker.recv().unwrap().unwrap(), 1);
+        worker.reconfigure(|source| source.next = 100);
+        worker.set_pacing(FramePacing::Unpaced);
+        // frames queued before the command are still delivered.
+        let frame = (0..4).map(|_| worker.recv().unwrap().unwrap()).find(|f| *f > 100);
+        assert!(frame.is_some());
+        worker.stop();
+        assert!(worker.is_finished());
+    }
+
+    #[test]
+    fn test_failed_factory() {
+        let worker = CaptureWorker::<CountingSource>::spawn(Default::default(), || Err(ErrorKind::Unsupported.into()));
+        assert!(matches!(worker.err(), Some(e) if e.kind() == ErrorKind::Unsupported));
+    }
+
+    #[test]
+    fn test_fatal_error_stops_worker() {
+        struct FailingSource;
+        impl FrameSource for FailingSource {
+            type Frame = ();
+            fn acquire_next_frame(&mut self, _timeout: Duration) -> Result<()> {
+                Err(DDApiError::unexpected("device removed"))
+            }
+        }
+        let worker = CaptureWorker::spawn(options(QueuePolicy::Block, ErrorPolicy::SkipRecoverable),
+                                          || Ok(FailingSource)).unwrap();
+        assert!(matches!(worker.recv(), Some(Err(e)) if e.kind() == ErrorKind::Unexpected));
+        assert!(worker.recv().is_none());
+    }
+}
+
+/// Settings for [CaptureWorker].
+#[derive(Clone, Debug)]
+pub struct CaptureWorkerOptions {
+    /// decides when the worker acquires the next frame.
+    pub pacing: FramePacing,
+    /// timeout passed to [FrameSource::acquire_next_frame]. with [FramePacing::Unpaced] this is
+    /// what paces the worker, so it should be non-zero.
+    pub acquire_timeout: Duration,
+    /// maximum number of frames waiting to be received.
+    pub queue_capacity: usize,
+    /// what happens when the consumer doesn't keep up.
+    pub queue_policy: QueuePolicy,
+    /// whether recoverable errors are delivered to the consumer.
+    pub error_policy: ErrorPolicy,
+    /// name of the capture thread.
+    pub thread_name: String,
+}
+
+impl Default for CaptureWorkerOptions {
+    fn default() -> Self {
+        Self {
+            pacing: FramePacing::VSync,
+            acquire_timeout: Duration::from_millis(0),
+            queue_capacity: 2,
+            queue_policy: QueuePolicy::DropOldest,
+            error_policy: ErrorPolicy::Yield,
+            thread_name: "capture_thread".to_owned(),
+        }
+    }
+}
+
+enum Command<S> {
+    Configure(Box<dyn FnOnce(&mut S) + Send>),
+    SetPacing(FramePacing),
+    SetErrorPolicy(ErrorPolicy),
+    Stop,
+}
+
+/// Dedicated capture thread that owns a [FrameSource] and delivers frames through a bounded
+/// channel.
+///
+/// The worker stops when [stop][CaptureWorker::stop] is called, when it is dropped, or after
+/// delivering a non-recoverable error. once stopped, [recv][CaptureWorker::recv] returns the
+/// remaining queued frames and then `None`.
+pub struct CaptureWorker<S: FrameSource> {
+    commands: Sender<Command<S>>,
+    frames: FrameReceiver<Result<S::Frame>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<S: FrameSource + 'static> CaptureWorker<S>
+    where S::Frame: Send + 'static {
+    /// spawn a new capture thread. `factory` is called on the new thread to create the source,
+    /// so sources that must be created on the thread that uses them (like
+    /// [DesktopDuplicationApi][crate::DesktopDuplicationApi]) work as expected.
+    ///
+    /// this blocks until the source is created and fails with the error returned by `factory`.
+    pub fn spawn<F>(options: CaptureWorkerOptions, factory: F) -> Result<Self>
+        where F: FnOnce() -> Result<S> + Send + 'static {
+        let (frame_tx, frame_rx) = frame_channel(options.queue_capacity, options.queue_policy);
+        let (cmd_tx, cmd_rx) = channel();
+        let (init_tx, init_rx) = channel();
+
+        let handle = Builder::new().name(options.thread_name.clone()).spawn(move || {
+            let source = match factory() {
+                Ok(source) => {
+                    let _ = init_tx.send(Ok(()));
+                    source
+                }
+                Err(e) => {
+                    let _ = init_tx.send(Err(e));
+                    return;
+                }
+            };
+            WorkerLoop {
+                source,
+                pacing: options.pacing,
+                error_policy: options.error_policy,
+                acquire_timeout: options.acquire_timeout,
+                next_tick: Instant::now(),
+                commands: cmd_rx,
+                frames: frame_tx,
+            }.run();
+            trace!("exiting capture worker thread");
+        }).map_err(|e| DDApiError::unexpected(format!("failed to spawn capture thread. {:?}", e)))?;
+
+        match init_rx.recv() {
+            Ok(Ok(())) => Ok(Self {
+                commands: cmd_tx,
+                frames: frame_rx,
+                handle: Some(handle),
+            }),
+            Ok(Err(e)) => {
+                let _ = handle.join();
+                Err(e)
+            }
+            Err(_) => {
+                let _ = handle.join();
+                Err(DDApiError::unexpected("capture thread exited before creating source"))
+            }
+        }
+    }
+}
+
+impl<S: FrameSource> CaptureWorker<S> {
+    /// wait for the next frame. returns `None` once the worker stopped and all queued frames were
+    /// received.
+    pub fn recv(&self) -> Option<Result<S::Frame>> {
+        self.frames.recv()
+    }
+
+    /// wait at most `timeout` for the next frame.
+    pub fn recv_timeout(&self, timeout: Duration) -> core::result::Result<Result<S::Frame>, TryRecvError> {
+        self.frames.recv_timeout(timeout)
+    }
+
+    /// return the next frame if one is queued without blocking.
+    pub fn try_recv(&self) -> core::result::Result<Result<S::Frame>, TryRecvError> {
+        self.frames.try_recv()
+    }
+
+    /// number of frames dropped so far because the consumer didn't keep up.
+    pub fn dropped(&self) -> u64 {
+        self.frames.dropped()
+    }
+
+    /// run `f` on the capture thread with mutable access to the source. use this to reconfigure
+    /// the source while it's running.
+    ///
+    /// ```
+    /// worker.reconfigure(|dupl| dupl.configure(DuplicationApiOptions {
+    ///     skip_cursor: true,
+    ///     ..Default::default()
+    /// }));
+    /// ```
+    pub fn reconfigure<F>(&self, f: F)
+        where F: FnOnce(&mut S) + Send + 'static {
+        let _ = self.commands.send(Command::Configure(Box::new(f)));
+    }
+
+    /// change the pacing of the worker.
+    pub fn set_pacing(&self, pacing: FramePacing) {
+        let _ = self.commands.send(Command::SetPacing(pacing));
+    }
+
+    /// change how the worker handles recoverable errors.
+    pub fn set_error_policy(&self, policy: ErrorPolicy) {
+        let _ = self.commands.send(Command::SetErrorPolicy(policy));
+    }
+
+    /// returns true if the capture thread exited.
+    pub fn is_finished(&self) -> bool {
+        self.handle.as_ref().is_none_or(|h| h.is_finished())
+    }
+
+    /// signal the capture thread to stop and wait for it to exit. queued frames can still be
+    /// received after this.
+    pub fn stop(&mut self) {
+        let _ = self.commands.send(Command::Stop);
+        // wakes up the thread if it's blocked on a full queue.
+        self.frames.close();
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                warn!("capture thread panicked");
+            }
+        }
+    }
+}
+
+impl<S: FrameSource> Drop for CaptureWorker<S> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct WorkerLoop<S: FrameSource> {
+    source: S,
+    pacing: FramePacing,
+    error_policy: ErrorPolicy,
+    acquire_timeout: Duration,
+    next_tick: Instant,
+    commands: Receiver<Command<S>>,
+    frames: FrameSender<Result<S::Frame>>,
+}
+
+impl<S: FrameSource> WorkerLoop<S> {
+    fn run(mut self) {
+        loop {
+            if !self.handle_commands() || !self.frames.is_connected() {
+                return;
+            }
+            match self.wait_for_tick() {
+                Tick::Ready => {}
+                Tick::Command => continue,
+                Tick::Stop => return,
+                Tick::Failed(e) => {
+                    let _ = self.frames.send(Err(e));
+                    return;
+                }
+            }
+
+            let res = self.source.acquire_next_frame(self.acquire_timeout);
+            let fatal = match &res {
+                Ok(_) => false,
+                Err(e) if e.is_recoverable() => {
+                    if self.error_policy == ErrorPolicy::SkipRecoverable {
+                        trace!("capture worker skipping recoverable error {:?}", e);
+                        continue;
+                    }
+                    false
+                }
+                Err(e) => {
+                    debug!("capture worker stopping after fatal error {:?}", e);
+                    true
+                }
+            };
+            if self.frames.send(res).is_err() || fatal {
+                return;
+            }
+        }
+    }
+
+    // applies pending commands. returns false if the worker should stop.
+    fn handle_commands(&mut self) -> bool {
+        loop {
+            match self.commands.try_recv() {
+                Ok(cmd) => {
+                    if !self.apply(cmd) {
+                        return false;
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => return true,
+                Err(mpsc::TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    fn apply(&mut self, cmd: Command<S>) -> bool {
+        match cmd {
+            Command::Configure(f) => f(&mut self.source),
+            Command::SetPacing(pacing) => {
+                self.pacing = pacing;
+                self.next_tick = Instant::now();
+            }
+            Command::SetErrorPolicy(policy) => self.error_policy = policy,
+            Command::Stop => return false,
+        }
+        true
+    }
+
+    fn wait_for_tick(&mut self) -> Tick {
+        match self.pacing {
+            FramePacing::VSync => {
+                match block_on(poll_fn(|cx| self.source.poll_vsync(cx))) {
+                    Some(Ok(())) => Tick::Ready,
+                    Some(Err(e)) => Tick::Failed(e),
+                    None => Tick::Stop,
+                }
+            }
+            FramePacing::Interval(period) => {
+                // wait for commands until the next tick is due so stop requests are not delayed.
+                let now = Instant::now();
+                if now < self.next_tick {
+                    return match self.commands.recv_timeout(self.next_tick - now) {
+                        Ok(cmd) => if self.apply(cmd) { Tick::Command } else { Tick::Stop },
+                        Err(RecvTimeoutError::Timeout) => {
+                            self.next_tick += period;
+                            Tick::Ready
+                        }
+                        Err(RecvTimeoutError::Disconnected) => Tick::Stop,
+                    };
+                }
+                // skip missed ticks
+                while self.next_tick <= now {
+                    self.next_tick += period.max(Duration::from_micros(1));
+                }
+                Tick::Ready
+            }
+            FramePacing::Unpaced => Tick::Ready,
+        }
+    }
+}
+
+enum Tick {
+    Ready,
+    Command,
+    Stop,
+    Failed(DDApiError),
+}